@@ -1,29 +1,88 @@
-use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId, async_executor::FuturesExecutor};
-use Nautilus_Core::record::{PeerManagement, PeerRecord};
+use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId};
+use Nautilus_Core::record::{MemoryStore, PeerEvent, PeerManagement, PeerQuery, PeerRecord};
 use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+const READERS: usize = 4;
+const WRITERS: usize = 4;
+const OPS_PER_TASK: usize = 2_000;
+
+fn record(i: usize) -> PeerRecord {
+    PeerRecord {
+        peer_id: Some(format!("peer{}", i)),
+        is_active: i.is_multiple_of(2),
+        ..PeerRecord::new(SocketAddr::from(([10, (i >> 16) as u8, (i >> 8) as u8, i as u8], 9000)))
+    }
+}
+
+async fn populated(peers: usize) -> PeerManagement {
+    let peer_manager = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+    for i in 0..peers {
+        peer_manager.add_or_update_peer(record(i)).await;
+    }
+    peer_manager
+}
+
+/// Readers look peers up by ID while writers update scores and last-seen times.
+async fn mixed_load(peer_manager: &PeerManagement, peers: usize) {
+    let mut tasks = Vec::new();
+    for task in 0..READERS + WRITERS {
+        let peer_manager = peer_manager.clone();
+        tasks.push(tokio::spawn(async move {
+            for op in 0..OPS_PER_TASK {
+                let key = format!("peer{}", (task * 7919 + op * 104_729) % peers);
+                if task < READERS {
+                    criterion::black_box(peer_manager.get_peer(&key).await);
+                } else if op % 2 == 0 {
+                    peer_manager.report(&key, PeerEvent::UsefulMessage).await;
+                } else {
+                    peer_manager.mark_seen(&key).await;
+                }
+            }
+        }));
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+}
 
 pub fn peer_management_benchmark(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
     let mut group = c.benchmark_group("Peer Management");
 
-    // Benchmark with asynchronous code
     group.bench_with_input(BenchmarkId::new("Add and Remove", "peers"), &(), |b, _| {
-        b.to_async(FuturesExecutor).iter(|| async {
+        b.to_async(&runtime).iter(|| async {
             let peer_manager = PeerManagement::new("test_peers_benchmark.json".to_string());
 
-            let peer = PeerRecord {
-                addr: "127.0.0.1:8000".parse::<SocketAddr>().unwrap(),
-                peer_id: Some("peer_benchmark".to_string()),
-                public_key: None,
-                is_active: true,
-                last_seen: None,
-            };
-
             // Add and immediately remove a peer to test performance
-            peer_manager.add_or_update_peer(peer.clone()).await;
-            peer_manager.remove_peer("peer_benchmark").await;
+            peer_manager.add_or_update_peer(record(0)).await;
+            peer_manager.remove_peer("peer0").await;
         });
     });
 
+    group.sample_size(10);
+    for peers in [10_000, 100_000] {
+        let peer_manager = runtime.block_on(populated(peers));
+
+        group.bench_with_input(BenchmarkId::new("Concurrent readers and writers", peers), &peers, |b, &peers| {
+            b.to_async(&runtime).iter(|| mixed_load(&peer_manager, peers));
+        });
+
+        // Whole-table reads running alongside the same writers
+        group.bench_with_input(BenchmarkId::new("Snapshot query under writes", peers), &peers, |b, &peers| {
+            b.to_async(&runtime).iter(|| async {
+                let writes = mixed_load(&peer_manager, peers);
+                let reads = async {
+                    for _ in 0..READERS {
+                        criterion::black_box(peer_manager.count(&PeerQuery::new().active(true)).await);
+                    }
+                };
+                tokio::join!(writes, reads);
+            });
+        });
+    }
+
     group.finish();
 }
 
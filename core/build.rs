@@ -0,0 +1,31 @@
+//build.rs
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+fn main() {
+    let proto_dir = env::current_dir()
+        .expect("Failed to get current directory")
+        .join("protocols");
+
+    // Every schema lives in a versioned protobuf package, so each one is
+    // generated into its own `<package>.rs` file in OUT_DIR.
+    let proto_files: Vec<PathBuf> = find_proto_files(&proto_dir)
+        .expect("Failed to find proto files")
+        .collect();
+
+    prost_build::compile_protos(&proto_files, &[&proto_dir])
+        .expect("Failed to compile .proto files");
+
+    // Ensure rebuild when .proto files change
+    println!("cargo:rerun-if-changed={}", proto_dir.display());
+}
+
+/// Find all `.proto` files in a directory
+fn find_proto_files(dir: &Path) -> io::Result<impl Iterator<Item = PathBuf>> {
+    Ok(fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "proto")))
+}
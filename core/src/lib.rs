@@ -1,3 +1,11 @@
 //lib.rs
 pub mod transport;
 pub mod record;
+
+mod log_config;
+#[cfg(feature = "logging")]
+pub use log_config::setup_logging;
+
+pub mod proto { // Wire schema for every core message, see protocols/*.proto
+  include!(concat!(env!("OUT_DIR"), "/nautilus.core.v1.rs"));
+}
@@ -0,0 +1,11 @@
+//log_config.rs
+
+/// Install the workspace `logger` subscriber so the `tracing` spans and events
+/// emitted by core end up in the configured log files.
+#[cfg(feature = "logging")]
+pub fn setup_logging(config_path: Option<&str>) {
+    match config_path {
+        Some(path) => logger::init_logging!(path),
+        None => logger::init_logging!(),
+    }
+}
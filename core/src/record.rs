@@ -1,6 +1,26 @@
 // record.rs
+mod ban_list;
+mod peer_addr;
 mod peer_record;
 mod peer_management;
+mod peer_store;
+mod record_error;
+mod reputation;
+mod signed_record;
 
-pub use peer_management::PeerManagement;
-pub use peer_record::PeerRecord;
\ No newline at end of file
+pub use peer_management::{
+    changed_fields, AddrFamily, AutosaveConfig, ExpiryReason, ExportFormat, ImportSummary, MaintenanceConfig,
+    MergeStrategy, PeerChange, PeerExpired, PeerField, PeerManagement, PeerQuery, QueryPage, SortKey,
+    EXPORT_FORMAT_VERSION,
+};
+pub use ban_list::{Ban, BanList, BanTarget, Subnet, BAN_FILE_VERSION};
+pub use peer_store::{JsonFileStore, MemoryStore, PeerStore, CACHE_FORMAT_VERSION};
+#[cfg(feature = "sled_store")]
+pub use peer_store::SledStore;
+pub use peer_addr::{AddrSource, AddrTransport, PeerAddr};
+pub use peer_record::{PeerHistory, PeerRecord};
+pub use record_error::RecordError;
+pub use reputation::{PeerEvent, Reputation, ScoreConfig};
+pub use signed_record::SignedPeerRecord;
+#[cfg(feature = "identity_integration")]
+pub(crate) use signed_record::peer_id_matches_key;
\ No newline at end of file
@@ -0,0 +1,324 @@
+// ban_list.rs
+//? Peers, addresses and subnets this node refuses to talk to, until the ban
+//? expires or is lifted
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::json;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// Version written in the header of ban files.
+pub const BAN_FILE_VERSION: u32 = 1;
+
+/// An IP network written `addr/prefix`, e.g. `10.0.0.0/8` or `2001:db8::/32`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Subnet {
+    addr: IpAddr, // Host bits are always zero
+    prefix: u8,
+}
+
+impl Subnet {
+    /// The network of `addr` with a `prefix` bit mask. `None` if the prefix
+    /// is longer than the address.
+    pub fn new(addr: IpAddr, prefix: u8) -> Option<Self> {
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        (prefix <= bits).then(|| Self {
+            addr: mask(addr, prefix),
+            prefix,
+        })
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// Whether `ip` is in this network. IPv4 addresses mapped into IPv6
+    /// count as IPv4.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        ip.is_ipv4() == self.addr.is_ipv4() && mask(ip, self.prefix) == self.addr
+    }
+}
+
+fn mask(addr: IpAddr, prefix: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(v4) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            IpAddr::V4((u32::from(v4) & mask).into())
+        }
+        IpAddr::V6(v6) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            IpAddr::V6((u128::from(v6) & mask).into())
+        }
+    }
+}
+
+impl FromStr for Subnet {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || io::Error::new(io::ErrorKind::InvalidInput, format!("bad subnet {:?}", s));
+        let (addr, prefix) = s.split_once('/').ok_or_else(bad)?;
+        let addr = addr.parse().map_err(|_| bad())?;
+        let prefix = prefix.parse().map_err(|_| bad())?;
+        Self::new(addr, prefix).ok_or_else(bad)
+    }
+}
+
+impl fmt::Display for Subnet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl Serialize for Subnet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Subnet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// What a ban applies to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BanTarget {
+    PeerId(String),
+    Ip(IpAddr),
+    Subnet(Subnet),
+}
+
+/// Reads `10.0.0.0/8` as a subnet, `10.0.0.1` as an IP and anything else as a peer ID.
+impl FromStr for BanTarget {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains('/') {
+            return s.parse().map(BanTarget::Subnet);
+        }
+        if let Ok(ip) = s.parse::<IpAddr>() {
+            return Ok(BanTarget::Ip(ip.to_canonical()));
+        }
+        if s.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty ban target"));
+        }
+        Ok(BanTarget::PeerId(s.to_string()))
+    }
+}
+
+impl fmt::Display for BanTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BanTarget::PeerId(peer_id) => write!(f, "{}", peer_id),
+            BanTarget::Ip(ip) => write!(f, "{}", ip),
+            BanTarget::Subnet(subnet) => write!(f, "{}", subnet),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ban {
+    pub target: BanTarget,
+    pub reason: String,
+    #[serde(default, with = "super::peer_record::unix_ms")]
+    pub expires: Option<SystemTime>, // `None` lasts until lifted
+}
+
+impl Ban {
+    /// A ban lasting `duration` from now, or until lifted.
+    pub fn new(target: BanTarget, reason: impl Into<String>, duration: Option<Duration>) -> Self {
+        Self {
+            target,
+            reason: reason.into(),
+            expires: duration.map(|duration| SystemTime::now() + duration),
+        }
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+}
+
+#[derive(Deserialize)]
+struct BanFile {
+    version: u32,
+    bans: Vec<Ban>,
+}
+
+/// Bans split by kind, so the per-connection checks are map lookups.
+#[derive(Default)]
+struct Bans {
+    peers: HashMap<String, Ban>,
+    ips: HashMap<IpAddr, Ban>,
+    subnets: Vec<Ban>,
+}
+
+impl Bans {
+    fn insert(&mut self, ban: Ban) {
+        match &ban.target {
+            BanTarget::PeerId(peer_id) => {
+                self.peers.insert(peer_id.clone(), ban);
+            }
+            BanTarget::Ip(ip) => {
+                self.ips.insert(ip.to_canonical(), ban);
+            }
+            BanTarget::Subnet(_) => {
+                self.subnets.retain(|known| known.target != ban.target);
+                self.subnets.push(ban);
+            }
+        }
+    }
+
+    fn remove(&mut self, target: &BanTarget) -> Option<Ban> {
+        match target {
+            BanTarget::PeerId(peer_id) => self.peers.remove(peer_id),
+            BanTarget::Ip(ip) => self.ips.remove(&ip.to_canonical()),
+            BanTarget::Subnet(_) => {
+                let index = self.subnets.iter().position(|known| known.target == *target)?;
+                Some(self.subnets.remove(index))
+            }
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Ban> {
+        self.peers.values().chain(self.ips.values()).chain(&self.subnets)
+    }
+}
+
+/// The bans of one node, shared by its transports the way `Throttle` is:
+/// clones see and change the same list. Expired bans stop applying on their
+/// own and are dropped the next time the list is written.
+#[derive(Clone, Default)]
+pub struct BanList {
+    bans: Arc<RwLock<Bans>>,
+    path: Option<Arc<PathBuf>>, // Where bans are kept between runs; `None` keeps them in memory
+    writes: Arc<Mutex<()>>,     // Keeps file writes in order
+}
+
+impl BanList {
+    /// A ban list that is not persisted.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A ban list persisted as JSON at `path`, starting with the bans saved
+    /// there. A missing file is an empty list.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let mut bans = Bans::default();
+        let now = SystemTime::now();
+        for ban in read(&path)?.into_iter().filter(|ban| !ban.is_expired(now)) {
+            bans.insert(ban);
+        }
+        Ok(Self {
+            bans: Arc::new(RwLock::new(bans)),
+            path: Some(Arc::new(path)),
+            writes: Arc::default(),
+        })
+    }
+
+    /// Add a ban, replacing any ban on the same target, and persist the list.
+    pub async fn ban(&self, ban: Ban) -> io::Result<()> {
+        info!(target = %ban.target, reason = %ban.reason, expires = ?ban.expires, "ban added");
+        self.bans.write().unwrap().insert(ban);
+        self.save().await
+    }
+
+    /// Lift the ban on `target` and persist the list. Returns the ban lifted, if any.
+    pub async fn lift(&self, target: &BanTarget) -> io::Result<Option<Ban>> {
+        let lifted = self.bans.write().unwrap().remove(target);
+        if lifted.is_some() {
+            info!(target = %target, "ban lifted");
+            self.save().await?;
+        }
+        Ok(lifted)
+    }
+
+    /// Every ban still in force.
+    pub fn list(&self) -> Vec<Ban> {
+        let now = SystemTime::now();
+        self.bans.read().unwrap().iter().filter(|ban| !ban.is_expired(now)).cloned().collect()
+    }
+
+    /// The ban in force on a peer ID, if any.
+    pub fn peer_ban(&self, peer_id: &str) -> Option<Ban> {
+        let bans = self.bans.read().unwrap();
+        bans.peers.get(peer_id).filter(|ban| !ban.is_expired(SystemTime::now())).cloned()
+    }
+
+    /// The ban in force on an IP, directly or through its subnet, if any.
+    pub fn ip_ban(&self, ip: IpAddr) -> Option<Ban> {
+        let now = SystemTime::now();
+        let ip = ip.to_canonical();
+        let bans = self.bans.read().unwrap();
+        let subnet = || {
+            bans.subnets.iter().find(|ban| {
+                matches!(&ban.target, BanTarget::Subnet(subnet) if subnet.contains(ip)) && !ban.is_expired(now)
+            })
+        };
+        bans.ips.get(&ip).filter(|ban| !ban.is_expired(now)).or_else(subnet).cloned()
+    }
+
+    async fn save(&self) -> io::Result<()> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+        let _ordered = self.writes.lock().await;
+        let bans = self.list();
+        tokio::task::spawn_blocking(move || write(&path, &bans))
+            .await
+            .map_err(io::Error::other)?
+    }
+}
+
+fn read(path: &Path) -> io::Result<Vec<Ban>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let file: BanFile = serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if file.version > BAN_FILE_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("ban file version {} is newer than supported version {}", file.version, BAN_FILE_VERSION),
+        ));
+    }
+    Ok(file.bans)
+}
+
+/// Write to a temporary file and rename it over the list, so a crash
+/// mid-write never leaves a truncated one behind.
+fn write(path: &Path, bans: &[Ban]) -> io::Result<()> {
+    let serialized = serde_json::to_string_pretty(&json!({
+        "version": BAN_FILE_VERSION,
+        "bans": bans,
+    }))?;
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let result = File::create(&temp).and_then(|mut file| {
+        file.write_all(serialized.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(e) = result.and_then(|_| fs::rename(&temp, path)) {
+        warn!(ban_file = %path.display(), error.kind = ?e.kind(), error = %e, "failed to write ban list");
+        return Err(e);
+    }
+    Ok(())
+}
@@ -0,0 +1,155 @@
+// peer_addr.rs
+//? One of the addresses a peer can be reached at, with where it came from and how far to trust it
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AddrTransport {
+    #[default]
+    Tcp,
+    Udp,
+}
+
+/// How this node learned an address.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AddrSource {
+    #[default]
+    Manual,       // Added by the application; never expires
+    Dial,         // This node connected to it and got a handshake back
+    Announced,    // The peer said in its handshake that it listens here
+    Observed,     // Traffic arrived from it
+    PeerExchange, // Another peer vouched for it
+    Discovery,    // Found by a discovery mechanism
+}
+
+impl AddrSource {
+    /// How long an address from this source stays valid without being confirmed.
+    pub fn default_ttl(self) -> Option<Duration> {
+        match self {
+            AddrSource::Manual => None,
+            AddrSource::Dial => Some(Duration::from_secs(7 * 24 * 60 * 60)),
+            AddrSource::Announced => Some(Duration::from_secs(24 * 60 * 60)),
+            AddrSource::Observed => Some(Duration::from_secs(10 * 60)),
+            AddrSource::PeerExchange => Some(Duration::from_secs(60 * 60)),
+            AddrSource::Discovery => Some(Duration::from_secs(30 * 60)),
+        }
+    }
+
+    /// Starting confidence, from 0 (unlikely to work) to 1 (known to work).
+    pub fn default_confidence(self) -> f64 {
+        match self {
+            AddrSource::Manual => 0.5,
+            AddrSource::Dial => 1.0,
+            AddrSource::Announced => 0.7,
+            AddrSource::Observed => 0.3,
+            AddrSource::PeerExchange => 0.4,
+            AddrSource::Discovery => 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PeerAddr {
+    pub addr: SocketAddr,
+    #[serde(default)]
+    pub transport: AddrTransport,
+    #[serde(default)]
+    pub source: AddrSource,
+    #[serde(default, with = "super::peer_record::unix_ms")]
+    pub expires: Option<SystemTime>, // `None` never expires
+    pub confidence: f64,
+    #[serde(default)]
+    pub successes: u32,
+    #[serde(default)]
+    pub failures: u32,
+    #[serde(default, with = "super::peer_record::unix_ms")]
+    pub last_success: Option<SystemTime>,
+    #[serde(default)]
+    pub learned_from: Option<String>, // Key of the peer that first vouched for it, for relayed addresses
+}
+
+impl PeerAddr {
+    /// An address with the TTL and confidence its source defaults to.
+    pub fn new(addr: SocketAddr, transport: AddrTransport, source: AddrSource) -> Self {
+        Self {
+            addr,
+            transport,
+            source,
+            expires: source.default_ttl().map(|ttl| SystemTime::now() + ttl),
+            confidence: source.default_confidence(),
+            successes: 0,
+            failures: 0,
+            last_success: None,
+            learned_from: None,
+        }
+    }
+
+    pub fn tcp(addr: SocketAddr, source: AddrSource) -> Self {
+        Self::new(addr, AddrTransport::Tcp, source)
+    }
+
+    pub fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.expires = ttl.map(|ttl| SystemTime::now() + ttl);
+        self
+    }
+
+    pub fn with_learned_from(mut self, peer_key: &str) -> Self {
+        self.learned_from = Some(peer_key.to_string());
+        self
+    }
+
+    pub fn with_confidence(mut self, confidence: f64) -> Self {
+        self.confidence = confidence.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Whether this is the same endpoint as `other`.
+    pub fn same_endpoint(&self, other: &PeerAddr) -> bool {
+        self.addr == other.addr && self.transport == other.transport
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Fold in a fresh sighting of the same endpoint: keep the later expiry,
+    /// the higher confidence and the more trustworthy source.
+    pub fn merge(&mut self, other: &PeerAddr) {
+        self.expires = match (self.expires, other.expires) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            _ => None,
+        };
+        self.confidence = self.confidence.max(other.confidence);
+        if other.source.default_confidence() > self.source.default_confidence() {
+            self.source = other.source;
+        }
+        if self.learned_from.is_none() {
+            self.learned_from = other.learned_from.clone();
+        }
+    }
+
+    /// A connection over this address worked: trust it fully and push its
+    /// expiry out by its source's TTL again.
+    pub fn record_success(&mut self, now: SystemTime) {
+        self.successes += 1;
+        self.last_success = Some(now);
+        self.confidence = 1.0;
+        if let Some(ttl) = self.source.default_ttl() {
+            self.expires = Some(self.expires.map_or(now + ttl, |expires| expires.max(now + ttl)));
+        }
+    }
+
+    /// A dial over this address failed: halve the confidence in it.
+    pub fn record_failure(&mut self) {
+        self.failures += 1;
+        self.confidence /= 2.0;
+    }
+
+    /// Ordering key for dialing: confidence first, then the success ratio.
+    pub fn rank(&self) -> f64 {
+        let attempts = (self.successes + self.failures) as f64;
+        let success_rate = if attempts == 0.0 { 0.5 } else { self.successes as f64 / attempts };
+        self.confidence + success_rate / 10.0
+    }
+}
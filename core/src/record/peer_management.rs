@@ -1,127 +1,458 @@
 // Peer_management.rs
 
-use crate::record::peer_record::PeerRecord;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io::{self, Read, Write};
+use crate::record::peer_addr::{AddrTransport, PeerAddr};
+#[cfg(feature = "identity_integration")]
+use crate::record::{peer_addr::AddrSource, record_error::RecordError, signed_record::SignedPeerRecord};
+use crate::record::peer_record::{PeerHistory, PeerRecord};
+use crate::record::peer_store::{JsonFileStore, PeerStore};
+use crate::record::reputation::{PeerEvent, Reputation, ScoreConfig};
+use std::io;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::SystemTime;
+use tokio::sync::{broadcast, Mutex};
+use tracing::{debug, error, trace, warn};
+
+mod autosave;
+mod changes;
+mod export;
+mod maintenance;
+mod peer_table;
+mod query;
+
+pub use autosave::AutosaveConfig;
+pub use changes::{changed_fields, PeerChange, PeerField};
+pub use export::{ExportFormat, ImportSummary, MergeStrategy, EXPORT_FORMAT_VERSION};
+pub use maintenance::{ExpiryReason, MaintenanceConfig, PeerExpired};
+pub use query::{AddrFamily, PeerQuery, QueryPage, SortKey};
+use autosave::Dirty;
+use peer_table::PeerTable;
 
 #[derive(Clone)]
 pub struct PeerManagement {
-    known_peers: Arc<Mutex<HashMap<String, PeerRecord>>>, // Peer records keyed by Peer ID
-    cache_file: String,                                  // Path to cache file
+    known_peers: Arc<PeerTable>,               // Peer records keyed by Peer ID
+    store: Arc<dyn PeerStore>,                 // Where records are persisted
+    store_writes: Arc<Mutex<()>>,              // Keeps store operations in order
+    scoring: Arc<ScoreConfig>,                 // Event weights and score thresholds
+    maintenance: Arc<MaintenanceConfig>,       // Expiry and size limits
+    expiry_tx: broadcast::Sender<PeerExpired>, // Peers expired by maintenance
+    change_tx: broadcast::Sender<PeerChange>,  // Every change to the table
+    autosave: Option<Arc<AutosaveConfig>>,     // `None` writes changes to the store as they are made
+    dirty: Arc<Dirty>,                         // Keys autosave has yet to write
 }
 
 impl PeerManagement {
-    /// Create a new PeerManagement instance
+    /// Create a new PeerManagement instance backed by a JSON cache file
     pub fn new(cache_file: String) -> Self {
+        Self::with_store(Arc::new(JsonFileStore::new(cache_file)))
+    }
+
+    /// Create a PeerManagement instance backed by any store
+    pub fn with_store(store: Arc<dyn PeerStore>) -> Self {
         Self {
-            known_peers: Arc::new(Mutex::new(HashMap::new())),
-            cache_file,
+            known_peers: Arc::new(PeerTable::default()),
+            store,
+            store_writes: Arc::new(Mutex::new(())),
+            scoring: Arc::new(ScoreConfig::default()),
+            maintenance: Arc::new(MaintenanceConfig::default()),
+            expiry_tx: broadcast::channel(256).0,
+            change_tx: broadcast::channel(1024).0,
+            autosave: None,
+            dirty: Arc::new(Dirty::default()),
         }
     }
-    /// Add or update a peer in the management list
-    pub async fn add_or_update_peer(&self, peer: PeerRecord) {
-        let mut peers = self.known_peers.lock().await;
+
+    /// Replace the default scoring weights and thresholds
+    pub fn with_score_config(mut self, config: ScoreConfig) -> Self {
+        self.scoring = Arc::new(config);
+        self
+    }
+
+    pub fn score_config(&self) -> &ScoreConfig {
+        &self.scoring
+    }
+
+    /// Run a store operation on the blocking thread pool, one at a time so
+    /// writes reach the store in the order they were made.
+    async fn run_store<T, F>(&self, op: F) -> io::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn PeerStore) -> io::Result<T> + Send + 'static,
+    {
+        let _ordered = self.store_writes.lock().await;
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || op(store.as_ref()))
+            .await
+            .map_err(io::Error::other)?
+    }
+    /// Add or update a peer in the management list. A record that carries no
+    /// history or reputation keeps what is already known for the peer, and
+    /// addresses are merged into the ones already known rather than replacing them.
+    pub async fn add_or_update_peer(&self, mut peer: PeerRecord) {
+        peer.ensure_primary();
         let key = peer.peer_id.clone().unwrap_or_else(|| peer.addr.to_string());
-    
-        println!("Adding or updating peer with key: {}", key);
-        peers.insert(key, peer);
-    
-        println!("Current peers in map: {:?}", *peers);
-        // No immediate file save here
+
+        // A peer first seen by address alone is filed under that address;
+        // once its ID is known, fold that entry into this one.
+        let mut folded = Vec::new();
+        if peer.peer_id.is_some() {
+            for addr in peer.addrs.iter().map(|known| known.addr.to_string()).collect::<Vec<_>>() {
+                if addr == key {
+                    continue;
+                }
+                let Some(anon) = self.known_peers.remove_if(&addr, |anon| anon.peer_id.is_none()) else {
+                    continue;
+                };
+                for known in &anon.addrs {
+                    peer.add_addr(known.clone());
+                }
+                self.announce(PeerChange::Removed { key: addr.clone() });
+                folded.push(addr);
+            }
+        }
+
+        debug!(peer.key = %key, peer.addr = %peer.addr, peer.active = peer.is_active, "adding or updating peer");
+        let merge = |existing: Option<&PeerRecord>| {
+            if let Some(existing) = existing {
+                // A signed record only gives way to a newer one, and the
+                // addresses it no longer lists are dropped with the old one
+                let mut retired = Vec::new();
+                if let Some(current) = &existing.signed_record {
+                    match &peer.signed_record {
+                        Some(signed) if signed.supersedes(current) => {
+                            retired.extend(current.addrs.iter().filter(|addr| !signed.addrs.contains(addr)));
+                        }
+                        _ => {
+                            peer.public_key = Some(current.public_key.clone());
+                            peer.signed_record = Some(current.clone());
+                        }
+                    }
+                }
+                // A key is only ever replaced by another one, never forgotten
+                if peer.public_key.is_none() {
+                    peer.public_key = existing.public_key.clone();
+                }
+                for known in existing.addrs.iter().filter(|known| !retired.contains(&known.addr)) {
+                    peer.add_addr(known.clone());
+                }
+                peer.protocols.extend(existing.protocols.iter().cloned());
+                peer.tags.extend(existing.tags.iter().cloned());
+                if peer.history == PeerHistory::default() {
+                    peer.history = existing.history.clone();
+                }
+                if peer.reputation == Reputation::default() {
+                    peer.reputation = existing.reputation;
+                }
+            }
+            if peer.history.first_seen.is_none() {
+                peer.history.first_seen = Some(peer.last_seen.unwrap_or_else(SystemTime::now));
+            }
+            peer
+        };
+        // Announced under the shard lock so changes to one key go out in order
+        let peer = self.known_peers.merge_insert(key.clone(), merge, |before, peer| {
+            let change = match before {
+                None => Some(PeerChange::Added { key: key.clone(), peer: peer.clone() }),
+                Some(before) => {
+                    let changed = changed_fields(before, peer);
+                    (!changed.is_empty()).then(|| PeerChange::Updated { key: key.clone(), peer: peer.clone(), changed })
+                }
+            };
+            if let Some(change) = change {
+                self.announce(change);
+            }
+            peer.clone()
+        });
+        trace!(peers = self.known_peers.len(), "peer table updated");
+
+        if self.writes_deferred() {
+            return;
+        }
+        let result = self
+            .run_store(move |store| {
+                for addr in &folded {
+                    store.remove(addr)?;
+                }
+                store.upsert(&key, &peer)
+            })
+            .await;
+        if let Err(e) = result {
+            error!(error.kind = ?e.kind(), error = %e, "failed to store peer");
+        }
+    }
+
+    /// Verify a signed record, from the peer itself or relayed by the peer
+    /// keyed `learned_from`, and store it unless a record with the same or a
+    /// higher sequence is already held for the peer.
+    #[cfg(feature = "identity_integration")]
+    pub async fn accept_signed_record(
+        &self,
+        record: SignedPeerRecord,
+        source: AddrSource,
+        learned_from: Option<&str>,
+    ) -> Result<(), RecordError> {
+        record.verify(SystemTime::now())?;
+        let current = self.get_peer(&record.peer_id).await.and_then(|peer| peer.signed_record);
+        if let Some(current) = current.filter(|current| !record.supersedes(current)) {
+            return Err(RecordError::Stale {
+                current: current.sequence,
+                received: record.sequence,
+            });
+        }
+        trace!(peer.id = %record.peer_id, sequence = record.sequence, addrs = record.addrs.len(), "accepted signed record");
+        let mut peer = record.to_peer_record(source);
+        if let Some(relay) = learned_from {
+            peer.addrs = peer.addrs.into_iter().map(|known| known.with_learned_from(relay)).collect();
+        }
+        self.add_or_update_peer(peer).await;
+        Ok(())
+    }
+
+    /// Add an address to a known peer. Returns false if the peer is unknown.
+    pub async fn add_addr(&self, peer_id: &str, addr: PeerAddr) -> bool {
+        trace!(peer.key = %peer_id, peer.addr = %addr.addr, source = ?addr.source, "adding address");
+        self.update_known(peer_id, |peer| peer.add_addr(addr)).await.is_some()
+    }
+
+    /// Note a successful connection over one of a peer's addresses and make
+    /// it the primary
+    pub async fn record_addr_success(&self, peer_id: &str, addr: SocketAddr) {
+        self.update_known(peer_id, |peer| {
+            if let Some(known) = peer.addrs.iter_mut().find(|known| known.addr == addr) {
+                known.record_success(SystemTime::now());
+                peer.addr = addr;
+            }
+        }).await;
+    }
+
+    /// Note a failed dial over one of a peer's addresses
+    pub async fn record_addr_failure(&self, peer_id: &str, addr: SocketAddr) {
+        self.update_known(peer_id, |peer| {
+            if let Some(known) = peer.addrs.iter_mut().find(|known| known.addr == addr) {
+                known.record_failure();
+            }
+        }).await;
+    }
+
+    /// Remove expired addresses, and peers left with no address at all.
+    /// Returns how many addresses were removed.
+    pub async fn gc_addrs(&self) -> usize {
+        self.expire_addrs().await.0
+    }
+
+    /// `gc_addrs`, also returning the keys of the peers it removed.
+    async fn expire_addrs(&self) -> (usize, Vec<String>) {
+        let now = SystemTime::now();
+        let mut removed = 0;
+        let emptied = self.known_peers.retain(|key, peer| {
+            if peer.addrs.iter().any(|known| known.is_expired(now)) {
+                removed += self.tracked(key, Arc::make_mut(peer), |peer| peer.remove_expired_addrs(now));
+            }
+            !peer.addrs.is_empty()
+        });
+        for key in &emptied {
+            self.announce_expired(PeerExpired {
+                key: key.clone(),
+                reason: ExpiryReason::NoAddresses,
+            });
+        }
+
+        if removed > 0 {
+            debug!(addrs = removed, peers = emptied.len(), "expired addresses removed");
+        }
+        self.remove_from_store(emptied.clone()).await;
+        (removed, emptied)
+    }
+
+    /// Delete peers already dropped from the table from the store as well.
+    async fn remove_from_store(&self, keys: Vec<String>) {
+        if keys.is_empty() || self.writes_deferred() {
+            return;
+        }
+        let result = self
+            .run_store(move |store| keys.iter().try_for_each(|key| store.remove(key)))
+            .await;
+        if let Err(e) = result {
+            error!(error.kind = ?e.kind(), error = %e, "failed to remove peers from store");
+        }
     }
     
 
     /// Remove a peer by ID
     pub async fn remove_peer(&self, peer_id: &str) {
         // Step 1: Remove the peer from the in-memory map
-        {
-            let mut peers = self.known_peers.lock().await;
-            peers.remove(peer_id);
+        if self.known_peers.remove(peer_id).is_some() {
+            self.announce(PeerChange::Removed { key: peer_id.to_string() });
         }
     
-        // Step 2: Save peers to file asynchronously
-        if let Err(e) = self.save_to_file().await {
-            eprintln!("Failed to save peers to file: {}", e);
+        // Step 2: Remove it from the store
+        if self.writes_deferred() {
+            return;
+        }
+        let key = peer_id.to_string();
+        if let Err(e) = self.run_store(move |store| store.remove(&key)).await {
+            error!(peer.id = %peer_id, error.kind = ?e.kind(), error = %e, "failed to remove peer from store");
         }
     }
 
-    /// Load peers from the cache file
+    /// Load peers from the backing store, replacing the in-memory table
     pub async fn load_from_file(&self) -> io::Result<()> {
-        let mut file = match File::open(&self.cache_file) {
-            Ok(file) => file,
-            Err(_) => return Ok(()), // If file doesn't exist, return
-        };
-
-        let mut content = String::new();
-        file.read_to_string(&mut content)?;
-
-        let peers: HashMap<String, PeerRecord> = serde_json::from_str(&content)?;
-        let mut known_peers = self.known_peers.lock().await;
-        *known_peers = peers;
+        let mut peers = self.run_store(|store| store.load()).await?;
+        peers.values_mut().for_each(PeerRecord::ensure_primary);
+        self.known_peers.replace_all(peers);
+        self.gc_addrs().await;
         Ok(())
     }
 
-    /// Save peers to the cache file
+    /// Write the whole peer table to the backing store
     pub async fn save_to_file(&self) -> io::Result<()> {
-        let peers = self.known_peers.lock().await;
-        let serialized = serde_json::to_string_pretty(&*peers)?;
-    
-        // Debug log
-        println!("Saving peers to file: {}", serialized);
-    
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&self.cache_file)?;
-        file.write_all(serialized.as_bytes())?;
-        println!("Peers successfully saved to {}", self.cache_file);
-    
-        Ok(())
+        let dirty = self.dirty.take();
+        let snapshot = self.known_peers.snapshot();
+        let result = self
+            .run_store(move |store| {
+                let peers = snapshot.into_iter().map(|(key, peer)| (key, Arc::unwrap_or_clone(peer))).collect();
+                store.save_all(&peers)
+            })
+            .await;
+        if result.is_err() {
+            self.dirty.restore(dirty);
+        }
+        result
+    }
+
+    /// Refresh the last-seen time of a known peer and mark it active
+    pub async fn mark_seen(&self, peer_id: &str) {
+        self.update_known(peer_id, |peer| {
+            peer.is_active = true;
+            peer.last_seen = Some(SystemTime::now());
+        }).await;
+    }
+
+    /// Note a connection established with a known peer
+    pub async fn record_connected(&self, peer_id: &str) {
+        self.update_known(peer_id, |peer| {
+            let now = SystemTime::now();
+            peer.is_active = true;
+            peer.last_seen = Some(now);
+            peer.history.record_success(now);
+        }).await;
+        self.report(peer_id, PeerEvent::Handshake).await;
+    }
+
+    /// Note a failed attempt to reach a known peer
+    pub async fn record_failure(&self, peer_id: &str) {
+        self.update_known(peer_id, |peer| peer.history.record_failure(SystemTime::now())).await;
+        self.report(peer_id, PeerEvent::DialFailed).await;
+    }
+
+    /// Adjust a known peer's score and return the new value
+    pub async fn report(&self, peer_id: &str, event: PeerEvent) -> Option<f64> {
+        let now = SystemTime::now();
+        let (before, score) = self.update_known(peer_id, |peer| {
+            let before = peer.reputation.score_at(now, &self.scoring);
+            (before, peer.reputation.apply(event, now, &self.scoring))
+        }).await?;
+        trace!(peer.key = %peer_id, ?event, score, "peer score updated");
+        if score < self.scoring.ban_below && before >= self.scoring.ban_below {
+            warn!(peer.key = %peer_id, ?event, score, "peer score fell below the ban threshold");
+        }
+        Some(score)
+    }
+
+    /// Current score of a known peer, with decay applied
+    pub async fn score(&self, peer_id: &str) -> Option<f64> {
+        let peer = self.known_peers.get(peer_id)?;
+        Some(peer.reputation.score_at(SystemTime::now(), &self.scoring))
+    }
+
+    /// Whether a peer's score is low enough that it should be refused
+    pub async fn is_banned(&self, peer_id: &str) -> bool {
+        self.score(peer_id).await.is_some_and(|score| score < self.scoring.ban_below)
+    }
+
+    /// Inactive, unbanned peers worth dialing, best score first
+    pub async fn dial_candidates(&self) -> Vec<PeerRecord> {
+        let query = PeerQuery::new()
+            .active(false)
+            .score_between(Some(self.scoring.ban_below), None)
+            .sort_by(SortKey::Score, true);
+        self.query(&query).await.peers
+    }
+
+    /// Drop inactive peers scoring under the eviction threshold, then more
+    /// peers until at most `capacity` remain: inactive before active, lowest
+    /// score first, and the longest unseen first among equal scores.
+    /// Returns the keys that were removed.
+    pub async fn evict_low_scores(&self, capacity: usize) -> Vec<String> {
+        let now = SystemTime::now();
+        let mut ranked: Vec<(bool, f64, SystemTime, String)> = Vec::new();
+        self.known_peers.for_each(|key, peer| {
+            let seen = peer.last_seen.or(peer.history.first_seen).unwrap_or(now);
+            ranked.push((peer.is_active, peer.reputation.score_at(now, &self.scoring), seen, key.to_string()));
+        });
+        ranked.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)).then(a.2.cmp(&b.2)));
+
+        let excess = ranked.len().saturating_sub(capacity);
+        let mut evicted = Vec::new();
+        for (i, (active, score, _, key)) in ranked.into_iter().enumerate() {
+            let below = !active && score < self.scoring.evict_below;
+            if !below && i >= excess {
+                break;
+            }
+            // Spare a low scorer that reconnected since it was ranked
+            let still_due = |peer: &PeerRecord| !below || !peer.is_active;
+            if self.known_peers.remove_if(&key, still_due).is_none() {
+                continue;
+            }
+            self.announce_expired(PeerExpired {
+                key: key.clone(),
+                reason: ExpiryReason::Evicted,
+            });
+            evicted.push(key);
+        }
+
+        for key in &evicted {
+            debug!(peer.key = %key, "evicted peer");
+        }
+        self.remove_from_store(evicted.clone()).await;
+        evicted
+    }
+
+    /// Mark a known peer inactive, e.g. after it said goodbye
+    pub async fn mark_inactive(&self, peer_id: &str) {
+        self.update_known(peer_id, |peer| peer.is_active = false).await;
     }
 
     /// Get a peer by ID
     pub async fn get_peer(&self, peer_id: &str) -> Option<PeerRecord> {
-        let peers = self.known_peers.lock().await;
-        peers.get(peer_id).cloned()
+        self.known_peers.get(peer_id).map(Arc::unwrap_or_clone)
+    }
+
+    /// Get the unexpired TCP addresses known for a peer in dialing order, by
+    /// past success. `peer_id` may also be the address a peer without a known
+    /// ID is filed under, in any form that parses as one.
+    pub async fn get_peer_addrs(&self, peer_id: &str) -> Vec<SocketAddr> {
+        let record = self.known_peers.get(peer_id).or_else(|| {
+            let addr = peer_id.parse::<SocketAddr>().ok()?;
+            self.known_peers.get(&addr.to_string())
+        });
+        record.map_or_else(Vec::new, |peer| peer.ranked_addrs(AddrTransport::Tcp, SystemTime::now()))
     }
 
     pub async fn get_all_peers(&self) -> Vec<String> {
-        let peers = self.known_peers.lock().await;
-        peers.keys().cloned().collect()
+        self.known_peers.keys()
     }
 
     pub async fn debug_dump(&self) {
-        let peers = self.known_peers.lock().await;
-        println!("Current peers in memory: {:?}", peers);
+        self.known_peers.for_each(|key, peer| {
+            trace!(
+                peer.key = %key,
+                peer.addr = %peer.addr,
+                peer.id = ?peer.peer_id,
+                peer.active = peer.is_active,
+                "known peer"
+            );
+        });
     }
 }
 
-
-// Custom serialization and deserialization
-impl Serialize for PeerManagement {
-  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-  where
-      S: serde::Serializer,
-  {
-      let peers = tokio::runtime::Handle::current().block_on(self.known_peers.lock());
-      peers.serialize(serializer)
-  }
-}
-
-impl<'de> Deserialize<'de> for PeerManagement {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        let peers = HashMap::<String, PeerRecord>::deserialize(deserializer)?;
-        Ok(Self {
-            known_peers: Arc::new(Mutex::new(peers)),
-            cache_file: "peer_cache.json".to_string(),
-        })
-    }
-}
@@ -0,0 +1,163 @@
+// autosave.rs
+//? Writing changed peers to the store in the background, so a crash only
+//? loses the last few seconds of what the node learned
+use std::collections::HashSet;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::{watch, Notify};
+use tokio::time::{sleep, sleep_until, Instant};
+use tracing::{debug, error, info};
+
+use super::PeerManagement;
+use crate::record::peer_record::PeerRecord;
+
+/// When changed peers are written to the store.
+#[derive(Clone, Debug)]
+pub struct AutosaveConfig {
+    pub debounce: Duration, // Quiet time after the last change before writing
+    pub interval: Duration, // Longest a change waits while changes keep coming
+}
+
+impl Default for AutosaveConfig {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_secs(2),
+            interval: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Keys changed since the last flush, removed ones included.
+#[derive(Default)]
+pub(super) struct Dirty {
+    keys: Mutex<HashSet<String>>,
+    changed: Notify, // Wakes the autosave task
+}
+
+impl Dirty {
+    fn mark(&self, key: &str) {
+        let mut keys = self.keys.lock().unwrap();
+        if !keys.contains(key) {
+            keys.insert(key.to_string());
+        }
+        self.changed.notify_one();
+    }
+
+    pub(super) fn take(&self) -> HashSet<String> {
+        std::mem::take(&mut *self.keys.lock().unwrap())
+    }
+
+    /// Put back keys a failed write took, so the next flush retries them.
+    pub(super) fn restore(&self, keys: HashSet<String>) {
+        self.keys.lock().unwrap().extend(keys);
+        self.changed.notify_one();
+    }
+}
+
+impl PeerManagement {
+    /// Write changes in the background instead of as they are made. Changes
+    /// are only persisted while `run_autosave` runs, or by `flush` and
+    /// `save_to_file`.
+    pub fn with_autosave(mut self, config: AutosaveConfig) -> Self {
+        self.autosave = Some(Arc::new(config));
+        self
+    }
+
+    pub fn autosave_config(&self) -> Option<&AutosaveConfig> {
+        self.autosave.as_deref()
+    }
+
+    /// Whether store writes wait for the next flush.
+    pub(super) fn writes_deferred(&self) -> bool {
+        self.autosave.is_some()
+    }
+
+    /// Note that the record under `key` changed or was removed.
+    pub(super) fn mark_dirty(&self, key: &str) {
+        if self.writes_deferred() {
+            self.dirty.mark(key);
+        }
+    }
+
+    /// How many keys changed since the last flush.
+    pub fn dirty_count(&self) -> usize {
+        self.dirty.keys.lock().unwrap().len()
+    }
+
+    /// Write every record changed since the last flush, and delete the ones
+    /// removed since. Returns how many keys were written.
+    pub async fn flush(&self) -> io::Result<usize> {
+        if self.store.saves_whole_table() {
+            let count = self.dirty_count();
+            if count > 0 {
+                self.save_to_file().await?;
+            }
+            return Ok(count);
+        }
+
+        let keys = self.dirty.take();
+        if keys.is_empty() {
+            return Ok(0);
+        }
+        let changes: Vec<(String, Option<PeerRecord>)> = keys
+            .iter()
+            .map(|key| (key.clone(), self.known_peers.get(key).map(Arc::unwrap_or_clone)))
+            .collect();
+        let result = self
+            .run_store(move |store| {
+                changes.iter().try_for_each(|(key, peer)| match peer {
+                    Some(peer) => store.upsert(key, peer),
+                    None => store.remove(key),
+                })
+            })
+            .await;
+        match result {
+            Ok(()) => Ok(keys.len()),
+            Err(e) => {
+                self.dirty.restore(keys);
+                Err(e)
+            }
+        }
+    }
+
+    /// Flush changes once they stop coming for `debounce`, or `interval`
+    /// after the first one at the latest, until `shutdown` turns true; then
+    /// flush what is left. Returns straight away without `with_autosave`.
+    pub async fn run_autosave(self, mut shutdown: watch::Receiver<bool>) {
+        let Some(config) = self.autosave.clone() else {
+            return;
+        };
+        let mut stopping = false;
+        while !stopping {
+            tokio::select! {
+                _ = self.dirty.changed.notified() => {}
+                _ = async { let _ = shutdown.wait_for(|stop| *stop).await; } => break,
+            }
+            let deadline = Instant::now() + config.interval;
+            loop {
+                tokio::select! {
+                    _ = sleep(config.debounce) => break,
+                    _ = sleep_until(deadline) => break,
+                    _ = self.dirty.changed.notified() => {}
+                    _ = async { let _ = shutdown.wait_for(|stop| *stop).await; } => {
+                        stopping = true;
+                        break;
+                    }
+                }
+            }
+            self.autosave_flush().await;
+        }
+        self.autosave_flush().await;
+        info!("peer autosave stopped");
+    }
+
+    async fn autosave_flush(&self) {
+        match self.flush().await {
+            Ok(0) => {}
+            Ok(written) => debug!(peers = written, "autosaved peers"),
+            Err(e) => error!(error.kind = ?e.kind(), error = %e, "peer autosave failed, will retry"),
+        }
+    }
+}
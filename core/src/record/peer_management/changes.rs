@@ -0,0 +1,110 @@
+// changes.rs
+//? Typed notifications of changes to the peer table
+use tokio::sync::broadcast;
+use tracing::error;
+
+use super::{PeerExpired, PeerManagement};
+use crate::record::peer_record::PeerRecord;
+
+/// A part of a `PeerRecord` that an update changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PeerField {
+    Addr,
+    Addrs,
+    PublicKey,
+    Active,
+    LastSeen,
+    History,
+    Reputation,
+    Protocols,
+    Tags,
+    SignedRecord,
+}
+
+/// Fields that differ between two versions of a record.
+pub fn changed_fields(before: &PeerRecord, after: &PeerRecord) -> Vec<PeerField> {
+    let checks = [
+        (PeerField::Addr, before.addr != after.addr),
+        (PeerField::Addrs, before.addrs != after.addrs),
+        (PeerField::PublicKey, before.public_key != after.public_key),
+        (PeerField::Active, before.is_active != after.is_active),
+        (PeerField::LastSeen, before.last_seen != after.last_seen),
+        (PeerField::History, before.history != after.history),
+        (PeerField::Reputation, before.reputation != after.reputation),
+        (PeerField::Protocols, before.protocols != after.protocols),
+        (PeerField::Tags, before.tags != after.tags),
+        (PeerField::SignedRecord, before.signed_record != after.signed_record),
+    ];
+    checks.into_iter().filter(|(_, changed)| *changed).map(|(field, _)| field).collect()
+}
+
+/// Something that happened to the peer table. Keys are the same as in the table.
+#[derive(Clone, Debug)]
+pub enum PeerChange {
+    Added { key: String, peer: PeerRecord },
+    Updated { key: String, peer: PeerRecord, changed: Vec<PeerField> },
+    Removed { key: String },
+    /// Expired by maintenance. Unless the reason is `Inactive`, the record is gone.
+    Expired(PeerExpired),
+}
+
+impl PeerManagement {
+    /// Every change to the peer table from now on. Events go out on a
+    /// bounded broadcast channel, so writers never wait for subscribers; a
+    /// subscriber that falls too far behind gets `RecvError::Lagged` and
+    /// should re-read whatever it needs with `query`.
+    pub fn subscribe_changes(&self) -> broadcast::Receiver<PeerChange> {
+        self.change_tx.subscribe()
+    }
+
+    pub(super) fn announce(&self, change: PeerChange) {
+        match &change {
+            PeerChange::Added { key, .. } | PeerChange::Updated { key, .. } | PeerChange::Removed { key } => {
+                self.mark_dirty(key)
+            }
+            PeerChange::Expired(expired) => self.mark_dirty(&expired.key),
+        }
+        // No subscribers just means nobody is listening
+        let _ = self.change_tx.send(change);
+    }
+
+    pub(super) fn announce_expired(&self, expired: PeerExpired) {
+        let _ = self.expiry_tx.send(expired.clone());
+        self.announce(PeerChange::Expired(expired));
+    }
+
+    /// Apply `update` to a known peer and announce what it changed. Without
+    /// autosave the record is written to the store straight away.
+    pub(super) async fn update_known<T>(&self, key: &str, update: impl FnOnce(&mut PeerRecord) -> T) -> Option<T> {
+        let result = self.known_peers.update(key, |peer| self.tracked(key, peer, update))?;
+        if !self.writes_deferred() {
+            if let Some(peer) = self.known_peers.get(key) {
+                let key = key.to_string();
+                if let Err(e) = self.run_store(move |store| store.upsert(&key, &peer)).await {
+                    error!(error.kind = ?e.kind(), error = %e, "failed to store peer");
+                }
+            }
+        }
+        Some(result)
+    }
+
+    /// Apply `update` to a record and announce what it changed. The record
+    /// is only copied for comparison when someone is subscribed.
+    pub(super) fn tracked<T>(&self, key: &str, peer: &mut PeerRecord, update: impl FnOnce(&mut PeerRecord) -> T) -> T {
+        if self.change_tx.receiver_count() == 0 {
+            self.mark_dirty(key);
+            return update(peer);
+        }
+        let before = peer.clone();
+        let result = update(peer);
+        let changed = changed_fields(&before, peer);
+        if !changed.is_empty() {
+            self.announce(PeerChange::Updated {
+                key: key.to_string(),
+                peer: peer.clone(),
+                changed,
+            });
+        }
+        result
+    }
+}
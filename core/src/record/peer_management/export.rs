@@ -0,0 +1,368 @@
+// export.rs
+//? Moving a peer table between nodes, or out to a file, as JSON, CBOR or protobuf
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+use super::{changed_fields, PeerChange, PeerManagement};
+use crate::proto;
+use crate::record::peer_addr::{AddrSource, AddrTransport, PeerAddr};
+use crate::record::peer_record::{from_unix_ms, to_unix_ms, PeerHistory, PeerRecord};
+use crate::record::reputation::Reputation;
+use crate::record::signed_record::SignedPeerRecord;
+#[cfg(feature = "identity_integration")]
+use crate::record::signed_record::peer_id_matches_key;
+
+/// Version written into every export. Exports from a newer version are refused.
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,     // Pretty-printed, for people and scripts
+    Cbor,     // The same document as JSON, in binary
+    Protobuf, // See protocols/peer_table.proto
+}
+
+/// What an import does with peers the table already holds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    Replace,      // The table ends up holding exactly the imported peers
+    #[default]
+    MergeNewer,   // An imported record replaces a known one last seen earlier
+    KeepExisting, // Only peers not known yet are added
+}
+
+/// What an import did.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub skipped: usize, // Known peers left as they were
+    pub removed: usize, // Known peers missing from a `Replace` import
+}
+
+/// The JSON and CBOR document: peers keyed the same way as in the table.
+#[derive(Serialize, Deserialize)]
+struct ExportFile<Peers> {
+    version: u32,
+    peers: Peers,
+}
+
+impl PeerManagement {
+    /// Encode every known peer in `format`. Encoding runs on the blocking
+    /// thread pool, so a large table does not hold up the runtime.
+    pub async fn export(&self, format: ExportFormat) -> io::Result<Vec<u8>> {
+        let snapshot = self.known_peers.snapshot();
+        tokio::task::spawn_blocking(move || encode(format, &snapshot))
+            .await
+            .map_err(io::Error::other)?
+    }
+
+    /// Decode peers exported in `format` and fold them into the table as
+    /// `strategy` says. Keys and signed records are held to the same checks
+    /// as ones from the network, and dropped if they fail them. Changes are
+    /// announced and written to the store like any other.
+    pub async fn import(&self, format: ExportFormat, data: &[u8], strategy: MergeStrategy) -> io::Result<ImportSummary> {
+        let data = data.to_vec();
+        let peers = tokio::task::spawn_blocking(move || decode(format, &data))
+            .await
+            .map_err(io::Error::other)??;
+
+        let imported: HashSet<String> = match strategy {
+            MergeStrategy::Replace => peers.keys().cloned().collect(),
+            _ => HashSet::new(),
+        };
+        let now = SystemTime::now();
+        let mut summary = ImportSummary::default();
+        let mut written = Vec::new();
+        for (key, mut peer) in peers {
+            peer.ensure_primary();
+            vet(&key, &mut peer, now);
+            let merge = |existing: Option<&PeerRecord>| match (existing, strategy) {
+                (None, _) => Some(peer),
+                (Some(existing), MergeStrategy::Replace) => Some(keep_signed(existing, peer)),
+                (Some(_), MergeStrategy::KeepExisting) => None,
+                (Some(existing), MergeStrategy::MergeNewer) => newer(existing, peer),
+            };
+            let stored = self.known_peers.try_merge_insert(key.clone(), merge, |before, peer| {
+                let change = match before {
+                    None => Some(PeerChange::Added { key: key.clone(), peer: peer.clone() }),
+                    Some(before) => {
+                        let changed = changed_fields(before, peer);
+                        (!changed.is_empty()).then(|| PeerChange::Updated { key: key.clone(), peer: peer.clone(), changed })
+                    }
+                };
+                match &change {
+                    Some(PeerChange::Added { .. }) => summary.added += 1,
+                    Some(_) => summary.updated += 1,
+                    None => summary.skipped += 1,
+                }
+                let stored = change.is_some().then(|| peer.clone());
+                if let Some(change) = change {
+                    self.announce(change);
+                }
+                stored
+            });
+            match stored {
+                Some(Some(peer)) => written.push((key, peer)),
+                Some(None) => {}
+                None => summary.skipped += 1,
+            }
+        }
+
+        let removed = match strategy {
+            MergeStrategy::Replace => self.known_peers.retain(|key, _| imported.contains(key)),
+            _ => Vec::new(),
+        };
+        for key in &removed {
+            self.announce(PeerChange::Removed { key: key.clone() });
+        }
+        summary.removed = removed.len();
+        debug!(?format, ?strategy, added = summary.added, updated = summary.updated, skipped = summary.skipped, removed = summary.removed, "imported peers");
+
+        let result = match strategy {
+            // Everything may have changed, so write the table out in one go
+            MergeStrategy::Replace => self.save_to_file().await,
+            _ if self.writes_deferred() => Ok(()),
+            _ => {
+                self.run_store(move |store| written.iter().try_for_each(|(key, peer)| store.upsert(key, peer)))
+                    .await
+            }
+        };
+        if let Err(e) = result {
+            error!(error.kind = ?e.kind(), error = %e, "failed to store imported peers");
+        }
+        Ok(summary)
+    }
+}
+
+/// Drop what an imported record cannot back up: a signed record that does
+/// not verify or is for another peer, and a public key the peer ID was not
+/// derived from.
+fn vet(key: &str, peer: &mut PeerRecord, now: SystemTime) {
+    #[cfg(feature = "identity_integration")]
+    {
+        if let Some(signed) = peer.signed_record.take() {
+            match signed.verify(now) {
+                Ok(()) if peer.peer_id.as_deref() == Some(signed.peer_id.as_str()) => {
+                    peer.public_key = Some(signed.public_key.clone());
+                    peer.signed_record = Some(signed);
+                }
+                Ok(()) => debug!(peer.key = %key, "dropped imported signed record for another peer"),
+                Err(e) => {
+                    debug!(peer.key = %key, error.kind = e.kind(), error = %e, "dropped imported signed record")
+                }
+            }
+        }
+        let proven = match (&peer.peer_id, &peer.public_key) {
+            (Some(peer_id), Some(public_key)) => peer_id_matches_key(peer_id, public_key),
+            _ => false,
+        };
+        if peer.public_key.is_some() && !proven {
+            debug!(peer.key = %key, "dropped imported public key that does not match the peer ID");
+            peer.public_key = None;
+        }
+    }
+    // Neither can be checked in this build, so neither is taken on trust
+    #[cfg(not(feature = "identity_integration"))]
+    {
+        let _ = (key, now);
+        peer.public_key = None;
+        peer.signed_record = None;
+    }
+}
+
+/// The imported record if it was seen more recently than the known one.
+fn newer(existing: &PeerRecord, imported: PeerRecord) -> Option<PeerRecord> {
+    (imported.last_seen > existing.last_seen).then(|| keep_signed(existing, imported))
+}
+
+/// As everywhere else, a signed record only gives way to a newer one.
+fn keep_signed(existing: &PeerRecord, mut imported: PeerRecord) -> PeerRecord {
+    if let Some(current) = &existing.signed_record {
+        if !imported.signed_record.as_ref().is_some_and(|signed| signed.supersedes(current)) {
+            imported.public_key = Some(current.public_key.clone());
+            imported.signed_record = Some(current.clone());
+        }
+    }
+    imported
+}
+
+fn encode(format: ExportFormat, peers: &HashMap<String, Arc<PeerRecord>>) -> io::Result<Vec<u8>> {
+    // Sorted, so the same table always exports the same way
+    let peers: BTreeMap<&str, &PeerRecord> = peers.iter().map(|(key, peer)| (key.as_str(), peer.as_ref())).collect();
+    let file = ExportFile {
+        version: EXPORT_FORMAT_VERSION,
+        peers,
+    };
+    match format {
+        ExportFormat::Json => serde_json::to_vec_pretty(&file).map_err(io::Error::other),
+        ExportFormat::Cbor => {
+            let mut out = Vec::new();
+            ciborium::into_writer(&file, &mut out).map_err(io::Error::other)?;
+            Ok(out)
+        }
+        ExportFormat::Protobuf => Ok(proto::PeerTableExport {
+            version: file.version,
+            peers: file.peers.iter().map(|(key, peer)| to_entry(key, peer)).collect(),
+        }
+        .encode_to_vec()),
+    }
+}
+
+fn decode(format: ExportFormat, data: &[u8]) -> io::Result<HashMap<String, PeerRecord>> {
+    let (version, peers) = match format {
+        ExportFormat::Json => {
+            let file: ExportFile<HashMap<String, PeerRecord>> = serde_json::from_slice(data).map_err(invalid)?;
+            (file.version, file.peers)
+        }
+        ExportFormat::Cbor => {
+            let file: ExportFile<HashMap<String, PeerRecord>> = ciborium::from_reader(data).map_err(invalid)?;
+            (file.version, file.peers)
+        }
+        ExportFormat::Protobuf => {
+            let export = proto::PeerTableExport::decode(data).map_err(invalid)?;
+            check_version(export.version)?;
+            let peers = export.peers.into_iter().map(from_entry).collect::<io::Result<_>>()?;
+            (export.version, peers)
+        }
+    };
+    check_version(version)?;
+    Ok(peers)
+}
+
+fn check_version(version: u32) -> io::Result<()> {
+    if version > EXPORT_FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("peer export version {} is newer than supported version {}", version, EXPORT_FORMAT_VERSION),
+        ));
+    }
+    Ok(())
+}
+
+fn invalid(e: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+fn to_ms(time: Option<SystemTime>) -> u64 {
+    time.map_or(0, to_unix_ms)
+}
+
+fn from_ms(ms: u64) -> Option<SystemTime> {
+    (ms > 0).then(|| from_unix_ms(ms))
+}
+
+fn to_entry(key: &str, peer: &PeerRecord) -> proto::PeerTableEntry {
+    proto::PeerTableEntry {
+        key: key.to_string(),
+        addr: peer.addr.to_string(),
+        addrs: peer.addrs.iter().map(to_addr_entry).collect(),
+        peer_id: peer.peer_id.clone().unwrap_or_default(),
+        public_key: peer.public_key.clone().unwrap_or_default(),
+        is_active: peer.is_active,
+        last_seen_ms: to_ms(peer.last_seen),
+        history: Some(proto::PeerHistoryEntry {
+            first_seen_ms: to_ms(peer.history.first_seen),
+            last_connected_ms: to_ms(peer.history.last_connected),
+            last_failed_ms: to_ms(peer.history.last_failed),
+            successes: peer.history.successes,
+            failures: peer.history.failures,
+        }),
+        score: peer.reputation.score,
+        score_updated_ms: to_ms(peer.reputation.updated),
+        protocols: peer.protocols.iter().cloned().collect(),
+        tags: peer.tags.iter().cloned().collect(),
+        signed_record: peer.signed_record.as_ref().map(SignedPeerRecord::to_bytes).unwrap_or_default(),
+    }
+}
+
+fn from_entry(entry: proto::PeerTableEntry) -> io::Result<(String, PeerRecord)> {
+    let history = entry.history.unwrap_or_default();
+    let signed_record = match entry.signed_record.is_empty() {
+        true => None,
+        false => Some(SignedPeerRecord::from_bytes(&entry.signed_record)?),
+    };
+    let peer = PeerRecord {
+        addr: entry.addr.parse().map_err(|_| invalid(format!("bad address {:?} for {}", entry.addr, entry.key)))?,
+        addrs: entry.addrs.into_iter().map(from_addr_entry).collect::<io::Result<_>>()?,
+        peer_id: Some(entry.peer_id).filter(|peer_id| !peer_id.is_empty()),
+        public_key: Some(entry.public_key).filter(|key| !key.is_empty()),
+        is_active: entry.is_active,
+        last_seen: from_ms(entry.last_seen_ms),
+        history: PeerHistory {
+            first_seen: from_ms(history.first_seen_ms),
+            last_connected: from_ms(history.last_connected_ms),
+            last_failed: from_ms(history.last_failed_ms),
+            successes: history.successes,
+            failures: history.failures,
+        },
+        reputation: Reputation {
+            score: entry.score,
+            updated: from_ms(entry.score_updated_ms),
+        },
+        protocols: entry.protocols.into_iter().collect(),
+        tags: entry.tags.into_iter().collect(),
+        signed_record,
+    };
+    Ok((entry.key, peer))
+}
+
+fn to_addr_entry(known: &PeerAddr) -> proto::PeerAddrEntry {
+    let transport = match known.transport {
+        AddrTransport::Tcp => proto::AddrTransport::Tcp,
+        AddrTransport::Udp => proto::AddrTransport::Udp,
+    };
+    let source = match known.source {
+        AddrSource::Manual => proto::AddrSource::Manual,
+        AddrSource::Dial => proto::AddrSource::Dial,
+        AddrSource::Announced => proto::AddrSource::Announced,
+        AddrSource::Observed => proto::AddrSource::Observed,
+        AddrSource::PeerExchange => proto::AddrSource::PeerExchange,
+        AddrSource::Discovery => proto::AddrSource::Discovery,
+    };
+    proto::PeerAddrEntry {
+        addr: known.addr.to_string(),
+        transport: transport as i32,
+        source: source as i32,
+        expires_ms: to_ms(known.expires),
+        confidence: known.confidence,
+        successes: known.successes,
+        failures: known.failures,
+        last_success_ms: to_ms(known.last_success),
+        learned_from: known.learned_from.clone().unwrap_or_default(),
+    }
+}
+
+fn from_addr_entry(entry: proto::PeerAddrEntry) -> io::Result<PeerAddr> {
+    let transport = match proto::AddrTransport::from_i32(entry.transport) {
+        Some(proto::AddrTransport::Tcp) => AddrTransport::Tcp,
+        Some(proto::AddrTransport::Udp) => AddrTransport::Udp,
+        None => return Err(invalid(format!("unknown transport {} for {}", entry.transport, entry.addr))),
+    };
+    let source = match proto::AddrSource::from_i32(entry.source) {
+        Some(proto::AddrSource::Manual) => AddrSource::Manual,
+        Some(proto::AddrSource::Dial) => AddrSource::Dial,
+        Some(proto::AddrSource::Announced) => AddrSource::Announced,
+        Some(proto::AddrSource::Observed) => AddrSource::Observed,
+        Some(proto::AddrSource::PeerExchange) => AddrSource::PeerExchange,
+        Some(proto::AddrSource::Discovery) => AddrSource::Discovery,
+        None => return Err(invalid(format!("unknown address source {} for {}", entry.source, entry.addr))),
+    };
+    Ok(PeerAddr {
+        addr: entry.addr.parse().map_err(|_| invalid(format!("bad address {:?}", entry.addr)))?,
+        transport,
+        source,
+        expires: from_ms(entry.expires_ms),
+        confidence: entry.confidence,
+        successes: entry.successes,
+        failures: entry.failures,
+        last_success: from_ms(entry.last_success_ms),
+        learned_from: Some(entry.learned_from).filter(|relay| !relay.is_empty()),
+    })
+}
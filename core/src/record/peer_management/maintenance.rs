@@ -0,0 +1,116 @@
+// maintenance.rs
+//? Background upkeep of the peer table: inactivity, staleness and the size cap
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::{broadcast, watch};
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{debug, info};
+
+use super::PeerManagement;
+
+/// When peers go quiet, when they are forgotten, and how many are kept.
+#[derive(Clone, Debug)]
+pub struct MaintenanceConfig {
+    pub interval: Duration,       // How often the maintenance pass runs
+    pub inactive_after: Duration, // Silence before an active peer is marked inactive
+    pub remove_after: Duration,   // Silence before an inactive peer is deleted
+    pub max_peers: usize,         // Table size enforced by eviction
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            inactive_after: Duration::from_secs(5 * 60),
+            remove_after: Duration::from_secs(7 * 24 * 60 * 60),
+            max_peers: 10_000,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExpiryReason {
+    Inactive,    // Marked inactive, still in the table
+    Stale,       // Deleted after being inactive too long
+    Evicted,     // Deleted for a low score or to stay under `max_peers`
+    NoAddresses, // Deleted because every address it had expired
+}
+
+/// A peer the maintenance pass expired, keyed as in the peer table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PeerExpired {
+    pub key: String,
+    pub reason: ExpiryReason,
+}
+
+impl PeerManagement {
+    /// Replace the default maintenance settings
+    pub fn with_maintenance_config(mut self, config: MaintenanceConfig) -> Self {
+        self.maintenance = Arc::new(config);
+        self
+    }
+
+    pub fn maintenance_config(&self) -> &MaintenanceConfig {
+        &self.maintenance
+    }
+
+    /// Events for every peer the maintenance pass expires
+    pub fn subscribe_expiry(&self) -> broadcast::Receiver<PeerExpired> {
+        self.expiry_tx.subscribe()
+    }
+
+    /// Run one maintenance pass and return what it expired. Each expiry is
+    /// also announced to `subscribe_expiry` and `subscribe_changes`.
+    pub async fn maintain(&self) -> Vec<PeerExpired> {
+        let config = self.maintenance.clone();
+        let mut expired = Vec::new();
+        let expire = |keys: Vec<String>, reason| keys.into_iter().map(move |key| PeerExpired { key, reason });
+
+        let (_, emptied) = self.expire_addrs().await;
+        expired.extend(expire(emptied, ExpiryReason::NoAddresses));
+
+        let now = SystemTime::now();
+        let mut inactive = Vec::new();
+        let stale = self.known_peers.retain(|key, peer| {
+            let Some(seen) = peer.last_seen.or(peer.history.first_seen) else {
+                return true;
+            };
+            let silence = now.duration_since(seen).unwrap_or_default();
+            if peer.is_active && silence >= config.inactive_after {
+                Arc::make_mut(peer).is_active = false;
+                inactive.push(key.to_string());
+            }
+            peer.is_active || silence < config.remove_after
+        });
+        self.remove_from_store(stale.clone()).await;
+        let passed = expire(inactive, ExpiryReason::Inactive).chain(expire(stale, ExpiryReason::Stale));
+        for event in passed {
+            self.announce_expired(event.clone());
+            expired.push(event);
+        }
+
+        let evicted = self.evict_low_scores(config.max_peers).await;
+        expired.extend(expire(evicted, ExpiryReason::Evicted));
+
+        if !expired.is_empty() {
+            debug!(expired = expired.len(), "peer maintenance pass");
+        }
+        expired
+    }
+
+    /// Run `maintain` every `interval` until `shutdown` turns true.
+    pub async fn run_maintenance(self, mut shutdown: watch::Receiver<bool>) {
+        let mut ticks = interval(self.maintenance.interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {
+                    self.maintain().await;
+                }
+                _ = async { let _ = shutdown.wait_for(|stop| *stop).await; } => break,
+            }
+        }
+        info!("peer maintenance stopped");
+    }
+}
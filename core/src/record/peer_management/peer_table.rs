@@ -0,0 +1,153 @@
+// peer_table.rs
+//? The peer table itself: records spread over independently locked shards
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::sync::{Arc, RwLock};
+
+use crate::record::peer_record::PeerRecord;
+
+/// Number of shards. Operations on keys in different shards never wait on
+/// each other, and whole-table reads only hold one shard at a time.
+const SHARDS: usize = 64;
+
+type Shard = HashMap<String, Arc<PeerRecord>>;
+
+/// Records are kept behind `Arc` so snapshots only bump reference counts;
+/// a record is copied when it is next written while a snapshot still holds it.
+/// Locks are never held across an `.await`.
+pub(super) struct PeerTable {
+    shards: Box<[RwLock<Shard>]>,
+    hasher: RandomState,
+}
+
+impl Default for PeerTable {
+    fn default() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| RwLock::new(HashMap::new())).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+}
+
+impl PeerTable {
+    fn shard(&self, key: &str) -> &RwLock<Shard> {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        &self.shards[index]
+    }
+
+    pub(super) fn get(&self, key: &str) -> Option<Arc<PeerRecord>> {
+        self.shard(key).read().unwrap().get(key).cloned()
+    }
+
+    /// Insert or replace a record, returning the one it replaced.
+    pub(super) fn insert(&self, key: String, peer: PeerRecord) -> Option<Arc<PeerRecord>> {
+        self.shard(&key).write().unwrap().insert(key, Arc::new(peer))
+    }
+
+    /// Build a record from the one currently stored under `key`, if any, and
+    /// store it, all under the shard lock. `merge` sees the old record, and
+    /// `then` sees the old and the new one before the lock is released.
+    pub(super) fn merge_insert<T>(
+        &self,
+        key: String,
+        merge: impl FnOnce(Option<&PeerRecord>) -> PeerRecord,
+        then: impl FnOnce(Option<&PeerRecord>, &PeerRecord) -> T,
+    ) -> T {
+        let mut shard = self.shard(&key).write().unwrap();
+        let peer = Arc::new(merge(shard.get(&key).map(Arc::as_ref)));
+        let before = shard.insert(key, peer.clone());
+        then(before.as_deref(), &peer)
+    }
+
+    /// `merge_insert`, except `merge` may return `None` to leave the table
+    /// as it is, in which case `then` is not called.
+    pub(super) fn try_merge_insert<T>(
+        &self,
+        key: String,
+        merge: impl FnOnce(Option<&PeerRecord>) -> Option<PeerRecord>,
+        then: impl FnOnce(Option<&PeerRecord>, &PeerRecord) -> T,
+    ) -> Option<T> {
+        let mut shard = self.shard(&key).write().unwrap();
+        let peer = Arc::new(merge(shard.get(&key).map(Arc::as_ref))?);
+        let before = shard.insert(key, peer.clone());
+        Some(then(before.as_deref(), &peer))
+    }
+
+    pub(super) fn remove(&self, key: &str) -> Option<Arc<PeerRecord>> {
+        self.shard(key).write().unwrap().remove(key)
+    }
+
+    /// Remove a record only if `condition` holds for it.
+    pub(super) fn remove_if(&self, key: &str, condition: impl FnOnce(&PeerRecord) -> bool) -> Option<Arc<PeerRecord>> {
+        let mut shard = self.shard(key).write().unwrap();
+        match shard.get(key) {
+            Some(peer) if condition(peer) => shard.remove(key),
+            _ => None,
+        }
+    }
+
+    /// Change one record in place.
+    pub(super) fn update<T>(&self, key: &str, update: impl FnOnce(&mut PeerRecord) -> T) -> Option<T> {
+        let mut shard = self.shard(key).write().unwrap();
+        shard.get_mut(key).map(|peer| update(Arc::make_mut(peer)))
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.read().unwrap().len()).sum()
+    }
+
+    pub(super) fn keys(&self) -> Vec<String> {
+        let mut keys = Vec::new();
+        for shard in self.shards.iter() {
+            keys.extend(shard.read().unwrap().keys().cloned());
+        }
+        keys
+    }
+
+    /// Visit every record, one shard at a time.
+    pub(super) fn for_each(&self, mut visit: impl FnMut(&str, &Arc<PeerRecord>)) {
+        for shard in self.shards.iter() {
+            for (key, peer) in shard.read().unwrap().iter() {
+                visit(key, peer);
+            }
+        }
+    }
+
+    /// Visit every record for writing, one shard at a time, and remove the
+    /// ones `keep` returns false for. Records are shared with snapshots, so
+    /// `keep` should only `Arc::make_mut` the ones it actually changes.
+    pub(super) fn retain(&self, mut keep: impl FnMut(&str, &mut Arc<PeerRecord>) -> bool) -> Vec<String> {
+        let mut removed = Vec::new();
+        for shard in self.shards.iter() {
+            shard.write().unwrap().retain(|key, peer| {
+                let kept = keep(key, peer);
+                if !kept {
+                    removed.push(key.clone());
+                }
+                kept
+            });
+        }
+        removed
+    }
+
+    /// A point-in-time copy of the table that shares records with it.
+    /// Each shard is copied atomically; the shards are not frozen together.
+    pub(super) fn snapshot(&self) -> HashMap<String, Arc<PeerRecord>> {
+        let mut peers = HashMap::with_capacity(self.len());
+        for shard in self.shards.iter() {
+            peers.extend(shard.read().unwrap().iter().map(|(key, peer)| (key.clone(), peer.clone())));
+        }
+        peers
+    }
+
+    /// Replace every record.
+    pub(super) fn replace_all(&self, peers: HashMap<String, PeerRecord>) {
+        for shard in self.shards.iter() {
+            shard.write().unwrap().clear();
+        }
+        for (key, peer) in peers {
+            self.insert(key, peer);
+        }
+    }
+}
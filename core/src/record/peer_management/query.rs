@@ -0,0 +1,243 @@
+// query.rs
+//? Filtered, sorted and paginated reads of the peer table
+use std::cmp::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use super::PeerManagement;
+use crate::record::peer_record::PeerRecord;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddrFamily {
+    V4,
+    V6,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Key,       // Peer ID, or address for peers without one
+    LastSeen,
+    FirstSeen,
+    Score,
+}
+
+/// Which peers to return and in what order. Every filter that is set must
+/// match; an empty query returns the whole table sorted by key.
+#[derive(Clone, Debug, Default)]
+pub struct PeerQuery {
+    active: Option<bool>,
+    seen_since: Option<SystemTime>,
+    seen_until: Option<SystemTime>,
+    connected_since: Option<SystemTime>,
+    family: Option<AddrFamily>,
+    min_score: Option<f64>,
+    max_score: Option<f64>,
+    protocols: Vec<String>,
+    tags: Vec<String>,
+    sort: SortKey,
+    descending: bool,
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl PeerQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn active(mut self, active: bool) -> Self {
+        self.active = Some(active);
+        self
+    }
+
+    /// Peers last seen within `window` of now.
+    pub fn seen_within(mut self, window: Duration) -> Self {
+        self.seen_since = Some(SystemTime::now() - window);
+        self
+    }
+
+    /// Peers last seen between `since` and `until`, either end open.
+    pub fn seen_between(mut self, since: Option<SystemTime>, until: Option<SystemTime>) -> Self {
+        self.seen_since = since;
+        self.seen_until = until;
+        self
+    }
+
+    /// Peers this node itself connected to within `window` of now. Unlike
+    /// `seen_within`, this cannot be vouched for by other peers.
+    pub fn connected_within(mut self, window: Duration) -> Self {
+        self.connected_since = Some(SystemTime::now() - window);
+        self
+    }
+
+    /// Peers with at least one address in `family`.
+    pub fn family(mut self, family: AddrFamily) -> Self {
+        self.family = Some(family);
+        self
+    }
+
+    /// Peers whose current score lies in `min..=max`, either end open.
+    pub fn score_between(mut self, min: Option<f64>, max: Option<f64>) -> Self {
+        self.min_score = min;
+        self.max_score = max;
+        self
+    }
+
+    /// Peers known to speak `protocol`. Repeat to require several.
+    pub fn protocol(mut self, protocol: impl Into<String>) -> Self {
+        self.protocols.push(protocol.into());
+        self
+    }
+
+    /// Peers carrying `tag`. Repeat to require several.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Order results by `key`. Peers without a value for it sort last.
+    pub fn sort_by(mut self, key: SortKey, descending: bool) -> Self {
+        self.sort = key;
+        self.descending = descending;
+        self
+    }
+
+    /// Skip the first `offset` matches and return at most `limit`.
+    pub fn page(mut self, offset: usize, limit: usize) -> Self {
+        self.offset = offset;
+        self.limit = Some(limit);
+        self
+    }
+
+    fn matches(&self, peer: &PeerRecord, score: f64) -> bool {
+        if self.active.is_some_and(|active| peer.is_active != active) {
+            return false;
+        }
+        if self.seen_since.is_some() || self.seen_until.is_some() {
+            let Some(seen) = peer.last_seen else {
+                return false;
+            };
+            if self.seen_since.is_some_and(|since| seen < since) || self.seen_until.is_some_and(|until| seen > until) {
+                return false;
+            }
+        }
+        if let Some(since) = self.connected_since {
+            if peer.history.last_connected.is_none_or(|connected| connected < since) {
+                return false;
+            }
+        }
+        if let Some(family) = self.family {
+            let in_family = |addr: &std::net::SocketAddr| match family {
+                AddrFamily::V4 => addr.is_ipv4(),
+                AddrFamily::V6 => addr.is_ipv6(),
+            };
+            if !in_family(&peer.addr) && !peer.addrs.iter().any(|known| in_family(&known.addr)) {
+                return false;
+            }
+        }
+        if self.min_score.is_some_and(|min| score < min) || self.max_score.is_some_and(|max| score > max) {
+            return false;
+        }
+        self.protocols.iter().all(|protocol| peer.protocols.contains(protocol))
+            && self.tags.iter().all(|tag| peer.tags.contains(tag))
+    }
+}
+
+/// One page of query results.
+#[derive(Clone, Debug, Default)]
+pub struct QueryPage {
+    pub total: usize,            // Matches before pagination
+    pub peers: Vec<PeerRecord>,
+}
+
+struct Match {
+    key: String,
+    score: f64,
+    peer: Arc<PeerRecord>,
+}
+
+/// Compare optional values so that missing ones sort last either way.
+fn cmp_present<T: Ord>(a: Option<T>, b: Option<T>, descending: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if descending => b.cmp(&a),
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+impl PeerManagement {
+    /// Records matching `query`, sorted and paginated.
+    pub async fn query(&self, query: &PeerQuery) -> QueryPage {
+        let now = SystemTime::now();
+        let mut matches = Vec::new();
+        self.known_peers.for_each(|key, peer| {
+            let score = peer.reputation.score_at(now, &self.scoring);
+            if query.matches(peer, score) {
+                matches.push(Match {
+                    key: key.to_string(),
+                    score,
+                    peer: peer.clone(),
+                });
+            }
+        });
+
+        let descending = query.descending;
+        matches.sort_by(|a, b| {
+            let order = match query.sort {
+                SortKey::Key => Ordering::Equal,
+                SortKey::LastSeen => cmp_present(a.peer.last_seen, b.peer.last_seen, descending),
+                SortKey::FirstSeen => {
+                    cmp_present(a.peer.history.first_seen, b.peer.history.first_seen, descending)
+                }
+                SortKey::Score if descending => b.score.total_cmp(&a.score),
+                SortKey::Score => a.score.total_cmp(&b.score),
+            };
+            // Keys break ties so pages are stable between calls
+            let by_key = match query.sort == SortKey::Key && descending {
+                true => b.key.cmp(&a.key),
+                false => a.key.cmp(&b.key),
+            };
+            order.then(by_key)
+        });
+
+        let total = matches.len();
+        let peers = matches
+            .into_iter()
+            .skip(query.offset)
+            .take(query.limit.unwrap_or(usize::MAX))
+            .map(|found| Arc::unwrap_or_clone(found.peer))
+            .collect();
+        QueryPage { total, peers }
+    }
+
+    /// How many records match `query`, ignoring its pagination.
+    pub async fn count(&self, query: &PeerQuery) -> usize {
+        let now = SystemTime::now();
+        let mut count = 0;
+        self.known_peers.for_each(|_, peer| {
+            if query.matches(peer, peer.reputation.score_at(now, &self.scoring)) {
+                count += 1;
+            }
+        });
+        count
+    }
+
+    /// Record that a known peer speaks a substream protocol
+    pub async fn add_protocol(&self, peer_id: &str, protocol: &str) {
+        self.update_known(peer_id, |peer| peer.protocols.insert(protocol.to_string())).await;
+    }
+
+    /// Attach an application label to a known peer. Returns false if the peer is unknown.
+    pub async fn tag_peer(&self, peer_id: &str, tag: &str) -> bool {
+        self.update_known(peer_id, |peer| peer.tags.insert(tag.to_string())).await
+            .is_some()
+    }
+
+    pub async fn untag_peer(&self, peer_id: &str, tag: &str) -> bool {
+        self.update_known(peer_id, |peer| peer.tags.remove(tag)).await
+            .unwrap_or(false)
+    }
+}
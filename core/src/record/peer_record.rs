@@ -1,36 +1,160 @@
 // peer_record.rs
 
 use serde::{Deserialize, Serialize};
+use super::peer_addr::{AddrTransport, PeerAddr};
+use super::reputation::Reputation;
+use super::signed_record::SignedPeerRecord;
+use std::collections::BTreeSet;
 use std::net::SocketAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PeerRecord {
-    pub addr: SocketAddr,
+    pub addr: SocketAddr,     // Primary address: the one most recently confirmed
+    #[serde(default)]
+    pub addrs: Vec<PeerAddr>, // Every address known for the peer, including `addr`
     pub peer_id: Option<String>,
     pub public_key: Option<String>,
     pub is_active: bool,
-    #[serde(
-        serialize_with = "serialize_instant",
-        deserialize_with = "deserialize_instant"
-    )]
-    pub last_seen: Option<std::time::Instant>,
+    #[serde(default, with = "unix_ms")]
+    pub last_seen: Option<SystemTime>, // Wall-clock time, stored as Unix milliseconds
+    #[serde(default)]
+    pub history: PeerHistory,
+    #[serde(default)]
+    pub reputation: Reputation,
+    #[serde(default)]
+    pub protocols: BTreeSet<String>, // Substream protocols the peer has accepted or proposed
+    #[serde(default)]
+    pub tags: BTreeSet<String>,      // Labels set by the application
+    #[serde(default)]
+    pub signed_record: Option<SignedPeerRecord>, // The newest record the peer signed itself
 }
 
-// Helper functions for serialization
-fn serialize_instant<S>(instant: &Option<std::time::Instant>, serializer: S) -> Result<S::Ok, S::Error>
-where
-    S: serde::Serializer,
-{
-    match instant {
-        Some(instant) => serializer.serialize_some(&instant.elapsed().as_secs()),
-        None => serializer.serialize_none(),
+impl PeerRecord {
+    /// A record of a peer at `addr` and nothing else known about it yet.
+    /// Set the fields that are known with `..PeerRecord::new(addr)`.
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            addrs: Vec::new(),
+            peer_id: None,
+            public_key: None,
+            is_active: false,
+            last_seen: None,
+            history: PeerHistory::default(),
+            reputation: Reputation::default(),
+            protocols: BTreeSet::new(),
+            tags: BTreeSet::new(),
+            signed_record: None,
+        }
+    }
+
+    /// Make sure the primary address is listed in `addrs`. Records from
+    /// before multi-address support only have the primary, which was always
+    /// TCP; one already listed under any transport is left as it is.
+    pub fn ensure_primary(&mut self) {
+        if !self.addrs.iter().any(|known| known.addr == self.addr) {
+            self.addrs.insert(0, PeerAddr::tcp(self.addr, Default::default()));
+        }
+    }
+
+    /// Add an address, or merge it into the entry for the same endpoint.
+    pub fn add_addr(&mut self, addr: PeerAddr) {
+        match self.addrs.iter_mut().find(|known| known.same_endpoint(&addr)) {
+            Some(known) => known.merge(&addr),
+            None => self.addrs.push(addr),
+        }
+    }
+
+    /// Unexpired addresses over `transport`, best first.
+    pub fn ranked_addrs(&self, transport: AddrTransport, now: SystemTime) -> Vec<SocketAddr> {
+        let mut ranked: Vec<&PeerAddr> = self
+            .addrs
+            .iter()
+            .filter(|known| known.transport == transport && !known.is_expired(now))
+            .collect();
+        ranked.sort_by(|a, b| b.rank().total_cmp(&a.rank()));
+        ranked.into_iter().map(|known| known.addr).collect()
+    }
+
+    /// Drop expired addresses, moving the primary to the best one left.
+    /// Returns how many were removed.
+    pub fn remove_expired_addrs(&mut self, now: SystemTime) -> usize {
+        let before = self.addrs.len();
+        self.addrs.retain(|known| !known.is_expired(now));
+        if !self.addrs.iter().any(|known| known.addr == self.addr) {
+            if let Some(best) = self.ranked_addrs(AddrTransport::Tcp, now).first() {
+                self.addr = *best;
+            }
+        }
+        before - self.addrs.len()
+    }
+
+    /// How long ago the peer was last seen, if ever. A timestamp in the
+    /// future (clock skew between machines) counts as just seen.
+    pub fn age(&self) -> Option<Duration> {
+        self.last_seen
+            .map(|seen| SystemTime::now().duration_since(seen).unwrap_or_default())
     }
 }
 
-fn deserialize_instant<'de, D>(deserializer: D) -> Result<Option<std::time::Instant>, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    let secs: Option<u64> = Option::deserialize(deserializer)?;
-    Ok(secs.map(|s| std::time::Instant::now() - std::time::Duration::from_secs(s)))
+/// What this node has observed of a peer over time.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerHistory {
+    #[serde(default, with = "unix_ms")]
+    pub first_seen: Option<SystemTime>,
+    #[serde(default, with = "unix_ms")]
+    pub last_connected: Option<SystemTime>,
+    #[serde(default, with = "unix_ms")]
+    pub last_failed: Option<SystemTime>,
+    #[serde(default)]
+    pub successes: u64, // Connections established
+    #[serde(default)]
+    pub failures: u64,  // Dial attempts that failed
+}
+
+impl PeerHistory {
+    pub fn record_success(&mut self, at: SystemTime) {
+        self.first_seen.get_or_insert(at);
+        self.last_connected = Some(at);
+        self.successes += 1;
+    }
+
+    pub fn record_failure(&mut self, at: SystemTime) {
+        self.last_failed = Some(at);
+        self.failures += 1;
+    }
+}
+
+/// Convert between `SystemTime` and Unix milliseconds.
+pub(crate) fn to_unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+pub(crate) fn from_unix_ms(ms: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(ms)
+}
+
+// Serde helpers for optional timestamps
+pub(crate) mod unix_ms {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::SystemTime;
+
+    pub fn serialize<S>(time: &Option<SystemTime>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match time {
+            Some(time) => serializer.serialize_some(&super::to_unix_ms(*time)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<SystemTime>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let ms: Option<u64> = Option::deserialize(deserializer)?;
+        Ok(ms.map(super::from_unix_ms))
+    }
 }
@@ -0,0 +1,43 @@
+// peer_store.rs
+//? Storage backends for the peer table
+use std::collections::HashMap;
+use std::io;
+
+use crate::record::peer_record::PeerRecord;
+
+mod memory_store;
+mod json_store;
+#[cfg(feature = "sled_store")]
+mod sled_store;
+
+pub use memory_store::MemoryStore;
+pub use json_store::{JsonFileStore, CACHE_FORMAT_VERSION};
+#[cfg(feature = "sled_store")]
+pub use sled_store::SledStore;
+
+/// Where `PeerManagement` keeps its records between runs.
+///
+/// Methods block, and `PeerManagement` only calls them from the blocking
+/// thread pool, so implementations are free to do plain file or database I/O.
+/// Records are keyed the same way as in `PeerManagement`: by peer ID, or by
+/// address for peers without one.
+pub trait PeerStore: Send + Sync + 'static {
+    /// Read every stored record.
+    fn load(&self) -> io::Result<HashMap<String, PeerRecord>>;
+
+    /// Insert or replace one record. Stores that can only write everything at
+    /// once may leave this to the next `save_all`.
+    fn upsert(&self, key: &str, peer: &PeerRecord) -> io::Result<()>;
+
+    /// Delete one record, with the same allowance as `upsert`.
+    fn remove(&self, key: &str) -> io::Result<()>;
+
+    /// Replace the stored records with `peers`.
+    fn save_all(&self, peers: &HashMap<String, PeerRecord>) -> io::Result<()>;
+
+    /// Whether `upsert` leaves writing to `save_all`. Autosave writes the
+    /// whole table to such stores instead of the records that changed.
+    fn saves_whole_table(&self) -> bool {
+        false
+    }
+}
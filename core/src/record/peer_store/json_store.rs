@@ -0,0 +1,212 @@
+// json_store.rs
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::{debug, info, warn};
+
+use super::PeerStore;
+use crate::record::peer_record::{to_unix_ms, PeerRecord};
+
+/// Version written in the header of new cache files. Files without a header
+/// are the original bare map and count as version 0.
+pub const CACHE_FORMAT_VERSION: u32 = 2;
+
+#[derive(Deserialize)]
+struct CacheFile {
+    version: u32,
+    peers: Value,
+}
+
+/// The whole peer table as one pretty-printed JSON file. Upserts wait for the
+/// next `save_all`; removals rewrite the file straight away so a dropped peer
+/// does not come back on restart.
+///
+/// Saves go to a temporary file that is synced and renamed over the cache,
+/// keeping the previous cache as `<path>.bak`. A cache that fails to parse is
+/// renamed to `<path>.corrupt-<unix time>` and the backup is loaded instead.
+pub struct JsonFileStore {
+    path: String,
+}
+
+impl JsonFileStore {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn backup_path(&self) -> String {
+        format!("{}.bak", self.path)
+    }
+
+    fn temp_path(&self) -> String {
+        format!("{}.tmp", self.path)
+    }
+
+    /// Read and parse one cache file. `Ok(None)` means it does not exist.
+    fn read(path: &str) -> io::Result<Option<HashMap<String, PeerRecord>>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let value: Value = serde_json::from_str(&content).map_err(corrupt)?;
+        let saved_at = fs::metadata(path).and_then(|meta| meta.modified()).unwrap_or_else(|_| SystemTime::now());
+        let (version, peers) = match serde_json::from_value::<CacheFile>(value.clone()) {
+            Ok(file) => (file.version, file.peers),
+            Err(_) => (0, value),
+        };
+        if version > CACHE_FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("peer cache version {} is newer than supported version {}", version, CACHE_FORMAT_VERSION),
+            ));
+        }
+        let peers = migrate(version, peers, saved_at)?;
+        if version < CACHE_FORMAT_VERSION {
+            info!(cache_file = %path, from = version, to = CACHE_FORMAT_VERSION, "migrated peer cache");
+        }
+        Ok(Some(serde_json::from_value(peers).map_err(corrupt)?))
+    }
+
+    /// Move a file that failed to parse out of the way so it is kept for
+    /// inspection but never loaded again.
+    fn quarantine(path: &str, error: &io::Error) {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let target = format!("{}.corrupt-{}", path, secs);
+        match fs::rename(path, &target) {
+            Ok(()) => warn!(cache_file = %path, quarantined = %target, error = %error, "quarantined corrupt peer cache"),
+            Err(e) => warn!(cache_file = %path, error = %e, "failed to quarantine corrupt peer cache"),
+        }
+    }
+
+    /// Load `path`, quarantining it if it is corrupt. Unsupported versions
+    /// are left in place and reported, since a newer build can still read them.
+    fn read_or_quarantine(path: &str) -> io::Result<Option<HashMap<String, PeerRecord>>> {
+        match Self::read(path) {
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                Self::quarantine(path, &e);
+                Ok(None)
+            }
+            result => result,
+        }
+    }
+}
+
+/// Any parse failure, including a truncated file, means the content is bad.
+fn corrupt(e: serde_json::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Bring the `peers` section of a cache file from `version` up to the
+/// current format, one version at a time. `saved_at` is when the file was
+/// last written.
+fn migrate(version: u32, mut peers: Value, saved_at: SystemTime) -> io::Result<Value> {
+    for from in version..CACHE_FORMAT_VERSION {
+        peers = match from {
+            // Version 1 only added the header around the same map
+            0 => peers,
+            1 => last_seen_to_wall_clock(peers, saved_at)?,
+            _ => unreachable!("no migration from peer cache version {}", from),
+        };
+    }
+    Ok(peers)
+}
+
+/// Versions 0 and 1 stored `last_seen` as seconds elapsed at save time.
+/// Anchor those at the file's modification time to get Unix milliseconds.
+fn last_seen_to_wall_clock(mut peers: Value, saved_at: SystemTime) -> io::Result<Value> {
+    let saved_at_ms = to_unix_ms(saved_at);
+    let records = peers
+        .as_object_mut()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "peer cache is not a map"))?;
+    for record in records.values_mut() {
+        if let Some(last_seen) = record.get_mut("last_seen") {
+            if let Some(secs) = last_seen.as_u64() {
+                *last_seen = json!(saved_at_ms.saturating_sub(secs * 1000));
+            }
+        }
+    }
+    Ok(peers)
+}
+
+/// Make a rename durable by syncing the directory that holds it.
+fn sync_parent(path: &str) {
+    let parent = match Path::new(path).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    // Directories cannot be opened for syncing on every platform
+    if let Ok(dir) = File::open(parent) {
+        let _ = dir.sync_all();
+    }
+}
+
+impl PeerStore for JsonFileStore {
+    fn load(&self) -> io::Result<HashMap<String, PeerRecord>> {
+        if let Some(peers) = Self::read_or_quarantine(&self.path)? {
+            info!(peers = peers.len(), cache_file = %self.path, "loaded peers from cache");
+            return Ok(peers);
+        }
+
+        let backup = self.backup_path();
+        if let Some(peers) = Self::read_or_quarantine(&backup)? {
+            warn!(peers = peers.len(), cache_file = %backup, "loaded peers from backup cache");
+            return Ok(peers);
+        }
+        Ok(HashMap::new()) // No usable cache, start empty
+    }
+
+    fn upsert(&self, _key: &str, _peer: &PeerRecord) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn saves_whole_table(&self) -> bool {
+        true
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        let mut peers = self.load()?;
+        if peers.remove(key).is_some() {
+            self.save_all(&peers)?;
+        }
+        Ok(())
+    }
+
+    fn save_all(&self, peers: &HashMap<String, PeerRecord>) -> io::Result<()> {
+        let serialized = serde_json::to_string_pretty(&json!({
+            "version": CACHE_FORMAT_VERSION,
+            "peers": peers,
+        }))?;
+        debug!(peers = peers.len(), bytes = serialized.len(), cache_file = %self.path, "saving peers to file");
+
+        let temp = self.temp_path();
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&temp)?;
+        file.write_all(serialized.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+
+        // Keep the previous cache as the backup, then move the new one in
+        match fs::rename(&self.path, self.backup_path()) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        fs::rename(&temp, &self.path)?;
+        sync_parent(&self.path);
+
+        info!(peers = peers.len(), cache_file = %self.path, "peers saved");
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+// memory_store.rs
+use std::collections::HashMap;
+use std::io;
+use std::sync::Mutex;
+
+use super::PeerStore;
+use crate::record::peer_record::PeerRecord;
+
+/// Keeps records for the life of the process only. Useful for tests and for
+/// nodes that should not remember peers across restarts.
+#[derive(Default)]
+pub struct MemoryStore {
+    peers: Mutex<HashMap<String, PeerRecord>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PeerStore for MemoryStore {
+    fn load(&self) -> io::Result<HashMap<String, PeerRecord>> {
+        Ok(self.peers.lock().unwrap().clone())
+    }
+
+    fn upsert(&self, key: &str, peer: &PeerRecord) -> io::Result<()> {
+        self.peers.lock().unwrap().insert(key.to_string(), peer.clone());
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        self.peers.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    fn save_all(&self, peers: &HashMap<String, PeerRecord>) -> io::Result<()> {
+        *self.peers.lock().unwrap() = peers.clone();
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+// sled_store.rs
+use std::collections::HashMap;
+use std::io;
+
+use tracing::{debug, info};
+
+use super::PeerStore;
+use crate::record::peer_record::PeerRecord;
+
+/// Records in an embedded sled database, one key per peer, so adding or
+/// removing a peer only touches that peer's entry.
+pub struct SledStore {
+    db: sled::Db,
+}
+
+impl SledStore {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let db = sled::open(path).map_err(to_io)?;
+        info!(path = %path, "opened peer database");
+        Ok(Self { db })
+    }
+}
+
+impl PeerStore for SledStore {
+    fn load(&self) -> io::Result<HashMap<String, PeerRecord>> {
+        let mut peers = HashMap::new();
+        for entry in self.db.iter() {
+            let (key, value) = entry.map_err(to_io)?;
+            let key = String::from_utf8(key.to_vec()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            peers.insert(key, serde_json::from_slice(&value)?);
+        }
+        info!(peers = peers.len(), "loaded peers from database");
+        Ok(peers)
+    }
+
+    fn upsert(&self, key: &str, peer: &PeerRecord) -> io::Result<()> {
+        self.db.insert(key, serde_json::to_vec(peer)?).map_err(to_io)?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        self.db.remove(key).map_err(to_io)?;
+        Ok(())
+    }
+
+    fn save_all(&self, peers: &HashMap<String, PeerRecord>) -> io::Result<()> {
+        let mut batch = sled::Batch::default();
+        for key in self.db.iter().keys() {
+            let key = key.map_err(to_io)?;
+            if !std::str::from_utf8(&key).is_ok_and(|key| peers.contains_key(key)) {
+                batch.remove(key);
+            }
+        }
+        for (key, peer) in peers {
+            batch.insert(key.as_str(), serde_json::to_vec(peer)?);
+        }
+        self.db.apply_batch(batch).map_err(to_io)?;
+        self.db.flush().map_err(to_io)?;
+        debug!(peers = peers.len(), "peer database saved");
+        Ok(())
+    }
+}
+
+fn to_io(e: sled::Error) -> io::Error {
+    io::Error::other(e)
+}
@@ -0,0 +1,56 @@
+// record_error.rs
+use std::fmt;
+use std::io;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RecordError {
+    Malformed(String),                     // Not a decodable signed record
+    Expired(String),                       // Peer ID of a record past its expiry
+    PeerIdMismatch(String),                // The peer ID is not a hash of the public key
+    BadSignature(String),
+    Stale { current: u64, received: u64 }, // Not newer than the record already held
+    Signing(String),                       // The local key could not sign
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::Malformed(msg) => write!(f, "Malformed peer record: {}", msg),
+            RecordError::Expired(peer_id) => write!(f, "Peer record for {} has expired", peer_id),
+            RecordError::PeerIdMismatch(peer_id) => {
+                write!(f, "Peer ID {} does not match the record's public key", peer_id)
+            }
+            RecordError::BadSignature(msg) => write!(f, "Bad signature: {}", msg),
+            RecordError::Stale { current, received } => {
+                write!(f, "Peer record sequence {} is not newer than {}", received, current)
+            }
+            RecordError::Signing(msg) => write!(f, "Could not sign peer record: {}", msg),
+        }
+    }
+}
+
+impl RecordError {
+    /// Short machine-readable name, used as the `error.kind` field in logs.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RecordError::Malformed(_) => "malformed",
+            RecordError::Expired(_) => "expired",
+            RecordError::PeerIdMismatch(_) => "peer_id_mismatch",
+            RecordError::BadSignature(_) => "bad_signature",
+            RecordError::Stale { .. } => "stale",
+            RecordError::Signing(_) => "signing",
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl From<RecordError> for io::Error {
+    fn from(e: RecordError) -> Self {
+        let kind = match e {
+            RecordError::Signing(_) => io::ErrorKind::Other,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, e.to_string())
+    }
+}
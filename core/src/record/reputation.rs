@@ -0,0 +1,104 @@
+// reputation.rs
+//? Peer scores built from observed behaviour, decaying back to neutral over time
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// Something a peer did that affects its score.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PeerEvent {
+    Handshake,          // A connection with the peer was established
+    Rtt(Duration),      // A measured round trip
+    UsefulMessage,      // A payload that was delivered to the application
+    DialFailed,
+    InvalidMessage,     // A frame or envelope that could not be decoded
+    ProtocolViolation,  // A bad signature or a message the protocol forbids
+    Spam,               // Replayed or otherwise redundant traffic
+}
+
+/// How much each event is worth and where the thresholds sit. Scores are
+/// clamped to `-max_score..=max_score` and halve towards zero every
+/// `half_life`, so old behaviour counts for less than recent behaviour.
+#[derive(Clone, Debug)]
+pub struct ScoreConfig {
+    pub handshake: f64,
+    pub useful_message: f64,
+    pub fast_rtt: Duration,   // Round trips at or under this are rewarded
+    pub fast_rtt_reward: f64,
+    pub slow_rtt: Duration,   // Round trips over this are penalized
+    pub slow_rtt_penalty: f64,
+    pub dial_failed: f64,
+    pub invalid_message: f64,
+    pub protocol_violation: f64,
+    pub spam: f64,
+    pub half_life: Duration,
+    pub max_score: f64,
+    pub evict_below: f64,     // Peers under this are dropped first when evicting
+    pub ban_below: f64,       // Peers under this are refused entirely
+}
+
+impl Default for ScoreConfig {
+    fn default() -> Self {
+        Self {
+            handshake: 5.0,
+            useful_message: 0.5,
+            fast_rtt: Duration::from_millis(100),
+            fast_rtt_reward: 1.0,
+            slow_rtt: Duration::from_millis(1000),
+            slow_rtt_penalty: -1.0,
+            dial_failed: -5.0,
+            invalid_message: -10.0,
+            protocol_violation: -25.0,
+            spam: -15.0,
+            half_life: Duration::from_secs(60 * 60),
+            max_score: 100.0,
+            evict_below: -20.0,
+            ban_below: -50.0,
+        }
+    }
+}
+
+impl ScoreConfig {
+    /// Score change for one event.
+    pub fn delta(&self, event: PeerEvent) -> f64 {
+        match event {
+            PeerEvent::Handshake => self.handshake,
+            PeerEvent::Rtt(rtt) if rtt <= self.fast_rtt => self.fast_rtt_reward,
+            PeerEvent::Rtt(rtt) if rtt > self.slow_rtt => self.slow_rtt_penalty,
+            PeerEvent::Rtt(_) => 0.0,
+            PeerEvent::UsefulMessage => self.useful_message,
+            PeerEvent::DialFailed => self.dial_failed,
+            PeerEvent::InvalidMessage => self.invalid_message,
+            PeerEvent::ProtocolViolation => self.protocol_violation,
+            PeerEvent::Spam => self.spam,
+        }
+    }
+}
+
+/// A peer's score as of `updated`. Decay is applied lazily when the score is
+/// read or changed, so nothing has to walk the peer table on a timer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Reputation {
+    pub score: f64,
+    #[serde(default, with = "super::peer_record::unix_ms")]
+    pub updated: Option<SystemTime>,
+}
+
+impl Reputation {
+    /// The score decayed up to `now`.
+    pub fn score_at(&self, now: SystemTime, config: &ScoreConfig) -> f64 {
+        let Some(updated) = self.updated else {
+            return self.score;
+        };
+        let elapsed = now.duration_since(updated).unwrap_or_default().as_secs_f64();
+        let half_life = config.half_life.as_secs_f64().max(f64::MIN_POSITIVE);
+        self.score * 0.5f64.powf(elapsed / half_life)
+    }
+
+    /// Apply an event at `now` and return the new score.
+    pub fn apply(&mut self, event: PeerEvent, now: SystemTime, config: &ScoreConfig) -> f64 {
+        let score = self.score_at(now, config) + config.delta(event);
+        self.score = score.clamp(-config.max_score, config.max_score);
+        self.updated = Some(now);
+        self.score
+    }
+}
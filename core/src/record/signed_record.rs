@@ -0,0 +1,174 @@
+// signed_record.rs
+//? Peer records signed by the peer they describe
+//? A peer signs its own addresses, a sequence number and an expiry with its
+//? KeyPair. Anyone may relay the record; whoever receives it can check that the
+//? peer ID is the hash of the key and that the key signed the record.
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "identity_integration")]
+use super::peer_record::to_unix_ms;
+#[cfg(feature = "identity_integration")]
+use identity::{Algorithm, KeyPair, PeerID, PeerIDGeneration};
+
+use super::peer_addr::{AddrSource, PeerAddr};
+use super::peer_record::{from_unix_ms, PeerRecord};
+use super::record_error::RecordError;
+use crate::proto;
+
+/// Keeps record signatures from being valid as any other signed message.
+const SIGNING_DOMAIN: &[u8] = b"nautilus/peer-record/v1";
+
+/// Rust side of `SignedPeerRecord` in the wire schema.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedPeerRecord {
+    pub peer_id: String,
+    pub public_key: String,
+    pub addrs: Vec<SocketAddr>,
+    pub sequence: u64,   // A higher sequence replaces a lower one
+    pub expires_ms: u64, // Milliseconds since the Unix epoch
+    pub signature: Vec<u8>,
+}
+
+impl SignedPeerRecord {
+    /// Sign `addrs` as the peer owning `key_pair`, valid until `expires`.
+    #[cfg(feature = "identity_integration")]
+    pub fn sign(
+        peer_id: &str,
+        key_pair: &KeyPair,
+        addrs: Vec<SocketAddr>,
+        sequence: u64,
+        expires: SystemTime,
+    ) -> Result<Self, RecordError> {
+        let mut record = Self {
+            peer_id: peer_id.to_string(),
+            public_key: key_pair.public_key.clone(),
+            addrs,
+            sequence,
+            expires_ms: to_unix_ms(expires),
+            signature: Vec::new(),
+        };
+        record.signature = key_pair.sign(&record.signing_bytes()).map_err(RecordError::Signing)?;
+        Ok(record)
+    }
+
+    /// Check the record is unexpired, that the peer ID belongs to the public
+    /// key, and that the key signed it.
+    #[cfg(feature = "identity_integration")]
+    pub fn verify(&self, now: SystemTime) -> Result<(), RecordError> {
+        if self.is_expired(now) {
+            return Err(RecordError::Expired(self.peer_id.clone()));
+        }
+        if self.addrs.is_empty() {
+            return Err(RecordError::Malformed(format!("record for {} lists no address", self.peer_id)));
+        }
+        if !peer_id_matches_key(&self.peer_id, &self.public_key) {
+            return Err(RecordError::PeerIdMismatch(self.peer_id.clone()));
+        }
+        // Only RSA keys are generated by identity today
+        let valid = KeyPair::verify_with_public_key(
+            Algorithm::RSA,
+            &self.public_key,
+            &self.signing_bytes(),
+            &self.signature,
+        )
+        .map_err(RecordError::BadSignature)?;
+        match valid {
+            true => Ok(()),
+            false => Err(RecordError::BadSignature(format!("record for {} does not verify", self.peer_id))),
+        }
+    }
+
+    /// Bytes covered by the signature: every field except the signature itself.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = SIGNING_DOMAIN.to_vec();
+        for field in [&self.peer_id, &self.public_key] {
+            bytes.extend_from_slice(&(field.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(field.as_bytes());
+        }
+        bytes.extend_from_slice(&(self.addrs.len() as u32).to_be_bytes());
+        for addr in &self.addrs {
+            let addr = addr.to_string();
+            bytes.extend_from_slice(&(addr.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(addr.as_bytes());
+        }
+        bytes.extend_from_slice(&self.sequence.to_be_bytes());
+        bytes.extend_from_slice(&self.expires_ms.to_be_bytes());
+        bytes
+    }
+
+    pub fn expires(&self) -> SystemTime {
+        from_unix_ms(self.expires_ms)
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires() <= now
+    }
+
+    /// Whether this record should replace `current`.
+    pub fn supersedes(&self, current: &SignedPeerRecord) -> bool {
+        self.sequence > current.sequence
+    }
+
+    /// A peer record holding this one, with its addresses valid until it
+    /// expires. The record must list an address, as verified ones do.
+    pub fn to_peer_record(&self, source: AddrSource) -> PeerRecord {
+        let ttl = self.expires().duration_since(SystemTime::now()).unwrap_or(Duration::ZERO);
+        let addrs: Vec<PeerAddr> = self
+            .addrs
+            .iter()
+            .map(|addr| PeerAddr::tcp(*addr, source).with_ttl(Some(ttl)))
+            .collect();
+        PeerRecord {
+            addrs,
+            peer_id: Some(self.peer_id.clone()),
+            public_key: Some(self.public_key.clone()),
+            signed_record: Some(self.clone()),
+            ..PeerRecord::new(self.addrs[0])
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        proto::SignedPeerRecord {
+            peer_id: self.peer_id.clone(),
+            public_key: self.public_key.clone(),
+            addrs: self.addrs.iter().map(SocketAddr::to_string).collect(),
+            sequence: self.sequence,
+            expires_ms: self.expires_ms,
+            signature: self.signature.clone(),
+        }
+        .encode_to_vec()
+    }
+
+    /// Decode a record. Only its shape is checked here; use `verify` before trusting it.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RecordError> {
+        let wire = proto::SignedPeerRecord::decode(bytes).map_err(|e| RecordError::Malformed(e.to_string()))?;
+        let addrs = wire
+            .addrs
+            .iter()
+            .map(|addr| addr.parse().map_err(|_| RecordError::Malformed(format!("bad address {:?}", addr))))
+            .collect::<Result<Vec<SocketAddr>, _>>()?;
+        if addrs.is_empty() {
+            return Err(RecordError::Malformed(format!("record for {} lists no address", wire.peer_id)));
+        }
+        Ok(Self {
+            peer_id: wire.peer_id,
+            public_key: wire.public_key,
+            addrs,
+            sequence: wire.sequence,
+            expires_ms: wire.expires_ms,
+            signature: wire.signature,
+        })
+    }
+}
+
+/// Whether `peer_id` is a hash of `public_key` the way identity derives IDs.
+/// Hash-derived IDs are the only ones a key can vouch for.
+#[cfg(feature = "identity_integration")]
+pub(crate) fn peer_id_matches_key(peer_id: &str, public_key: &str) -> bool {
+    [PeerIDGeneration::SHA256, PeerIDGeneration::SHA512]
+        .into_iter()
+        .any(|hash| PeerID::generate(Some(hash), public_key) == peer_id)
+}
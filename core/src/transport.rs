@@ -3,104 +3,601 @@
 //? Responsible for Transporting Data between Machines
 use std::io;
 use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{broadcast, watch};
+use tokio::time::Instant;
+use tracing::{debug, error, info, trace, warn};
 
 mod tcp_transport;
 mod udp_transport;
+mod transport_error;
+#[cfg(feature = "identity_integration")]
+mod envelope;
+mod lifecycle;
+mod blob;
+mod substream;
+mod scheduler;
+mod throttle;
+mod pex;
+mod bootstrap;
+mod bans;
+mod handshake;
+pub mod wire;
 
 
+use blob::Blobs;
+use pex::Pex;
+use substream::Substreams;
 use tcp_transport::TcpTransport;
 use udp_transport::UdpTransport;
-use crate::record::{PeerManagement,PeerRecord};
+pub use transport_error::TransportError;
+pub use lifecycle::{Lifecycle, SendGuard};
+pub use scheduler::{Priority, SendOptions};
+pub use substream::Substream;
+pub use blob::{blob_id_hex, build_manifest, BlobEvent, BlobId, DEFAULT_CHUNK_SIZE, DEFAULT_PARALLELISM};
+pub use throttle::{Flow, FlowStats, RateLimit, Throttle, ThrottleStats, TrafficStats};
+pub use pex::PexConfig;
+pub use bootstrap::{BootstrapConfig, BootstrapPeer, BOOTSTRAP_TAG};
+#[cfg(feature = "identity_integration")]
+pub use envelope::{Envelope, EnvelopeSigner, EnvelopeVerifier, DEFAULT_REPLAY_WINDOW};
+use crate::record::{
+    AddrSource, AddrTransport, AutosaveConfig, BanList, PeerAddr, PeerEvent, PeerManagement, PeerRecord,
+};
+#[cfg(feature = "identity_integration")]
+use crate::record::SignedPeerRecord;
+use crate::proto::{Data, Goodbye, Handshake, MessageKind, Ping, Pong, TransportEnvelope};
+
+#[cfg(feature = "identity_integration")]
+use identity::Identity;
+#[cfg(feature = "identity_integration")]
+use std::sync::Arc;
+
+/// Largest message a single read from a listener will hand over.
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// How long shutdown waits for in-flight sends and tasks when triggered
+/// through `start_listeners`.
+pub const DEFAULT_SHUTDOWN_DEADLINE: Duration = Duration::from_secs(5);
+
+/// A message delivered to the application by the listeners.
+#[derive(Clone, Debug)]
+pub struct InboundMessage {
+    pub source: SocketAddr,
+    pub peer_id: Option<String>, // Verified sender, only set for signed envelopes
+    pub sequence: Option<u64>,
+    pub payload: Vec<u8>,
+}
+
 #[derive(Clone)]
 pub struct NautilusTransport {
     pub tcp: TcpTransport,
     pub udp: UdpTransport,
     peer_manager : PeerManagement,
+    inbound: broadcast::Sender<InboundMessage>,
+    lifecycle: Lifecycle,
+    throttle: Throttle,
+    bans: BanList,
+    blobs: Blobs,
+    substreams: Substreams,
+    pex: Pex,
+    #[cfg(feature = "identity_integration")]
+    signer: Option<Arc<EnvelopeSigner>>,
+    #[cfg(feature = "identity_integration")]
+    verifier: Arc<EnvelopeVerifier>,
+    #[cfg(feature = "identity_integration")]
+    local_record: Arc<std::sync::Mutex<Option<SignedPeerRecord>>>, // This node's own signed record
 }
 
 impl NautilusTransport {
-    /// Create a new UnifiedTransport instance for the given port.
+    /// Create a new UnifiedTransport instance for the given port. Bans are
+    /// kept in memory only; see `with_ban_list` to persist them.
     pub async fn new(port: u16) -> io::Result<Self> {
+        Self::with_ban_list(port, BanList::new()).await
+    }
+
+    /// Create a transport that checks and records bans in `bans`, e.g. one
+    /// from `BanList::open` to keep them between runs.
+    pub async fn with_ban_list(port: u16, bans: BanList) -> io::Result<Self> {
         let addr: SocketAddr = format!("0.0.0.0:{}", port).parse().unwrap();
 
         // Initialize TCP and UDP transports
-        let tcp_transport = TcpTransport::new(addr);
-        let udp_transport = UdpTransport::new(addr).await?;
+        let lifecycle = Lifecycle::new();
+        let throttle = Throttle::new();
+        let tcp_transport = TcpTransport::new(addr, lifecycle.clone(), throttle.clone(), bans.clone());
+        let udp_transport = UdpTransport::new(addr, throttle.clone(), bans.clone()).await?;
 
 
         // Initialize Peer Management
-        let peer_manager = PeerManagement::new("KPR.json".to_string());
+        let peer_manager = PeerManagement::new("KPR.json".to_string()).with_autosave(AutosaveConfig::default());
         peer_manager.load_from_file().await?; // Load peers from cache
 
+        let (inbound, _) = broadcast::channel(100);
+
         Ok(NautilusTransport {
             tcp: tcp_transport,
             udp: udp_transport,
-            peer_manager
+            peer_manager,
+            inbound,
+            lifecycle,
+            throttle,
+            bans,
+            blobs: Blobs::default(),
+            substreams: Substreams::default(),
+            pex: Pex::default(),
+            #[cfg(feature = "identity_integration")]
+            signer: None,
+            #[cfg(feature = "identity_integration")]
+            verifier: Arc::new(EnvelopeVerifier::default()),
+            #[cfg(feature = "identity_integration")]
+            local_record: Default::default(),
         })
     }
-    /// Start both TCP and UDP listeners.
-    pub async fn start_listeners(&self, mut shutdown_rx: tokio::sync::watch::Receiver<bool>) -> io::Result<()> {
-        let (tx, mut rx) = tokio::sync::mpsc::channel(100);
+
+    /// Create a transport that signs every outgoing message with the identity
+    /// and only delivers incoming messages whose envelope verifies. Handshakes
+    /// prove the identity, and peers that cannot prove theirs are refused.
+    #[cfg(feature = "identity_integration")]
+    pub async fn with_identity(port: u16, identity: &Identity) -> io::Result<Self> {
+        Self::with_identity_and_ban_list(port, identity, BanList::new()).await
+    }
+
+    /// `with_identity`, checking and recording bans in `bans` as
+    /// `with_ban_list` does.
+    #[cfg(feature = "identity_integration")]
+    pub async fn with_identity_and_ban_list(port: u16, identity: &Identity, bans: BanList) -> io::Result<Self> {
+        let mut transport = Self::with_ban_list(port, bans).await?;
+        let signer = Arc::new(EnvelopeSigner::new(identity));
+        transport.tcp.set_signer(signer.clone());
+        transport.signer = Some(signer);
+        Ok(transport)
+    }
+
+    /// Sign the addresses this node can be reached at, valid for `ttl`. The
+    /// record goes out with every peer exchange from now on, and each call
+    /// replaces the previous one with a higher sequence number.
+    #[cfg(feature = "identity_integration")]
+    pub fn publish_record(&self, addrs: Vec<SocketAddr>, ttl: Duration) -> Result<SignedPeerRecord, TransportError> {
+        let signer = self
+            .signer
+            .as_ref()
+            .ok_or_else(|| TransportError::BadSignature("no identity to sign the record with".to_string()))?;
+        if addrs.is_empty() {
+            return Err(TransportError::InvalidEnvelope("a peer record needs an address".to_string()));
+        }
+        let mut local = self.local_record.lock().unwrap();
+        // Seeded from the clock so a restarted node still moves forward
+        let sequence = local
+            .as_ref()
+            .map_or(0, |previous| previous.sequence + 1)
+            .max(wire::now_ms());
+        let record = SignedPeerRecord::sign(
+            signer.peer_id(),
+            signer.key_pair(),
+            addrs,
+            sequence,
+            SystemTime::now() + ttl,
+        )?;
+        *local = Some(record.clone());
+        Ok(record)
+    }
+
+    /// Receive the messages accepted by `start_listeners`.
+    pub fn subscribe(&self) -> broadcast::Receiver<InboundMessage> {
+        self.inbound.subscribe()
+    }
+    /// Start both TCP and UDP listeners and handle incoming messages.
+    /// When `shutdown_rx` flips to true the whole transport is shut down with
+    /// `shutdown`, and this returns once everything has stopped.
+    pub async fn start_listeners(&self, mut shutdown_rx: watch::Receiver<bool>) -> io::Result<()> {
+        // One channel per transport, so a frame's origin is never in doubt
+        let (tcp_sender, mut tcp_rx) = tokio::sync::mpsc::channel(100);
+        let (udp_sender, mut udp_rx) = tokio::sync::mpsc::channel(100);
     
         let tcp = self.tcp.clone();
-        let tcp_sender = tx.clone();
-        let mut shutdown_rx_tcp = shutdown_rx.clone();
-        tokio::spawn(async move {
-            if let Err(e) = tcp.listen(tcp_sender, shutdown_rx_tcp).await {
-                eprintln!("Error in TCP listener: {}", e);
+        let tcp_shutdown = self.lifecycle.shutdown_signal();
+        self.lifecycle.spawn(async move {
+            if let Err(e) = tcp.listen(tcp_sender, tcp_shutdown).await {
+                error!(error.kind = ?e.kind(), error = %e, "TCP listener failed");
             }
         });
     
         let udp = self.udp.clone();
-        let mut shutdown_rx_udp = shutdown_rx.clone();
-        tokio::spawn(async move {
-            if let Err(e) = udp.listen(tx).await {
-                eprintln!("Error in UDP listener: {}", e);
-            }
-            while shutdown_rx_udp.changed().await.is_ok() {
-                if *shutdown_rx_udp.borrow() {
-                    println!("Shutting down UDP listener.");
-                    break;
-                }
+        let udp_shutdown = self.lifecycle.shutdown_signal();
+        self.lifecycle.spawn(async move {
+            if let Err(e) = udp.listen(udp_sender, udp_shutdown).await {
+                error!(error.kind = ?e.kind(), error = %e, "UDP listener failed");
             }
         });
     
+        let peer_manager = self.peer_manager.clone();
+        let maintenance_shutdown = self.lifecycle.shutdown_signal();
+        self.lifecycle.spawn(peer_manager.run_maintenance(maintenance_shutdown));
+        let autosave_shutdown = self.lifecycle.shutdown_signal();
+        self.lifecycle.spawn(self.peer_manager.clone().run_autosave(autosave_shutdown));
+
+        let pex_shutdown = self.lifecycle.shutdown_signal();
+        self.lifecycle.spawn(self.clone().run_pex(pex_shutdown));
+
         // Handle incoming messages and update peers
-        while !*shutdown_rx.borrow() {
-            if let Ok(Some((addr, data))) = tokio::select! {
-                recv = rx.recv() => Ok(recv),
-                _ = shutdown_rx.changed() => Err(()),
-            } {
-                println!("start_listeners received message from {}: {}", addr, String::from_utf8_lossy(&data));
-                let peer_record = PeerRecord {
-                    addr,
-                    peer_id: None,        // Generate or resolve this if needed
-                    public_key: None,     // Set if available
-                    is_active: true,
-                    last_seen: Some(std::time::Instant::now()),
-                };
-    
-                println!("Attempting to add or update peer: {:?}", peer_record);
-    
-                self.peer_manager.add_or_update_peer(peer_record).await;
-                println!("Peer record updated for {}", addr);
+        let mut stopping = self.lifecycle.shutdown_signal();
+        let (mut tcp_open, mut udp_open) = (true, true);
+        while tcp_open || udp_open {
+            tokio::select! {
+                recv = tcp_rx.recv(), if tcp_open => match recv {
+                    Some((addr, data)) => self.receive(addr, data, AddrTransport::Tcp).await,
+                    None => tcp_open = false,
+                },
+                recv = udp_rx.recv(), if udp_open => match recv {
+                    Some((addr, data)) => self.receive(addr, data, AddrTransport::Udp).await,
+                    None => udp_open = false,
+                },
+                // A dropped sender can never ask for shutdown, so treat it as a request
+                changed = shutdown_rx.changed() => {
+                    let requested = changed.is_err() || *shutdown_rx.borrow();
+                    if requested {
+                        self.shutdown(DEFAULT_SHUTDOWN_DEADLINE).await?;
+                        break;
+                    }
+                }
+                // Someone else called `shutdown`. The watch guard is dropped
+                // inside the block so it never lives across an await.
+                _ = async { let _ = stopping.wait_for(|stopping| *stopping).await; } => break,
             }
         }
     
-        println!("Shutting down listeners.");
+        info!("listeners stopped");
+        Ok(())
+    }
+
+    /// Shut the transport down: refuse new sends, let in-flight ones finish
+    /// until the deadline, say goodbye to connected peers, stop and join every
+    /// task, and persist the peer cache.
+    pub async fn shutdown(&self, deadline: Duration) -> io::Result<()> {
+        let deadline = Instant::now() + deadline;
+        info!("shutting down transport");
+
+        self.lifecycle.stop_sends();
+        self.lifecycle.drain(deadline).await;
+        self.say_goodbye("shutdown").await;
+
+        self.lifecycle.signal_shutdown();
+        self.tcp.close_all().await?;
+        self.lifecycle.join(deadline).await;
+
+        self.save_peers().await?;
+        let traffic = self.throttle.stats().total;
+        info!(
+            bytes.up = traffic.upload.bytes,
+            bytes.down = traffic.download.bytes,
+            throttled.up = traffic.upload.throttled,
+            throttled.down = traffic.download.throttled,
+            "transport stopped"
+        );
+        Ok(())
+    }
+
+    /// Tell every connected peer this node is leaving.
+    async fn say_goodbye(&self, reason: &str) {
+        let goodbye = wire::encode(
+            MessageKind::Goodbye,
+            &Goodbye {
+                peer_id: self.local_peer_id(),
+                reason: reason.to_string(),
+            },
+        );
+        for addr in self.tcp.connected_peers().await {
+            if let Err(e) = self.tcp.send_with(addr, &goodbye, SendOptions::control()).await {
+                debug!(peer.addr = %addr, error.kind = ?e.kind(), error = %e, "failed to send goodbye");
+            }
+        }
+    }
+
+    fn local_peer_id(&self) -> String {
+        #[cfg(feature = "identity_integration")]
+        if let Some(signer) = &self.signer {
+            return signer.peer_id().to_string();
+        }
+        String::new()
+    }
+
+    /// Hand a frame from a listener to `accept_message` and publish what comes out.
+    async fn receive(&self, addr: SocketAddr, data: Vec<u8>, transport: AddrTransport) {
+        trace!(peer.addr = %addr, bytes = data.len(), ?transport, "message received");
+        if let Some(message) = self.accept_message(addr, data, transport).await {
+            // No subscribers just means nobody is interested yet
+            let _ = self.inbound.send(message);
+        }
+    }
+
+    /// Decode a frame from the listeners and dispatch it by message kind.
+    /// Only application payloads come back out; with an identity configured
+    /// those must be signed envelopes that verify.
+    async fn accept_message(&self, addr: SocketAddr, data: Vec<u8>, transport: AddrTransport) -> Option<InboundMessage> {
+        let key = self.peer_key(addr).await;
+        if self.is_refused(&key).await {
+            trace!(peer.addr = %addr, peer.key = %key, bytes = data.len(), "dropped frame from banned peer");
+            return None;
+        }
+
+        let envelope = match wire::decode(&data) {
+            Ok(envelope) => envelope.message,
+            Err(e) => {
+                warn!(peer.addr = %addr, bytes = data.len(), error.kind = ?e.kind(), error = %e, "rejected undecodable frame");
+                self.peer_manager.report(&key, PeerEvent::InvalidMessage).await;
+                return None;
+            }
+        };
+
+        let result = match MessageKind::from_i32(envelope.kind) {
+            Some(MessageKind::Handshake) | Some(MessageKind::HandshakeAck) => match transport {
+                AddrTransport::Tcp => self.handle_handshake(addr).await.map(|_| None),
+                AddrTransport::Udp => {
+                    debug!(peer.addr = %addr, "dropped handshake sent over UDP");
+                    Ok(None)
+                }
+            },
+            // Only meaningful inside the TCP handshake, which consumes it
+            Some(MessageKind::HandshakeProof) => Ok(None),
+            Some(MessageKind::Ping) => self.handle_ping(addr, &envelope).await.map(|_| None),
+            Some(MessageKind::Pong) => self.handle_pong(addr, &envelope).await.map(|_| None),
+            // Learned peers are attributed to the relay, which must have shaken hands
+            Some(MessageKind::PeerExchange) => match transport {
+                AddrTransport::Tcp if self.tcp.remote_handshake(addr).await.is_some() => {
+                    self.handle_peer_exchange(addr, &envelope).await.map(|_| None)
+                }
+                _ => {
+                    debug!(peer.addr = %addr, ?transport, "dropped peer exchange from an unknown connection");
+                    Ok(None)
+                }
+            },
+            Some(MessageKind::Data) => self.open_data(addr, &envelope).await.map(Some),
+            Some(MessageKind::Signed) => self.open_envelope(addr, &envelope).await.map(Some),
+            Some(MessageKind::Goodbye) => self.handle_goodbye(addr, &envelope).await.map(|_| None),
+            // Blob replies are matched to the connection a request went out on
+            Some(MessageKind::BlobRequest | MessageKind::BlobManifest | MessageKind::BlobChunk)
+                if transport == AddrTransport::Udp =>
+            {
+                debug!(peer.addr = %addr, kind = envelope.kind, "dropped blob message sent over UDP");
+                Ok(None)
+            }
+            Some(MessageKind::BlobRequest) => self.handle_blob_request(addr, &envelope).map(|_| None),
+            Some(MessageKind::BlobManifest) => self.handle_blob_manifest(addr, &envelope).map(|_| None),
+            Some(MessageKind::BlobChunk) => self.handle_blob_chunk(addr, &envelope).map(|_| None),
+            // Streams run over connections; a datagram can be spoofed into one
+            Some(MessageKind::Stream) => match transport {
+                AddrTransport::Tcp => self.handle_stream_frame(addr, &envelope).map(|_| None),
+                AddrTransport::Udp => {
+                    debug!(peer.addr = %addr, "dropped stream frame sent over UDP");
+                    Ok(None)
+                }
+            },
+            Some(MessageKind::Unspecified) | None => {
+                // Sent by a newer node; skip it rather than fail the connection
+                debug!(peer.addr = %addr, kind = envelope.kind, "ignoring unknown message kind");
+                Ok(None)
+            }
+        };
+
+        let event = match &result {
+            Ok(Some(_)) => Some(PeerEvent::UsefulMessage),
+            Ok(None) => None,
+            Err(TransportError::InvalidEnvelope(_)) => Some(PeerEvent::InvalidMessage),
+            Err(TransportError::BadSignature(_)) => Some(PeerEvent::ProtocolViolation),
+            Err(TransportError::Replay(_)) => Some(PeerEvent::Spam),
+            Err(_) => None,
+        };
+        if let Some(event) = event {
+            self.peer_manager.report(&key, event).await;
+        }
+
+        result.unwrap_or_else(|e| {
+            warn!(peer.addr = %addr, bytes = data.len(), error.kind = e.kind(), error = %e, "rejected message");
+            None
+        })
+    }
+
+    /// Key of the peer behind `addr` in the peer table: its ID when the
+    /// connection announced one, otherwise the address itself.
+    async fn peer_key(&self, addr: SocketAddr) -> String {
+        match self.tcp.peer_id(addr).await {
+            Some(peer_id) => peer_id,
+            None => addr.to_string(),
+        }
+    }
+
+    /// Record the peer behind a TCP connection under its listening address.
+    /// The frame itself is not trusted: what gets recorded is what the
+    /// connection's handshake established, with a public key only if it was proven.
+    async fn handle_handshake(&self, addr: SocketAddr) -> Result<(), TransportError> {
+        let Some(handshake) = self.tcp.remote_handshake(addr).await else {
+            debug!(peer.addr = %addr, "ignoring handshake outside an established connection");
+            return Ok(());
+        };
+        self.record_handshake(addr, &handshake, AddrSource::Announced).await;
+        Ok(())
+    }
+
+    /// `source` says how far to trust the listening address: `Dial` when this
+    /// node just connected to it, `Announced` when the peer connected to us.
+    async fn record_handshake(&self, addr: SocketAddr, handshake: &Handshake, source: AddrSource) {
+        let listen_addr = match handshake.listen_port {
+            0 => addr,
+            port => SocketAddr::new(addr.ip(), port as u16),
+        };
+        let peer_record = PeerRecord {
+            addrs: vec![PeerAddr::tcp(listen_addr, source)],
+            peer_id: Some(handshake.peer_id.clone()).filter(|id| !id.is_empty()),
+            public_key: Some(handshake.public_key.clone()).filter(|key| !key.is_empty()),
+            is_active: true,
+            last_seen: Some(SystemTime::now()),
+            ..PeerRecord::new(listen_addr)
+        };
+        let key = peer_record.peer_id.clone().unwrap_or_else(|| listen_addr.to_string());
+        debug!(peer.addr = %listen_addr, peer.id = %handshake.peer_id, agent = %handshake.agent, "peer handshake recorded");
+        self.peer_manager.add_or_update_peer(peer_record).await;
+        self.peer_manager.record_connected(&key).await;
+        if source == AddrSource::Dial {
+            self.peer_manager.record_addr_success(&key, listen_addr).await;
+        }
+
+        // Give a new neighbour peers to start from instead of waiting a round
+        let transport = self.clone();
+        self.lifecycle.spawn(async move {
+            if let Err(e) = transport.exchange_peers(addr).await {
+                debug!(peer.addr = %addr, error.kind = ?e.kind(), error = %e, "peer exchange failed");
+            }
+        });
+    }
+
+    /// Answer a ping on the connection it arrived on, or over UDP.
+    async fn handle_ping(&self, addr: SocketAddr, envelope: &TransportEnvelope) -> Result<(), TransportError> {
+        let ping = wire::decode_body::<Ping>(envelope)?.message;
+        let pong = wire::encode(
+            MessageKind::Pong,
+            &Pong {
+                nonce: ping.nonce,
+                timestamp_ms: ping.timestamp_ms,
+            },
+        );
+        if self.tcp.is_connected(addr).await {
+            self.tcp.send_with(addr, &pong, SendOptions::control()).await?;
+        } else {
+            self.udp.send(addr, &pong).await?;
+        }
+        Ok(())
+    }
+
+    /// A peer is leaving: drop its connection and mark it inactive.
+    async fn handle_goodbye(&self, addr: SocketAddr, envelope: &TransportEnvelope) -> Result<(), TransportError> {
+        let goodbye = wire::decode_body::<Goodbye>(envelope)?.message;
+        info!(peer.addr = %addr, peer.id = %goodbye.peer_id, reason = %goodbye.reason, "peer said goodbye");
+        self.tcp.disconnect(addr).await;
+        let key = match goodbye.peer_id.as_str() {
+            "" => addr.to_string(),
+            peer_id => peer_id.to_string(),
+        };
+        self.peer_manager.mark_inactive(&key).await;
+        Ok(())
+    }
+
+    async fn handle_pong(&self, addr: SocketAddr, envelope: &TransportEnvelope) -> Result<(), TransportError> {
+        let pong = wire::decode_body::<Pong>(envelope)?.message;
+        let rtt_ms = wire::now_ms().saturating_sub(pong.timestamp_ms);
+        debug!(peer.addr = %addr, nonce = pong.nonce, rtt_ms, "pong received");
+        let key = self.peer_key(addr).await;
+        self.peer_manager.report(&key, PeerEvent::Rtt(Duration::from_millis(rtt_ms))).await;
+        Ok(())
+    }
+
+    /// Deliver an unsigned payload; refused when this node requires signatures.
+    async fn open_data(&self, addr: SocketAddr, envelope: &TransportEnvelope) -> Result<InboundMessage, TransportError> {
+        #[cfg(feature = "identity_integration")]
+        if self.signer.is_some() {
+            return Err(TransportError::InvalidEnvelope("unsigned payload".to_string()));
+        }
+
+        let data = wire::decode_body::<Data>(envelope)?.message;
+        let transport = match self.tcp.is_connected(addr).await {
+            true => AddrTransport::Tcp,
+            false => AddrTransport::Udp,
+        };
+        let peer_record = PeerRecord {
+            addrs: vec![PeerAddr::new(addr, transport, AddrSource::Observed)],
+            is_active: true,
+            last_seen: Some(SystemTime::now()),
+            ..PeerRecord::new(addr)
+        };
+
+        self.peer_manager.add_or_update_peer(peer_record).await;
+        Ok(InboundMessage {
+            source: addr,
+            peer_id: None,
+            sequence: None,
+            payload: data.payload,
+        })
+    }
+
+    /// Verify a signed envelope against the sender's cached public key.
+    #[cfg(feature = "identity_integration")]
+    async fn open_envelope(&self, addr: SocketAddr, envelope: &TransportEnvelope) -> Result<InboundMessage, TransportError> {
+        let envelope = Envelope::from_bytes(&envelope.body)?;
+        let public_key = self
+            .peer_manager
+            .get_peer(&envelope.sender_id)
+            .await
+            .and_then(|peer| peer.public_key)
+            .ok_or_else(|| TransportError::UnknownSender(envelope.sender_id.clone()))?;
+
+        self.verifier.verify(&envelope, &public_key)?;
+        self.peer_manager.mark_seen(&envelope.sender_id).await;
+        trace!(peer.addr = %addr, peer.id = %envelope.sender_id, sequence = envelope.sequence, bytes = envelope.payload.len(), "verified envelope");
+
+        Ok(InboundMessage {
+            source: addr,
+            peer_id: Some(envelope.sender_id),
+            sequence: Some(envelope.sequence),
+            payload: envelope.payload,
+        })
+    }
+
+    /// Without identity support signed envelopes cannot be checked, so none are delivered.
+    #[cfg(not(feature = "identity_integration"))]
+    async fn open_envelope(&self, _addr: SocketAddr, _envelope: &TransportEnvelope) -> Result<InboundMessage, TransportError> {
+        Err(TransportError::InvalidEnvelope(
+            "signed envelopes need the identity_integration feature".to_string(),
+        ))
+    }
+
+    /// Encode outgoing data for the wire: a signed envelope when an identity is
+    /// configured, a plain data message otherwise.
+    fn seal(&self, data: &[u8]) -> Result<Vec<u8>, TransportError> {
+        #[cfg(feature = "identity_integration")]
+        if let Some(signer) = &self.signer {
+            return Ok(wire::encode(MessageKind::Signed, &signer.seal(data)?));
+        }
+        Ok(wire::encode(MessageKind::Data, &Data { payload: data.to_vec() }))
+    }
+
+    /// Ping a peer; the answer is logged with its round-trip time.
+    pub async fn ping(&self, peer_addr: SocketAddr) -> io::Result<()> {
+        let ping = wire::encode(
+            MessageKind::Ping,
+            &Ping {
+                nonce: rand::random(),
+                timestamp_ms: wire::now_ms(),
+            },
+        );
+        if self.tcp.is_connected(peer_addr).await {
+            self.tcp.send_with(peer_addr, &ping, SendOptions::control()).await?;
+        } else {
+            self.udp.send(peer_addr, &ping).await?;
+        }
         Ok(())
     }
 
     /// Send a message to a specific peer using TCP or UDP.
     pub async fn send(&self, peer_addr: SocketAddr, data: &[u8]) -> io::Result<()> {
+        self.send_with(peer_addr, data, SendOptions::default()).await
+    }
+
+    /// Send with a priority class and an optional deadline. Over TCP the
+    /// message waits in the peer's outbound queue; once the deadline passes it
+    /// is dropped instead of being written.
+    pub async fn send_with(&self, peer_addr: SocketAddr, data: &[u8], options: SendOptions) -> io::Result<()> {
+        let _in_flight = self.lifecycle.begin_send()?;
+        let data = &self.seal(data)?;
+
         // Attempt TCP first
-        if let Err(e) = self.tcp.send(peer_addr, data).await {
-            eprintln!("Failed to send via TCP to {}: {}", peer_addr, e);
+        if let Err(e) = self.tcp.send_with(peer_addr, data, options).await {
+            warn!(peer.addr = %peer_addr, bytes = data.len(), error.kind = ?e.kind(), error = %e, "TCP send failed");
         }
 
-        // Attempt UDP
+        // Attempt UDP, unless the message is already too late
+        if options.deadline.is_some_and(|deadline| deadline <= Instant::now()) {
+            return Ok(());
+        }
         if let Err(e) = self.udp.send(peer_addr, data).await {
-            eprintln!("Failed to send via UDP to {}: {}", peer_addr, e);
+            warn!(peer.addr = %peer_addr, bytes = data.len(), error.kind = ?e.kind(), error = %e, "UDP send failed");
         }
 
         Ok(())
@@ -108,40 +605,124 @@ impl NautilusTransport {
 
     /// Broadcast a message to all known peers via TCP and UDP.
     pub async fn broadcast(&self, data: &[u8]) -> io::Result<()> {
+        self.broadcast_with(data, SendOptions::default()).await
+    }
+
+    /// Broadcast with a priority class and an optional deadline.
+    pub async fn broadcast_with(&self, data: &[u8], options: SendOptions) -> io::Result<()> {
+        let _in_flight = self.lifecycle.begin_send()?;
+        let data = &self.seal(data)?;
+
         // Broadcast via TCP
-        if let Err(e) = self.tcp.broadcast(data).await {
-            eprintln!("Error broadcasting via TCP: {}", e);
+        if let Err(e) = self.tcp.send_all(data, options).await {
+            warn!(bytes = data.len(), error.kind = ?e.kind(), error = %e, "TCP broadcast failed");
         }
 
         // Broadcast via UDP
         if let Err(e) = self.udp.broadcast(data).await {
-            eprintln!("Error broadcasting via UDP: {}", e);
+            warn!(bytes = data.len(), error.kind = ?e.kind(), error = %e, "UDP broadcast failed");
         }
 
         Ok(())
     }
 pub async fn connect(&self, peer_addr: SocketAddr) -> io::Result<()> {
-    self.tcp.connect(peer_addr).await?;
+    let handshake = self.tcp.connect(peer_addr).await?;
 
-    println!("Successfully connected to peer: {}", peer_addr);
+    // Add or update the peer in PeerManagement with what it told us about itself
+    self.record_handshake(peer_addr, &handshake, AddrSource::Dial).await;
 
-    // Add or update the peer in PeerManagement
-    let peer_record = PeerRecord {
-        addr: peer_addr,
-        peer_id: None,        // Generate or resolve this if needed
-        public_key: None,     // Set if available
-        is_active: true,
-        last_seen: Some(std::time::Instant::now()),
-    };
+    Ok(())
+}
+
+    /// Connect to a peer by ID, reusing an open connection when one exists.
+    /// Known addresses are tried in turn; returns the address that connected.
+    pub async fn connect_to_peer(&self, peer_id: &str) -> Result<SocketAddr, TransportError> {
+        if self.is_refused(peer_id).await {
+            return Err(TransportError::Banned(peer_id.to_string()));
+        }
+        let addrs = self.peer_manager.get_peer_addrs(peer_id).await;
+        if addrs.is_empty() {
+            return Err(TransportError::UnknownPeer(peer_id.to_string()));
+        }
+
+        let mut last_error = String::from("no address could be dialed");
+        for addr in addrs {
+            if self.tcp.is_connected(addr).await {
+                return Ok(addr);
+            }
+            match self.tcp.connect(addr).await {
+                Ok(handshake) => {
+                    self.record_handshake(addr, &handshake, AddrSource::Dial).await;
+                    return Ok(addr);
+                }
+                Err(e) => {
+                    debug!(peer.id = %peer_id, peer.addr = %addr, error.kind = ?e.kind(), error = %e, "dial failed, trying next address");
+                    self.peer_manager.record_failure(peer_id).await;
+                    self.peer_manager.record_addr_failure(peer_id, addr).await;
+                    last_error = format!("{}: {}", addr, e);
+                }
+            }
+        }
 
-    println!("Adding or updating peer record after connection: {:?}", peer_record);
+        warn!(peer.id = %peer_id, error = %last_error, "peer unreachable");
+        Err(TransportError::Unreachable(peer_id.to_string(), last_error))
+    }
 
-    // Ensure peer is added to PeerManagement
-    self.peer_manager.add_or_update_peer(peer_record).await;
-    println!("Peer record updated for {}", peer_addr);
+    /// Send a message to a peer by ID over TCP.
+    /// A failed write drops that connection and moves on to the next known address.
+    pub async fn send_to_peer(&self, peer_id: &str, data: &[u8]) -> Result<(), TransportError> {
+        self.send_to_peer_with(peer_id, data, SendOptions::default()).await
+    }
 
-    Ok(())
-}
+    /// `send_to_peer` with a priority class and an optional deadline.
+    pub async fn send_to_peer_with(&self, peer_id: &str, data: &[u8], options: SendOptions) -> Result<(), TransportError> {
+        let _in_flight = self.lifecycle.begin_send()?;
+        if self.is_refused(peer_id).await {
+            return Err(TransportError::Banned(peer_id.to_string()));
+        }
+        let addrs = self.peer_manager.get_peer_addrs(peer_id).await;
+        if addrs.is_empty() {
+            return Err(TransportError::UnknownPeer(peer_id.to_string()));
+        }
+        let data = &self.seal(data)?;
+
+        let mut last_error = String::from("no address could be dialed");
+        for addr in addrs {
+            if !self.tcp.is_connected(addr).await {
+                match self.tcp.connect(addr).await {
+                    Ok(handshake) => self.record_handshake(addr, &handshake, AddrSource::Dial).await,
+                    Err(e) => {
+                        debug!(peer.id = %peer_id, peer.addr = %addr, error.kind = ?e.kind(), error = %e, "dial failed, trying next address");
+                        self.peer_manager.record_failure(peer_id).await;
+                        self.peer_manager.record_addr_failure(peer_id, addr).await;
+                        last_error = format!("{}: {}", addr, e);
+                        continue;
+                    }
+                }
+            }
+
+            match self.tcp.send_with(addr, data, options).await {
+                Ok(_) => {
+                    trace!(peer.id = %peer_id, peer.addr = %addr, bytes = data.len(), "sent to peer");
+                    self.peer_manager.mark_seen(peer_id).await;
+                    return Ok(());
+                }
+                // The connection is fine, the message was just too late
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                    debug!(peer.id = %peer_id, peer.addr = %addr, "send deadline passed, message dropped");
+                    return Err(TransportError::Expired);
+                }
+                Err(e) => {
+                    debug!(peer.id = %peer_id, peer.addr = %addr, error.kind = ?e.kind(), error = %e, "send failed, trying next address");
+                    self.tcp.disconnect(addr).await;
+                    last_error = format!("{}: {}", addr, e);
+                }
+            }
+        }
+
+        warn!(peer.id = %peer_id, bytes = data.len(), error = %last_error, "peer unreachable");
+        Err(TransportError::Unreachable(peer_id.to_string(), last_error))
+    }
 
     pub async fn remove_peer(&self, peer_addr: SocketAddr) {
         self.peer_manager
@@ -151,15 +732,13 @@ pub async fn connect(&self, peer_addr: SocketAddr) -> io::Result<()> {
 
     /// Save peers to cache during shutdown
     pub async fn save_peers(&self) -> io::Result<()> {
-        println!("Dumping peer map before saving:");
         self.peer_manager.debug_dump().await;
     
         if let Err(e) = self.peer_manager.save_to_file().await {
-            eprintln!("Error saving peers to file: {}", e);
+            error!(error.kind = ?e.kind(), error = %e, "error saving peers to file");
             return Err(e);
         }
-    
-        println!("Peers successfully saved.");
+
         Ok(())
     }
 
@@ -167,4 +746,16 @@ pub async fn connect(&self, peer_addr: SocketAddr) -> io::Result<()> {
         self.peer_manager.get_all_peers().await
     }
 
+    /// Access the peer table backing this transport.
+    pub fn peer_manager(&self) -> &PeerManagement {
+        &self.peer_manager
+    }
+
+    /// Bandwidth limits for TCP and UDP. Limits can be changed while running
+    /// and `stats()` reports how much traffic was held back.
+    pub fn throttle(&self) -> &Throttle {
+        &self.throttle
+    }
+
 }
+
@@ -0,0 +1,53 @@
+// bans.rs
+//? Banning peers, addresses and subnets from the transport, and lifting bans
+use std::time::Duration;
+
+use tracing::info;
+
+use super::{NautilusTransport, TransportError};
+use crate::record::{Ban, BanList, BanTarget};
+
+impl NautilusTransport {
+    /// The ban list TCP and UDP check, persisted if the transport was
+    /// created `with_ban_list` and a list from `BanList::open`.
+    pub fn ban_list(&self) -> &BanList {
+        &self.bans
+    }
+
+    /// Ban a peer ID, IP or subnet for `duration`, or until lifted when
+    /// `None`, and close the connections it covers. A peer ID ban only holds
+    /// against peers that have to prove their ID, which they do once this node
+    /// has an identity; without one, a peer can announce any ID it likes.
+    pub async fn ban(&self, target: BanTarget, reason: &str, duration: Option<Duration>) -> Result<Ban, TransportError> {
+        let ban = Ban::new(target, reason, duration);
+        self.bans.ban(ban.clone()).await?;
+
+        for addr in self.tcp.connected_peers().await {
+            let covered = match &ban.target {
+                BanTarget::PeerId(peer_id) => self.tcp.peer_id(addr).await.as_ref() == Some(peer_id),
+                BanTarget::Ip(_) | BanTarget::Subnet(_) => self.bans.ip_ban(addr.ip()).is_some(),
+            };
+            if covered {
+                info!(peer.addr = %addr, ban.target = %ban.target, "closing connection to banned peer");
+                self.tcp.disconnect(addr).await;
+            }
+        }
+        Ok(ban)
+    }
+
+    /// Lift the ban on `target`. Returns the ban lifted, if there was one.
+    pub async fn lift_ban(&self, target: &BanTarget) -> Result<Option<Ban>, TransportError> {
+        Ok(self.bans.lift(target).await?)
+    }
+
+    /// Every ban in force.
+    pub fn bans(&self) -> Vec<Ban> {
+        self.bans.list()
+    }
+
+    /// Whether a peer, by ID or by address key, is banned or scores too low
+    /// to talk to.
+    pub(super) async fn is_refused(&self, peer_key: &str) -> bool {
+        self.bans.peer_ban(peer_key).is_some() || self.peer_manager.is_banned(peer_key).await
+    }
+}
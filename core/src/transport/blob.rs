@@ -0,0 +1,440 @@
+// blob.rs
+//? Chunked, content-addressed blob transfer with per-chunk SHA-256 checks
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use sha2::{Digest, Sha256};
+use tokio::sync::{broadcast, oneshot};
+use tokio::task::JoinSet;
+use tokio::time::{sleep, timeout, Duration};
+use tracing::{debug, info, warn};
+
+use super::scheduler::{Priority, SendOptions};
+use super::{wire, NautilusTransport, TransportError};
+use crate::proto::{BlobChunk, BlobManifest, BlobRequest, MessageKind, TransportEnvelope};
+
+pub const DEFAULT_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Largest chunk accepted in a manifest, so one chunk always fits in a frame.
+pub const MAX_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Chunk requests kept in flight at once by `fetch_blob`.
+pub const DEFAULT_PARALLELISM: usize = 4;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_ATTEMPTS: u32 = 5;
+const RETRY_DELAY: Duration = Duration::from_millis(200);
+
+pub type BlobId = [u8; 32];
+
+/// What happened to a blob, for anyone following transfers.
+#[derive(Clone, Debug)]
+pub enum BlobEvent {
+    /// A peer told us about a blob it can serve.
+    Advertised { from: SocketAddr, manifest: BlobManifest },
+    Progress {
+        blob_id: BlobId,
+        chunks_done: usize,
+        chunks_total: usize,
+        bytes_done: u64,
+        bytes_total: u64,
+    },
+    Completed { blob_id: BlobId },
+    Failed { blob_id: BlobId, error: String },
+}
+
+pub fn sha256(data: &[u8]) -> BlobId {
+    Sha256::digest(data).into()
+}
+
+pub fn blob_id_hex(blob_id: &[u8]) -> String {
+    blob_id.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Split content into chunks and describe it.
+pub fn build_manifest(data: &[u8], chunk_size: usize, name: &str) -> BlobManifest {
+    let chunk_size = chunk_size.clamp(1, MAX_CHUNK_SIZE);
+    BlobManifest {
+        blob_id: sha256(data).to_vec(),
+        size: data.len() as u64,
+        chunk_size: chunk_size as u32,
+        chunk_hashes: data.chunks(chunk_size).map(|chunk| sha256(chunk).to_vec()).collect(),
+        name: name.to_string(),
+        missing: false,
+    }
+}
+
+/// Check that a manifest received from a peer is self-consistent.
+fn validate_manifest(manifest: &BlobManifest) -> Result<BlobId, TransportError> {
+    let invalid = |reason: &str| Err(TransportError::InvalidEnvelope(format!("blob manifest {}", reason)));
+    let Ok(blob_id) = BlobId::try_from(manifest.blob_id.as_slice()) else {
+        return invalid("has a malformed blob ID");
+    };
+    let chunk_size = manifest.chunk_size as u64;
+    if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE as u64 {
+        return invalid("has an unsupported chunk size");
+    }
+    if manifest.chunk_hashes.len() as u64 != manifest.size.div_ceil(chunk_size) {
+        return invalid("does not list one hash per chunk");
+    }
+    if manifest.chunk_hashes.iter().any(|hash| hash.len() != 32) {
+        return invalid("has a malformed chunk hash");
+    }
+    Ok(blob_id)
+}
+
+struct Published {
+    manifest: BlobManifest,
+    data: Vec<u8>,
+}
+
+impl Published {
+    fn chunk(&self, index: usize) -> Option<&[u8]> {
+        let chunk_size = self.manifest.chunk_size as usize;
+        self.data.chunks(chunk_size).nth(index)
+    }
+}
+
+/// Chunks received so far. Kept across failed attempts so the next
+/// `fetch_blob` only asks for what is still missing.
+struct Download {
+    manifest: BlobManifest,
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+impl Download {
+    fn progress(&self, blob_id: BlobId) -> BlobEvent {
+        let received = self.chunks.iter().flatten();
+        BlobEvent::Progress {
+            blob_id,
+            chunks_done: received.clone().count(),
+            chunks_total: self.chunks.len(),
+            bytes_done: received.map(|chunk| chunk.len() as u64).sum(),
+            bytes_total: self.manifest.size,
+        }
+    }
+}
+
+enum Reply {
+    Manifest(BlobManifest),
+    Chunk(Vec<u8>),
+    Missing,
+}
+
+/// Requests awaiting a reply, keyed by the connection the request went out
+/// on, the blob and the chunk; `None` asks for the manifest.
+type Pending = HashMap<(SocketAddr, BlobId, Option<u32>), oneshot::Sender<Reply>>;
+
+/// Blobs this node serves, transfers in progress and requests awaiting a reply.
+#[derive(Clone)]
+pub(crate) struct Blobs {
+    published: Arc<Mutex<HashMap<BlobId, Arc<Published>>>>,
+    downloads: Arc<Mutex<HashMap<BlobId, Download>>>,
+    pending: Arc<Mutex<Pending>>,
+    events: broadcast::Sender<BlobEvent>,
+}
+
+impl Default for Blobs {
+    fn default() -> Self {
+        let (events, _) = broadcast::channel(256);
+        Self {
+            published: Arc::default(),
+            downloads: Arc::default(),
+            pending: Arc::default(),
+            events,
+        }
+    }
+}
+
+impl Blobs {
+    fn expect(&self, addr: SocketAddr, blob_id: BlobId, chunk: Option<u32>) -> oneshot::Receiver<Reply> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert((addr, blob_id, chunk), tx);
+        rx
+    }
+
+    /// Hand a reply from the connection at `addr` to whoever asked it.
+    /// Returns false if nobody did.
+    fn resolve(&self, addr: SocketAddr, blob_id: BlobId, chunk: Option<u32>, reply: Reply) -> bool {
+        match self.pending.lock().unwrap().remove(&(addr, blob_id, chunk)) {
+            Some(waiting) => waiting.send(reply).is_ok(),
+            None => false,
+        }
+    }
+
+    fn emit(&self, event: BlobEvent) {
+        // Nobody listening is fine
+        let _ = self.events.send(event);
+    }
+
+    /// Indices still missing, starting a download if there is none yet.
+    fn missing_chunks(&self, blob_id: BlobId, manifest: &BlobManifest) -> Vec<u32> {
+        let mut downloads = self.downloads.lock().unwrap();
+        // A manifest chunked differently cannot reuse what was received so far
+        if downloads.get(&blob_id).is_some_and(|download| download.manifest != *manifest) {
+            downloads.remove(&blob_id);
+        }
+        let download = downloads.entry(blob_id).or_insert_with(|| Download {
+            manifest: manifest.clone(),
+            chunks: vec![None; manifest.chunk_hashes.len()],
+        });
+        (0..download.chunks.len() as u32)
+            .filter(|index| download.chunks[*index as usize].is_none())
+            .collect()
+    }
+
+    fn store_chunk(&self, blob_id: BlobId, index: u32, data: Vec<u8>) {
+        let mut downloads = self.downloads.lock().unwrap();
+        if let Some(download) = downloads.get_mut(&blob_id) {
+            download.chunks[index as usize] = Some(data);
+            self.emit(download.progress(blob_id));
+        }
+    }
+
+    /// Join the chunks and check the result against the blob ID.
+    fn finish(&self, blob_id: BlobId) -> Result<Vec<u8>, TransportError> {
+        let download = self.downloads.lock().unwrap().remove(&blob_id);
+        let Some(download) = download else {
+            return Err(TransportError::BlobNotFound(blob_id_hex(&blob_id)));
+        };
+        let data: Vec<u8> = download.chunks.into_iter().flatten().flatten().collect();
+        if sha256(&data) != blob_id {
+            return Err(TransportError::Integrity(format!(
+                "blob {} does not match its ID",
+                blob_id_hex(&blob_id)
+            )));
+        }
+        Ok(data)
+    }
+}
+
+impl NautilusTransport {
+    /// Make content available to peers. Returns the manifest to advertise.
+    pub fn publish_blob(&self, data: Vec<u8>, name: &str) -> BlobManifest {
+        let manifest = build_manifest(&data, DEFAULT_CHUNK_SIZE, name);
+        let blob_id = sha256(&data);
+        info!(blob.id = %blob_id_hex(&blob_id), bytes = data.len(), chunks = manifest.chunk_hashes.len(), "blob published");
+        self.blobs.published.lock().unwrap().insert(
+            blob_id,
+            Arc::new(Published {
+                manifest: manifest.clone(),
+                data,
+            }),
+        );
+        manifest
+    }
+
+    /// Stop serving a blob.
+    pub fn unpublish_blob(&self, blob_id: &BlobId) {
+        self.blobs.published.lock().unwrap().remove(blob_id);
+    }
+
+    /// Follow advertisements, progress and completion of blob transfers.
+    pub fn subscribe_blobs(&self) -> broadcast::Receiver<BlobEvent> {
+        self.blobs.events.subscribe()
+    }
+
+    /// Send the manifest of a published blob to a peer.
+    pub async fn advertise_blob(&self, peer_id: &str, blob_id: &BlobId) -> Result<(), TransportError> {
+        let published = self.blobs.published.lock().unwrap().get(blob_id).cloned();
+        let Some(published) = published else {
+            return Err(TransportError::BlobNotFound(blob_id_hex(blob_id)));
+        };
+        let addr = self.connect_to_peer(peer_id).await?;
+        let frame = wire::encode(MessageKind::BlobManifest, &published.manifest);
+        self.tcp.send_with(addr, &frame, SendOptions::default()).await?;
+        Ok(())
+    }
+
+    /// Ask a peer for the manifest of a blob.
+    pub async fn fetch_manifest(&self, peer_id: &str, blob_id: &BlobId) -> Result<BlobManifest, TransportError> {
+        let request = BlobRequest {
+            blob_id: blob_id.to_vec(),
+            manifest: true,
+            ..Default::default()
+        };
+        match self.request_blob(peer_id, *blob_id, None, &request).await? {
+            Reply::Manifest(manifest) => {
+                if validate_manifest(&manifest)? != *blob_id {
+                    return Err(TransportError::Integrity("manifest is for another blob".to_string()));
+                }
+                Ok(manifest)
+            }
+            _ => Err(TransportError::BlobNotFound(blob_id_hex(blob_id))),
+        }
+    }
+
+    /// Download a blob from a peer, keeping up to `DEFAULT_PARALLELISM` chunk
+    /// requests in flight. Chunks that already arrived in an earlier call are
+    /// not requested again, so calling this after a failure resumes the transfer.
+    pub async fn fetch_blob(&self, peer_id: &str, manifest: &BlobManifest) -> Result<Vec<u8>, TransportError> {
+        self.fetch_blob_with(peer_id, manifest, DEFAULT_PARALLELISM).await
+    }
+
+    pub async fn fetch_blob_with(
+        &self,
+        peer_id: &str,
+        manifest: &BlobManifest,
+        parallelism: usize,
+    ) -> Result<Vec<u8>, TransportError> {
+        let blob_id = validate_manifest(manifest)?;
+        let mut missing = self.blobs.missing_chunks(blob_id, manifest).into_iter();
+        debug!(blob.id = %blob_id_hex(&blob_id), peer.id = %peer_id, missing = missing.len(), "fetching blob");
+
+        let mut requests = JoinSet::new();
+        loop {
+            while requests.len() < parallelism.max(1) {
+                let Some(index) = missing.next() else { break };
+                let transport = self.clone();
+                let peer_id = peer_id.to_string();
+                let expected = manifest.chunk_hashes[index as usize].clone();
+                requests.spawn(async move {
+                    let data = transport.fetch_chunk(&peer_id, blob_id, index, &expected).await?;
+                    Ok::<_, TransportError>((index, data))
+                });
+            }
+            let Some(joined) = requests.join_next().await else { break };
+            let fetched = joined.map_err(|e| TransportError::IO(e.to_string())).and_then(|fetched| fetched);
+            match fetched {
+                Ok((index, data)) => self.blobs.store_chunk(blob_id, index, data),
+                Err(e) => {
+                    warn!(blob.id = %blob_id_hex(&blob_id), peer.id = %peer_id, error.kind = e.kind(), error = %e, "blob transfer failed");
+                    self.blobs.emit(BlobEvent::Failed { blob_id, error: e.to_string() });
+                    return Err(e);
+                }
+            }
+        }
+
+        let data = self.blobs.finish(blob_id)?;
+        info!(blob.id = %blob_id_hex(&blob_id), bytes = data.len(), "blob received");
+        self.blobs.emit(BlobEvent::Completed { blob_id });
+        Ok(data)
+    }
+
+    /// Fetch and verify one chunk, redialing the peer between attempts.
+    async fn fetch_chunk(&self, peer_id: &str, blob_id: BlobId, index: u32, expected: &[u8]) -> Result<Vec<u8>, TransportError> {
+        let request = BlobRequest {
+            blob_id: blob_id.to_vec(),
+            chunk_index: index,
+            manifest: false,
+        };
+        let mut last_error = TransportError::Unreachable(peer_id.to_string(), "no attempt made".to_string());
+        for attempt in 1..=MAX_ATTEMPTS {
+            match self.request_blob(peer_id, blob_id, Some(index), &request).await {
+                Ok(Reply::Chunk(data)) if sha256(&data).as_slice() == expected => return Ok(data),
+                Ok(Reply::Chunk(_)) => {
+                    warn!(blob.id = %blob_id_hex(&blob_id), chunk = index, attempt, "chunk failed its hash check");
+                    last_error = TransportError::Integrity(format!("chunk {} of {}", index, blob_id_hex(&blob_id)));
+                }
+                Ok(_) => {
+                    // The peer may be republishing it; give it another chance
+                    debug!(blob.id = %blob_id_hex(&blob_id), chunk = index, attempt, "peer does not have the chunk");
+                    last_error = TransportError::BlobNotFound(blob_id_hex(&blob_id));
+                }
+                Err(e) => {
+                    debug!(blob.id = %blob_id_hex(&blob_id), chunk = index, attempt, error.kind = e.kind(), error = %e, "chunk request failed");
+                    last_error = e;
+                }
+            }
+            sleep(RETRY_DELAY * attempt).await;
+        }
+        Err(last_error)
+    }
+
+    /// Send a blob request over the peer's connection and wait for the reply.
+    async fn request_blob(
+        &self,
+        peer_id: &str,
+        blob_id: BlobId,
+        chunk: Option<u32>,
+        request: &BlobRequest,
+    ) -> Result<Reply, TransportError> {
+        let addr = self.connect_to_peer(peer_id).await?;
+        // Replies arrive keyed by the connection, which may be the peer's ephemeral port
+        let connection = self.tcp.connection_addr(addr).await.unwrap_or(addr);
+        let reply = self.blobs.expect(connection, blob_id, chunk);
+        let frame = wire::encode(MessageKind::BlobRequest, request);
+        if let Err(e) = self.tcp.send_with(addr, &frame, SendOptions::default()).await {
+            // Drop the broken connection so the next attempt dials again
+            self.tcp.disconnect(addr).await;
+            return Err(e.into());
+        }
+        match timeout(REQUEST_TIMEOUT, reply).await {
+            Ok(Ok(reply)) => Ok(reply),
+            Ok(Err(_)) => Err(TransportError::Unreachable(peer_id.to_string(), "request superseded".to_string())),
+            Err(_) => {
+                self.blobs.pending.lock().unwrap().remove(&(connection, blob_id, chunk));
+                Err(TransportError::Unreachable(peer_id.to_string(), "blob request timed out".to_string()))
+            }
+        }
+    }
+
+    /// Serve a manifest or chunk of a published blob.
+    pub(super) fn handle_blob_request(&self, addr: SocketAddr, envelope: &TransportEnvelope) -> Result<(), TransportError> {
+        let request = wire::decode_body::<BlobRequest>(envelope)?.message;
+        let published = BlobId::try_from(request.blob_id.as_slice())
+            .ok()
+            .and_then(|blob_id| self.blobs.published.lock().unwrap().get(&blob_id).cloned());
+
+        let (frame, priority) = match (published, request.manifest) {
+            (Some(published), true) => (wire::encode(MessageKind::BlobManifest, &published.manifest), Priority::Normal),
+            (None, true) => {
+                let missing = BlobManifest { blob_id: request.blob_id, missing: true, ..Default::default() };
+                (wire::encode(MessageKind::BlobManifest, &missing), Priority::Normal)
+            }
+            (published, false) => {
+                let data = published.as_ref().and_then(|published| published.chunk(request.chunk_index as usize));
+                let chunk = BlobChunk {
+                    blob_id: request.blob_id,
+                    chunk_index: request.chunk_index,
+                    data: data.map(<[u8]>::to_vec).unwrap_or_default(),
+                    missing: data.is_none(),
+                };
+                (wire::encode(MessageKind::BlobChunk, &chunk), Priority::Bulk)
+            }
+        };
+
+        // Writing a chunk can take a while; keep the listener loop moving
+        let tcp = self.tcp.clone();
+        self.lifecycle.spawn(async move {
+            if let Err(e) = tcp.send_with(addr, &frame, SendOptions::new(priority)).await {
+                debug!(peer.addr = %addr, error.kind = ?e.kind(), error = %e, "failed to answer blob request");
+            }
+        });
+        Ok(())
+    }
+
+    pub(super) fn handle_blob_manifest(&self, addr: SocketAddr, envelope: &TransportEnvelope) -> Result<(), TransportError> {
+        let manifest = wire::decode_body::<BlobManifest>(envelope)?.message;
+        let Ok(blob_id) = BlobId::try_from(manifest.blob_id.as_slice()) else {
+            return Err(TransportError::InvalidEnvelope("blob manifest has a malformed blob ID".to_string()));
+        };
+        let reply = match manifest.missing {
+            true => Reply::Missing,
+            false => Reply::Manifest(manifest.clone()),
+        };
+        // Not something we asked for, so the peer is advertising it
+        if !self.blobs.resolve(addr, blob_id, None, reply) && !manifest.missing {
+            validate_manifest(&manifest)?;
+            debug!(peer.addr = %addr, blob.id = %blob_id_hex(&blob_id), "blob advertised");
+            self.blobs.emit(BlobEvent::Advertised { from: addr, manifest });
+        }
+        Ok(())
+    }
+
+    pub(super) fn handle_blob_chunk(&self, addr: SocketAddr, envelope: &TransportEnvelope) -> Result<(), TransportError> {
+        let chunk = wire::decode_body::<BlobChunk>(envelope)?.message;
+        let Ok(blob_id) = BlobId::try_from(chunk.blob_id.as_slice()) else {
+            return Err(TransportError::InvalidEnvelope("blob chunk has a malformed blob ID".to_string()));
+        };
+        let reply = match chunk.missing {
+            true => Reply::Missing,
+            false => Reply::Chunk(chunk.data),
+        };
+        if !self.blobs.resolve(addr, blob_id, Some(chunk.chunk_index), reply) {
+            debug!(peer.addr = %addr, blob.id = %blob_id_hex(&blob_id), chunk = chunk.chunk_index, "unrequested blob chunk dropped");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+// bootstrap.rs
+//? Joining a network: dial a configured list of known nodes, check they are who
+//? they should be, and keep trying until enough connections are open.
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use tokio::task::JoinSet;
+use tokio::time::{sleep, timeout};
+use tracing::{debug, info, warn};
+
+use super::{NautilusTransport, TransportError};
+use crate::proto::Handshake;
+use crate::record::{AddrSource, AddrTransport};
+
+/// Tag put on peers reached through the bootstrap list.
+pub const BOOTSTRAP_TAG: &str = "bootstrap";
+
+/// A node to join through, written `addr` or `peer_id@addr`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BootstrapPeer {
+    pub addr: SocketAddr,
+    pub peer_id: Option<String>, // When set, a node answering with another identity is refused
+}
+
+impl FromStr for BootstrapPeer {
+    type Err = io::Error;
+
+    fn from_str(entry: &str) -> Result<Self, Self::Err> {
+        let (peer_id, addr) = match entry.rsplit_once('@') {
+            Some((peer_id, addr)) => (Some(peer_id.to_string()), addr),
+            None => (None, entry),
+        };
+        let addr = addr.parse().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("bad bootstrap address {:?}", entry))
+        })?;
+        Ok(Self { addr, peer_id })
+    }
+}
+
+impl fmt::Display for BootstrapPeer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.peer_id {
+            Some(peer_id) => write!(f, "{}@{}", peer_id, self.addr),
+            None => write!(f, "{}", self.addr),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BootstrapConfig {
+    pub peers: Vec<BootstrapPeer>,
+    pub min_connections: usize,    // Open connections needed before bootstrap returns
+    pub dial_timeout: Duration,    // Per dial, handshake included
+    pub initial_backoff: Duration, // Wait after the first round that fell short, doubled each round
+    pub max_backoff: Duration,
+    pub max_rounds: Option<usize>, // `None` keeps trying until shutdown
+}
+
+impl Default for BootstrapConfig {
+    fn default() -> Self {
+        Self {
+            peers: Vec::new(),
+            min_connections: 3,
+            dial_timeout: Duration::from_secs(10),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_rounds: None,
+        }
+    }
+}
+
+impl BootstrapConfig {
+    pub fn new(peers: Vec<BootstrapPeer>) -> Self {
+        Self {
+            peers,
+            ..Default::default()
+        }
+    }
+
+    /// Read a bootstrap list separated by whitespace, commas or newlines.
+    /// Anything after a `#` on a line is a comment.
+    pub fn parse(list: &str) -> io::Result<Self> {
+        let peers = list
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default())
+            .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+            .filter(|entry| !entry.is_empty())
+            .map(BootstrapPeer::from_str)
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Self::new(peers))
+    }
+}
+
+impl NautilusTransport {
+    /// Join the network: dial every bootstrap peer not yet connected in
+    /// parallel, then, if that is not enough, the best peers already in the
+    /// table. Rounds repeat with exponential backoff until `min_connections`
+    /// are open. Returns how many connections are open.
+    pub async fn bootstrap(&self, config: &BootstrapConfig) -> Result<usize, TransportError> {
+        let mut delay = config.initial_backoff;
+        let mut stopping = self.lifecycle.shutdown_signal();
+        let mut round = 0;
+        loop {
+            if self.lifecycle.is_shutting_down() {
+                return Err(TransportError::ShuttingDown);
+            }
+            let connected = self.tcp.connected_peers().await.len();
+            if connected >= config.min_connections {
+                info!(connections = connected, rounds = round, "bootstrap complete");
+                return Ok(connected);
+            }
+            round += 1;
+
+            let targets = self.bootstrap_targets(config, config.min_connections - connected).await;
+            if targets.is_empty() && config.peers.is_empty() {
+                return Err(TransportError::UnknownPeer("no bootstrap peers configured".to_string()));
+            }
+            debug!(round, targets = targets.len(), connections = connected, "bootstrap round");
+            let mut dials = JoinSet::new();
+            for target in targets {
+                let transport = self.clone();
+                let dial_timeout = config.dial_timeout;
+                dials.spawn(async move {
+                    let result = transport.dial_bootstrap(&target, dial_timeout).await;
+                    (target, result)
+                });
+            }
+            let mut last_error = String::from("nothing left to dial");
+            while let Some(joined) = dials.join_next().await {
+                let Ok((target, result)) = joined else { continue };
+                if let Err(e) = result {
+                    debug!(peer = %target, error.kind = e.kind(), error = %e, "bootstrap dial failed");
+                    last_error = format!("{}: {}", target, e);
+                }
+            }
+
+            let connected = self.tcp.connected_peers().await.len();
+            if connected >= config.min_connections {
+                continue;
+            }
+            if config.max_rounds.is_some_and(|max| round >= max) {
+                let reason = format!(
+                    "{} of {} connections after {} rounds, last error {}",
+                    connected, config.min_connections, round, last_error
+                );
+                warn!(connections = connected, rounds = round, error = %last_error, "bootstrap gave up");
+                return Err(TransportError::Unreachable("bootstrap".to_string(), reason));
+            }
+            info!(connections = connected, wanted = config.min_connections, retry_in = ?delay, "bootstrap short of connections");
+            tokio::select! {
+                _ = sleep(delay) => {}
+                _ = async { let _ = stopping.wait_for(|stopping| *stopping).await; } => {
+                    return Err(TransportError::ShuttingDown);
+                }
+            }
+            delay = delay.saturating_mul(2).min(config.max_backoff);
+        }
+    }
+
+    /// Bootstrap peers not connected yet, then up to `wanted` dial candidates
+    /// from the peer table.
+    async fn bootstrap_targets(&self, config: &BootstrapConfig, wanted: usize) -> Vec<BootstrapPeer> {
+        let mut targets = Vec::new();
+        for peer in &config.peers {
+            if self.tcp.is_connected(peer.addr).await {
+                continue;
+            }
+            if let Some(peer_id) = &peer.peer_id {
+                if self.is_refused(peer_id).await {
+                    continue;
+                }
+            }
+            targets.push(peer.clone());
+        }
+
+        let now = SystemTime::now();
+        for candidate in self.peer_manager.dial_candidates().await {
+            if targets.len() >= config.peers.len() + wanted {
+                break;
+            }
+            let Some(addr) = candidate.ranked_addrs(AddrTransport::Tcp, now).first().copied() else {
+                continue;
+            };
+            if targets.iter().any(|target| target.addr == addr) || self.tcp.is_connected(addr).await {
+                continue;
+            }
+            targets.push(BootstrapPeer {
+                addr,
+                peer_id: candidate.peer_id,
+            });
+        }
+        targets
+    }
+
+    /// Dial one target and refuse it unless it is who it should be.
+    async fn dial_bootstrap(&self, target: &BootstrapPeer, dial_timeout: Duration) -> Result<(), TransportError> {
+        let unreachable = |reason: String| TransportError::Unreachable(target.to_string(), reason);
+        let handshake = timeout(dial_timeout, self.tcp.connect(target.addr))
+            .await
+            .map_err(|_| unreachable(format!("no handshake within {:?}", dial_timeout)))?
+            .map_err(|e| unreachable(e.to_string()))?;
+        if let Err(e) = check_identity(target, &handshake) {
+            warn!(peer = %target, peer.id = %handshake.peer_id, "bootstrap peer presented the wrong identity");
+            self.tcp.disconnect(target.addr).await;
+            return Err(e);
+        }
+
+        self.record_handshake(target.addr, &handshake, AddrSource::Dial).await;
+        let key = match handshake.peer_id.as_str() {
+            "" => target.addr.to_string(),
+            peer_id => peer_id.to_string(),
+        };
+        self.peer_manager.tag_peer(&key, BOOTSTRAP_TAG).await;
+        Ok(())
+    }
+}
+
+/// A target with an expected ID must answer with that ID and, when keys can
+/// be checked, with the public key the ID was derived from.
+fn check_identity(target: &BootstrapPeer, handshake: &Handshake) -> Result<(), TransportError> {
+    let Some(expected) = &target.peer_id else {
+        return Ok(());
+    };
+    if handshake.peer_id != *expected {
+        let found = match handshake.peer_id.as_str() {
+            "" => "no peer ID".to_string(),
+            peer_id => peer_id.to_string(),
+        };
+        return Err(TransportError::IdentityMismatch(expected.clone(), found));
+    }
+    #[cfg(feature = "identity_integration")]
+    if !crate::record::peer_id_matches_key(&handshake.peer_id, &handshake.public_key) {
+        return Err(TransportError::IdentityMismatch(
+            expected.clone(),
+            "a public key that ID is not derived from".to_string(),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,179 @@
+// envelope.rs
+//? Signed message envelopes
+//? Every payload is wrapped with the sender's peer ID, a sequence number and a
+//? timestamp, then signed with the sender's KeyPair so receivers can reject
+//? spoofed and replayed messages.
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use identity::{Algorithm, Identity, KeyPair};
+use prost::Message;
+
+use crate::proto::SignedEnvelope;
+use crate::transport::wire::now_ms;
+use crate::transport::TransportError;
+
+/// How far a timestamp may drift from the local clock, and how long sequence
+/// numbers are remembered for replay detection.
+pub const DEFAULT_REPLAY_WINDOW: Duration = Duration::from_secs(30);
+
+/// Signed envelope as defined by `SignedEnvelope` in the wire schema.
+pub type Envelope = SignedEnvelope;
+
+impl Envelope {
+    /// Bytes covered by the signature: every field except the signature itself.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.sender_id.len() + self.payload.len() + 20);
+        bytes.extend_from_slice(&(self.sender_id.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(self.sender_id.as_bytes());
+        bytes.extend_from_slice(&self.sequence.to_be_bytes());
+        bytes.extend_from_slice(&self.timestamp_ms.to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encode_to_vec()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TransportError> {
+        Envelope::decode(bytes).map_err(|e| TransportError::InvalidEnvelope(e.to_string()))
+    }
+}
+
+/// Seals outgoing payloads with the local identity.
+pub struct EnvelopeSigner {
+    peer_id: String,
+    key_pair: KeyPair,
+    sequence: AtomicU64,
+}
+
+impl EnvelopeSigner {
+    pub fn new(identity: &Identity) -> Self {
+        // Seed the counter from the clock so a restarted node never reuses
+        // sequence numbers a receiver may still remember.
+        Self {
+            peer_id: identity.get_peer_id().to_string(),
+            key_pair: identity.get_key_pair().clone(),
+            sequence: AtomicU64::new(now_ms().saturating_mul(1000)),
+        }
+    }
+
+    pub fn peer_id(&self) -> &str {
+        &self.peer_id
+    }
+
+    pub fn public_key(&self) -> &str {
+        &self.key_pair.public_key
+    }
+
+    pub(crate) fn key_pair(&self) -> &KeyPair {
+        &self.key_pair
+    }
+
+    /// Wrap and sign a payload.
+    pub fn seal(&self, payload: &[u8]) -> Result<Envelope, TransportError> {
+        let mut envelope = Envelope {
+            sender_id: self.peer_id.clone(),
+            sequence: self.sequence.fetch_add(1, Ordering::Relaxed),
+            timestamp_ms: now_ms(),
+            payload: payload.to_vec(),
+            signature: Vec::new(),
+        };
+        envelope.signature = self
+            .key_pair
+            .sign(&envelope.signing_bytes())
+            .map_err(TransportError::BadSignature)?;
+        Ok(envelope)
+    }
+}
+
+/// Sequences delivered within the replay window.
+#[derive(Default)]
+struct Seen {
+    senders: HashMap<String, HashMap<u64, u64>>, // Sender -> sequence -> timestamp
+    swept_ms: u64,                               // When senders gone quiet were last dropped
+}
+
+impl Seen {
+    /// Forget senders with nothing left inside the window, at most once per window.
+    fn sweep(&mut self, now: u64, window_ms: u64) {
+        if now.saturating_sub(self.swept_ms) < window_ms {
+            return;
+        }
+        self.swept_ms = now;
+        self.senders.retain(|_, sequences| {
+            sequences.retain(|_, timestamp| now.saturating_sub(*timestamp) <= window_ms);
+            !sequences.is_empty()
+        });
+    }
+}
+
+/// Checks incoming envelopes for freshness, authenticity and replays.
+pub struct EnvelopeVerifier {
+    window: Duration,
+    seen: Mutex<Seen>,
+}
+
+impl EnvelopeVerifier {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: Mutex::new(Seen::default()),
+        }
+    }
+
+    /// How many senders have sequences remembered.
+    pub fn tracked_senders(&self) -> usize {
+        self.seen.lock().unwrap().senders.len()
+    }
+
+    /// Verify an envelope against the sender's cached public key.
+    /// A verified envelope is remembered so the same one is refused if it comes back.
+    pub fn verify(&self, envelope: &Envelope, public_key: &str) -> Result<(), TransportError> {
+        let now = now_ms();
+        let window_ms = self.window.as_millis() as u64;
+        if envelope.timestamp_ms.abs_diff(now) > window_ms {
+            return Err(TransportError::Replay(format!(
+                "timestamp from {} is outside the {:?} window",
+                envelope.sender_id, self.window
+            )));
+        }
+
+        // Only RSA keys are generated by identity today
+        let valid = KeyPair::verify_with_public_key(
+            Algorithm::RSA,
+            public_key,
+            &envelope.signing_bytes(),
+            &envelope.signature,
+        )
+        .map_err(TransportError::BadSignature)?;
+        if !valid {
+            return Err(TransportError::BadSignature(format!(
+                "envelope from {} does not verify",
+                envelope.sender_id
+            )));
+        }
+
+        let mut seen = self.seen.lock().unwrap();
+        seen.sweep(now, window_ms);
+        let sequences = seen.senders.entry(envelope.sender_id.clone()).or_default();
+        sequences.retain(|_, timestamp| now.saturating_sub(*timestamp) <= window_ms);
+        if sequences.contains_key(&envelope.sequence) {
+            return Err(TransportError::Replay(format!(
+                "sequence {} from {} was already delivered",
+                envelope.sequence, envelope.sender_id
+            )));
+        }
+        sequences.insert(envelope.sequence, envelope.timestamp_ms);
+        Ok(())
+    }
+}
+
+impl Default for EnvelopeVerifier {
+    fn default() -> Self {
+        Self::new(DEFAULT_REPLAY_WINDOW)
+    }
+}
@@ -0,0 +1,83 @@
+// handshake.rs
+//? Proving the public key announced in a handshake: each side signs the
+//? nonce the other side just sent, so a handshake cannot be forged or replayed
+#[cfg(feature = "identity_integration")]
+use std::io;
+
+use rand::RngCore;
+
+use crate::proto::Handshake;
+#[cfg(feature = "identity_integration")]
+use super::envelope::EnvelopeSigner;
+#[cfg(feature = "identity_integration")]
+use crate::record::peer_id_matches_key;
+#[cfg(feature = "identity_integration")]
+use identity::{Algorithm, KeyPair};
+
+/// Length of the nonce each side sends.
+const NONCE_LEN: usize = 32;
+
+/// Which half of the handshake a signature was made for, so the listener's
+/// signature can never be passed off as a dialer's, or the other way round.
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Role {
+    Ack = 1,   // The listener, signing the dialer's nonce
+    Proof = 2, // The dialer, signing the listener's nonce
+}
+
+pub(super) fn nonce() -> Vec<u8> {
+    let mut nonce = vec![0; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    nonce
+}
+
+/// Bytes covered by a handshake signature: the signer's ID and key, the ID
+/// of the peer it is meant for and the nonce that peer sent.
+#[cfg_attr(not(feature = "identity_integration"), allow(dead_code))]
+fn signing_bytes(role: Role, signer: &Handshake, audience: &str, nonce: &[u8]) -> Vec<u8> {
+    let mut bytes = b"nautilus-handshake".to_vec();
+    bytes.push(role as u8);
+    for field in [signer.peer_id.as_bytes(), signer.public_key.as_bytes(), audience.as_bytes(), nonce] {
+        bytes.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(field);
+    }
+    bytes
+}
+
+/// Sign the `nonce` sent by the peer `audience`, as the node announcing `local`.
+#[cfg(feature = "identity_integration")]
+pub(super) fn sign(
+    signer: &EnvelopeSigner,
+    role: Role,
+    local: &Handshake,
+    audience: &str,
+    nonce: &[u8],
+) -> io::Result<Vec<u8>> {
+    signer
+        .key_pair()
+        .sign(&signing_bytes(role, local, audience, nonce))
+        .map_err(io::Error::other)
+}
+
+/// Check that `remote` holds the key it announced: its ID must be derived
+/// from the key, and `signature` must cover the `nonce` this node sent to it
+/// as `local_id`.
+#[cfg(feature = "identity_integration")]
+pub(super) fn verify(role: Role, remote: &Handshake, signature: &[u8], local_id: &str, nonce: &[u8]) -> io::Result<()> {
+    let denied = |reason: String| io::Error::new(io::ErrorKind::PermissionDenied, reason);
+    if !peer_id_matches_key(&remote.peer_id, &remote.public_key) {
+        return Err(denied(format!("peer ID {} is not derived from its public key", remote.peer_id)));
+    }
+    // Only RSA keys are generated by identity today
+    let valid = KeyPair::verify_with_public_key(
+        Algorithm::RSA,
+        &remote.public_key,
+        &signing_bytes(role, remote, local_id, nonce),
+        signature,
+    )
+    .map_err(denied)?;
+    match valid {
+        true => Ok(()),
+        false => Err(denied(format!("handshake from {} does not verify", remote.peer_id))),
+    }
+}
@@ -0,0 +1,146 @@
+// lifecycle.rs
+//? Owns every task the transport spawns and coordinates an orderly shutdown
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{watch, Notify};
+use tokio::task::{JoinError, JoinSet};
+use tokio::time::{timeout_at, Instant};
+use tracing::{debug, warn};
+
+use super::TransportError;
+
+#[derive(Clone)]
+pub struct Lifecycle {
+    shutdown_tx: Arc<watch::Sender<bool>>,
+    tasks: Arc<Mutex<JoinSet<()>>>,
+    accepting_sends: Arc<AtomicBool>,
+    in_flight: Arc<AtomicUsize>,
+    drained: Arc<Notify>,
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        let (shutdown_tx, _) = watch::channel(false);
+        Self {
+            shutdown_tx: Arc::new(shutdown_tx),
+            tasks: Arc::new(Mutex::new(JoinSet::new())),
+            accepting_sends: Arc::new(AtomicBool::new(true)),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            drained: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Spawn a task that shutdown will wait for. Tasks that already finished
+    /// are dropped from the set first, so it only grows with running tasks.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let mut tasks = self.tasks.lock().unwrap();
+        while let Some(result) = tasks.try_join_next() {
+            log_panic(result);
+        }
+        tasks.spawn(task);
+    }
+
+    /// Tasks spawned and not yet reaped. Finished tasks are reaped on the next spawn.
+    pub fn task_count(&self) -> usize {
+        self.tasks.lock().unwrap().len()
+    }
+
+    /// Receiver that flips to `true` once tasks should stop.
+    pub fn shutdown_signal(&self) -> watch::Receiver<bool> {
+        self.shutdown_tx.subscribe()
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        !self.accepting_sends.load(Ordering::SeqCst)
+    }
+
+    /// Register an outgoing send. Fails once shutdown has started; the send
+    /// counts as in flight until the guard is dropped.
+    pub fn begin_send(&self) -> Result<SendGuard, TransportError> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = SendGuard {
+            lifecycle: self.clone(),
+        };
+        if self.is_shutting_down() {
+            return Err(TransportError::ShuttingDown);
+        }
+        Ok(guard)
+    }
+
+    /// Refuse new sends from now on.
+    pub fn stop_sends(&self) {
+        self.accepting_sends.store(false, Ordering::SeqCst);
+    }
+
+    /// Wait for in-flight sends to finish. Returns false if the deadline passed first.
+    pub async fn drain(&self, deadline: Instant) -> bool {
+        loop {
+            let drained = self.drained.notified();
+            let in_flight = self.in_flight.load(Ordering::SeqCst);
+            if in_flight == 0 {
+                return true;
+            }
+            debug!(in_flight, "waiting for in-flight sends");
+            if timeout_at(deadline, drained).await.is_err() {
+                warn!(in_flight = self.in_flight.load(Ordering::SeqCst), "send drain deadline passed");
+                return false;
+            }
+        }
+    }
+
+    /// Tell every task to stop.
+    pub fn signal_shutdown(&self) {
+        self.stop_sends();
+        self.shutdown_tx.send_replace(true);
+    }
+
+    /// Wait for every spawned task, including ones spawned while waiting.
+    /// Tasks still running at the deadline are aborted.
+    pub async fn join(&self, deadline: Instant) {
+        loop {
+            let mut tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+            if tasks.is_empty() {
+                return;
+            }
+            while let Ok(Some(result)) = timeout_at(deadline, tasks.join_next()).await {
+                log_panic(result);
+            }
+            if !tasks.is_empty() {
+                warn!(remaining = tasks.len(), "shutdown deadline passed, aborting tasks");
+                tasks.shutdown().await;
+            }
+        }
+    }
+}
+
+fn log_panic(result: Result<(), JoinError>) {
+    if let Err(e) = result {
+        if e.is_panic() {
+            warn!(error = %e, "task panicked");
+        }
+    }
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Marks a send as in flight for as long as it is held.
+pub struct SendGuard {
+    lifecycle: Lifecycle,
+}
+
+impl Drop for SendGuard {
+    fn drop(&mut self) {
+        if self.lifecycle.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.lifecycle.drained.notify_waiters();
+        }
+    }
+}
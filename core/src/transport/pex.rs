@@ -0,0 +1,275 @@
+// pex.rs
+//? Peer exchange: connected nodes periodically share a random sample of the
+//? healthy peers they recently verified, so a new node learns a useful part of
+//? the network from its first few connections.
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use prost::Message;
+use rand::seq::SliceRandom;
+use tokio::sync::watch;
+use tokio::time::{sleep, Instant};
+use tracing::{debug, info, trace};
+
+use super::scheduler::SendOptions;
+use super::wire::{self, Preserved};
+use super::{NautilusTransport, TransportError};
+use crate::proto::{MessageKind, PeerExchange, PeerInfo, TransportEnvelope};
+use crate::record::{
+    AddrSource, AddrTransport, PeerAddr, PeerEvent, PeerQuery, PeerRecord,
+};
+#[cfg(feature = "identity_integration")]
+use crate::record::{RecordError, SignedPeerRecord};
+
+/// How often peers are shared, what is shared and how much is taken in.
+#[derive(Clone, Debug)]
+pub struct PexConfig {
+    pub interval: Duration,        // Time between exchange rounds
+    pub fanout: usize,             // Connected peers picked each round
+    pub sample_size: usize,        // Peers sent in one exchange
+    pub verified_within: Duration, // Only peers this node connected to this recently are shared
+    pub min_score: f64,            // Only peers scoring at least this are shared
+    pub min_gap: Duration,         // Exchanges from one peer closer together than this are dropped
+    pub max_entries: usize,        // Entries past this in one exchange are ignored
+    pub max_learned: usize,        // New peers taken from one exchange
+}
+
+impl Default for PexConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+            fanout: 3,
+            sample_size: 32,
+            verified_within: Duration::from_secs(60 * 60),
+            min_score: 0.0,
+            min_gap: Duration::from_secs(10),
+            max_entries: 64,
+            max_learned: 16,
+        }
+    }
+}
+
+/// Exchange settings and when each neighbour last sent one.
+#[derive(Clone, Default)]
+pub(crate) struct Pex {
+    config: Arc<RwLock<PexConfig>>,
+    last_received: Arc<Mutex<HashMap<SocketAddr, Instant>>>,
+}
+
+impl Pex {
+    fn config(&self) -> PexConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Whether an exchange from `addr` arrives soon enough after the last to drop.
+    fn too_soon(&self, addr: SocketAddr, min_gap: Duration) -> bool {
+        let now = Instant::now();
+        let mut last_received = self.last_received.lock().unwrap();
+        last_received.retain(|_, at| now.duration_since(*at) < min_gap);
+        if last_received.contains_key(&addr) {
+            return true;
+        }
+        last_received.insert(addr, now);
+        false
+    }
+}
+
+impl NautilusTransport {
+    /// Change how peers are exchanged; takes effect from the next round.
+    pub fn set_pex_config(&self, config: PexConfig) {
+        *self.pex.config.write().unwrap() = config;
+    }
+
+    pub fn pex_config(&self) -> PexConfig {
+        self.pex.config()
+    }
+
+    /// Share a random sample of healthy, recently verified peers with a
+    /// connected peer, led by this node's own signed record if it published one.
+    pub async fn exchange_peers(&self, peer_addr: SocketAddr) -> io::Result<()> {
+        let config = self.pex.config();
+        let now = SystemTime::now();
+        let recipient = self.peer_key(peer_addr).await;
+        let mut exchange = PeerExchange::default();
+        #[cfg(feature = "identity_integration")]
+        if let Some(local) = self.local_record.lock().unwrap().clone().filter(|local| !local.is_expired(now)) {
+            let info = PeerInfo {
+                peer_id: local.peer_id.clone(),
+                addrs: local.addrs.iter().map(SocketAddr::to_string).collect(),
+                public_key: local.public_key.clone(),
+                last_seen_ms: wire::now_ms(),
+                signed_record: local.to_bytes(),
+            };
+            exchange.peers.push(info.encode_to_vec());
+        }
+
+        let query = PeerQuery::new()
+            .connected_within(config.verified_within)
+            .score_between(Some(config.min_score), None);
+        let mut healthy: Vec<PeerRecord> = self
+            .peer_manager
+            .query(&query)
+            .await
+            .peers
+            .into_iter()
+            .filter(|peer| peer.peer_id.as_ref().is_some_and(|peer_id| *peer_id != recipient))
+            .collect();
+        healthy.shuffle(&mut rand::thread_rng());
+
+        for peer in healthy {
+            if exchange.peers.len() >= config.sample_size {
+                break;
+            }
+            let addrs = peer.ranked_addrs(AddrTransport::Tcp, now);
+            if addrs.is_empty() {
+                continue;
+            }
+            let signed_record = peer
+                .signed_record
+                .as_ref()
+                .filter(|signed| !signed.is_expired(now))
+                .map(|signed| signed.to_bytes())
+                .unwrap_or_default();
+            let info = PeerInfo {
+                peer_id: peer.peer_id.unwrap_or_default(),
+                addrs: addrs.iter().map(SocketAddr::to_string).collect(),
+                public_key: peer.public_key.unwrap_or_default(),
+                last_seen_ms: peer
+                    .history
+                    .last_connected
+                    .and_then(|seen| seen.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |since| since.as_millis() as u64),
+                signed_record,
+            };
+            exchange.peers.push(info.encode_to_vec());
+        }
+
+        if exchange.peers.is_empty() {
+            return Ok(());
+        }
+        trace!(peer.addr = %peer_addr, entries = exchange.peers.len(), "sending peer exchange");
+        let frame = wire::encode(MessageKind::PeerExchange, &exchange);
+        self.tcp.send_with(peer_addr, &frame, SendOptions::control()).await?;
+        Ok(())
+    }
+
+    /// Exchange peers with `fanout` random connected peers every `interval`
+    /// until `shutdown` turns true.
+    pub(super) async fn run_pex(self, mut shutdown: watch::Receiver<bool>) {
+        loop {
+            // New connections exchange on their own, so the first round waits too
+            let PexConfig { interval, fanout, .. } = self.pex.config();
+            tokio::select! {
+                _ = sleep(interval) => {
+                    let neighbours: Vec<SocketAddr> = {
+                        let connected = self.tcp.connected_peers().await;
+                        connected.choose_multiple(&mut rand::thread_rng(), fanout).copied().collect()
+                    };
+                    for addr in neighbours {
+                        if let Err(e) = self.exchange_peers(addr).await {
+                            debug!(peer.addr = %addr, error.kind = ?e.kind(), error = %e, "peer exchange failed");
+                        }
+                    }
+                }
+                _ = async { let _ = shutdown.wait_for(|stop| *stop).await; } => break,
+            }
+        }
+        info!("peer exchange stopped");
+    }
+
+    /// Merge peers shared by a neighbour, attributed to it. Exchanges arriving
+    /// faster than `min_gap` are dropped, and each one adds at most
+    /// `max_learned` new peers. Entries carrying a signed record are only
+    /// taken if it verifies, and a node with an identity takes no unsigned
+    /// entries at all.
+    pub(super) async fn handle_peer_exchange(&self, addr: SocketAddr, envelope: &TransportEnvelope) -> Result<(), TransportError> {
+        let exchange = wire::decode_body::<PeerExchange>(envelope)?.message;
+        let config = self.pex.config();
+        let relay = self.peer_key(addr).await;
+        if self.pex.too_soon(addr, config.min_gap) {
+            debug!(peer.addr = %addr, "dropped peer exchange sent too soon after the last");
+            self.peer_manager.report(&relay, PeerEvent::Spam).await;
+            return Ok(());
+        }
+
+        let mut learned = 0;
+        for entry in exchange.peers.iter().take(config.max_entries) {
+            let info = Preserved::<PeerInfo>::decode(entry)
+                .map_err(|e| TransportError::InvalidEnvelope(e.to_string()))?
+                .message;
+            if info.peer_id.is_empty() || info.peer_id == self.local_peer_id() {
+                continue;
+            }
+            let known = self.peer_manager.get_peer(&info.peer_id).await.is_some();
+            if !known && learned >= config.max_learned {
+                continue;
+            }
+            #[cfg(feature = "identity_integration")]
+            {
+                if !info.signed_record.is_empty() {
+                    let accepted = self.accept_relayed_record(addr, &relay, &info.signed_record).await;
+                    learned += (accepted && !known) as usize;
+                    continue;
+                }
+                if self.signer.is_some() {
+                    continue;
+                }
+            }
+
+            let addrs: Vec<PeerAddr> = info
+                .addrs
+                .iter()
+                .filter_map(|a| a.parse::<SocketAddr>().ok())
+                .map(|a| PeerAddr::tcp(a, AddrSource::PeerExchange).with_learned_from(&relay))
+                .collect();
+            let Some(peer_addr) = addrs.first().map(|known| known.addr) else {
+                continue;
+            };
+            // Known peers only pick up addresses they did not have
+            if known {
+                for known in addrs {
+                    self.peer_manager.add_addr(&info.peer_id, known).await;
+                }
+                continue;
+            }
+            self.peer_manager
+                .add_or_update_peer(PeerRecord {
+                    addrs,
+                    peer_id: Some(info.peer_id),
+                    public_key: Some(info.public_key).filter(|key| !key.is_empty()),
+                    // No last_seen: the relay's claim is not something this node saw
+                    ..PeerRecord::new(peer_addr)
+                })
+                .await;
+            learned += 1;
+        }
+        debug!(peer.addr = %addr, entries = exchange.peers.len(), learned, "peer exchange received");
+        Ok(())
+    }
+
+    /// Verify and store a signed record relayed by `addr`. Forwarding a record
+    /// that does not verify counts against the relaying peer.
+    #[cfg(feature = "identity_integration")]
+    async fn accept_relayed_record(&self, addr: SocketAddr, relay: &str, bytes: &[u8]) -> bool {
+        let result = match SignedPeerRecord::from_bytes(bytes) {
+            Ok(record) => {
+                self.peer_manager
+                    .accept_signed_record(record, AddrSource::PeerExchange, Some(relay))
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => true,
+            Err(RecordError::Stale { .. }) => false,
+            Err(e) => {
+                debug!(peer.addr = %addr, error.kind = e.kind(), error = %e, "rejected relayed peer record");
+                self.peer_manager.report(relay, PeerEvent::InvalidMessage).await;
+                false
+            }
+        }
+    }
+}
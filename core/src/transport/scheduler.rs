@@ -0,0 +1,234 @@
+// scheduler.rs
+//? Per-connection outbound queues with priority classes and deadlines
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::sync::{oneshot, Notify};
+use tokio::time::{Duration, Instant};
+use tracing::{debug, trace};
+
+use super::throttle::{Flow, Throttle};
+use super::wire;
+
+/// Bytes a class may write per round for each unit of weight.
+const QUANTUM: usize = 16 * 1024;
+
+/// How urgently a message should be written. Each class gets a share of the
+/// connection in proportion to its weight, so bulk data still moves while
+/// control traffic is waiting, just more slowly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Priority {
+    Control, // Handshakes, pings and membership updates
+    #[default]
+    Normal,
+    Bulk,    // Large transfers that can wait
+}
+
+impl Priority {
+    const ALL: [Priority; 3] = [Priority::Control, Priority::Normal, Priority::Bulk];
+
+    pub fn weight(self) -> usize {
+        match self {
+            Priority::Control => 8,
+            Priority::Normal => 4,
+            Priority::Bulk => 1,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// How a single send should be scheduled.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SendOptions {
+    pub priority: Priority,
+    pub deadline: Option<Instant>, // Drop the message instead of writing it after this
+}
+
+impl SendOptions {
+    pub fn new(priority: Priority) -> Self {
+        Self {
+            priority,
+            deadline: None,
+        }
+    }
+
+    pub fn control() -> Self {
+        Self::new(Priority::Control)
+    }
+
+    /// Give up on the message if it has not been written within `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(Instant::now() + timeout);
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+}
+
+struct Queued {
+    frame: Vec<u8>,
+    deadline: Option<Instant>,
+    done: oneshot::Sender<io::Result<()>>,
+}
+
+impl Queued {
+    fn expire(self) {
+        let _ = self.done.send(Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "send deadline passed before the message was written",
+        )));
+    }
+}
+
+/// Deficit round robin over the priority classes.
+#[derive(Default)]
+struct Queues {
+    queues: [VecDeque<Queued>; 3],
+    deficits: [usize; 3],
+    current: usize,
+    topped_up: bool, // Whether the current class already got this round's quantum
+    closed: bool,
+    expired: u64,
+}
+
+impl Queues {
+    fn is_empty(&self) -> bool {
+        self.queues.iter().all(VecDeque::is_empty)
+    }
+
+    /// Fail every message at the front of a queue whose deadline has passed.
+    fn drop_expired(&mut self, now: Instant) {
+        for queue in &mut self.queues {
+            while queue.front().is_some_and(|item| item.deadline.is_some_and(|d| d <= now)) {
+                queue.pop_front().unwrap().expire();
+                self.expired += 1;
+            }
+        }
+    }
+
+    fn advance(&mut self) {
+        self.current = (self.current + 1) % self.queues.len();
+        self.topped_up = false;
+    }
+
+    fn next(&mut self, now: Instant) -> Option<Queued> {
+        self.drop_expired(now);
+        if self.is_empty() {
+            return None;
+        }
+        loop {
+            let class = self.current;
+            let Some(len) = self.queues[class].front().map(|item| item.frame.len()) else {
+                // An idle class does not bank credit for later
+                self.deficits[class] = 0;
+                self.advance();
+                continue;
+            };
+            if !self.topped_up {
+                self.deficits[class] += QUANTUM * Priority::ALL[class].weight();
+                self.topped_up = true;
+            }
+            if len <= self.deficits[class] {
+                self.deficits[class] -= len;
+                return self.queues[class].pop_front();
+            }
+            self.advance();
+        }
+    }
+}
+
+/// Outbound queue of one connection. Sends are queued by priority and a
+/// writer task drains them onto the stream.
+#[derive(Default)]
+pub(crate) struct Outbound {
+    queues: Mutex<Queues>,
+    ready: Notify,
+}
+
+impl Outbound {
+    /// Queue a frame. The receiver resolves once the frame is written, fails,
+    /// or is dropped for missing its deadline.
+    pub(crate) fn push(&self, frame: Vec<u8>, options: SendOptions) -> io::Result<oneshot::Receiver<io::Result<()>>> {
+        let (done, written) = oneshot::channel();
+        let item = Queued {
+            frame,
+            deadline: options.deadline,
+            done,
+        };
+        if options.deadline.is_some_and(|deadline| deadline <= Instant::now()) {
+            item.expire();
+            return Ok(written);
+        }
+
+        let mut queues = self.queues.lock().unwrap();
+        if queues.closed {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, "Peer not connected"));
+        }
+        queues.queues[options.priority.index()].push_back(item);
+        drop(queues);
+        self.ready.notify_one();
+        Ok(written)
+    }
+
+    /// Stop accepting frames. Anything still queued fails.
+    pub(crate) fn close(&self) {
+        let mut queues = self.queues.lock().unwrap();
+        queues.closed = true;
+        for queue in &mut queues.queues {
+            for item in queue.drain(..) {
+                let _ = item.done.send(Err(io::Error::new(io::ErrorKind::NotConnected, "connection closed")));
+            }
+        }
+        drop(queues);
+        self.ready.notify_one();
+    }
+
+    /// Messages dropped so far because their deadline passed.
+    pub(crate) fn expired(&self) -> u64 {
+        self.queues.lock().unwrap().expired
+    }
+
+    async fn next(&self) -> Option<Queued> {
+        loop {
+            let ready = self.ready.notified();
+            {
+                let mut queues = self.queues.lock().unwrap();
+                if let Some(item) = queues.next(Instant::now()) {
+                    return Some(item);
+                }
+                if queues.closed {
+                    return None;
+                }
+            }
+            ready.await;
+        }
+    }
+
+    /// Write queued frames to the stream until the queue is closed.
+    pub(crate) async fn run(
+        self: Arc<Self>,
+        writer: Arc<tokio::sync::Mutex<OwnedWriteHalf>>,
+        addr: SocketAddr,
+        throttle: Throttle,
+    ) {
+        while let Some(item) = self.next().await {
+            throttle.acquire(Flow::Upload, addr, item.frame.len()).await;
+            let result = wire::write_frame(&mut *writer.lock().await, &item.frame).await;
+            match &result {
+                Ok(()) => trace!(peer.addr = %addr, bytes = item.frame.len(), "TCP frame sent"),
+                Err(e) => debug!(peer.addr = %addr, error.kind = ?e.kind(), error = %e, "TCP write failed"),
+            }
+            let _ = item.done.send(result);
+        }
+        trace!(peer.addr = %addr, "outbound queue closed");
+    }
+}
@@ -0,0 +1,348 @@
+// substream.rs
+//? Bidirectional substreams multiplexed over a peer connection
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream, ReadBuf};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::{timeout, Duration};
+use tracing::{debug, trace, warn};
+
+use super::scheduler::SendOptions;
+use super::tcp_transport::TcpTransport;
+use super::{wire, NautilusTransport, TransportError};
+use crate::proto::{MessageKind, StreamFrame, StreamOp, TransportEnvelope};
+
+/// Bytes buffered inside a substream handle in each direction.
+const STREAM_BUFFER: usize = 64 * 1024;
+
+/// Frames from the peer held for a stream before it is reset for not being
+/// read. Streams have no flow control of their own, so this bounds memory.
+const INBOUND_FRAMES: usize = 256;
+
+const NEGOTIATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Streams a peer may have open over one connection at a time. Proposals past
+/// this are rejected as if no handler were registered.
+const MAX_STREAMS_PER_CONNECTION: usize = 64;
+
+/// Reply to a protocol proposal.
+const NOT_AVAILABLE: &str = "na";
+
+/// A stream is identified by the connection it runs over, the ID its opener
+/// picked and whether this node is that opener.
+type StreamKey = (SocketAddr, u64, bool);
+
+enum Inbound {
+    Data(Vec<u8>),
+    Fin,
+    Reset,
+}
+
+/// A conversation with a peer over one negotiated protocol. Reads return what
+/// the peer wrote; shutting down the write half tells the peer no more is coming.
+pub struct Substream {
+    inner: DuplexStream,
+    protocol: String,
+    peer_addr: SocketAddr,
+}
+
+impl Substream {
+    pub fn protocol(&self) -> &str {
+        &self.protocol
+    }
+
+    /// Address of the connection the stream runs over.
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+}
+
+impl AsyncRead for Substream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Substream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Open streams, protocol handlers and proposals awaiting an answer.
+#[derive(Clone, Default)]
+pub(crate) struct Substreams {
+    streams: Arc<Mutex<HashMap<StreamKey, mpsc::Sender<Inbound>>>>,
+    proposals: Arc<Mutex<HashMap<StreamKey, oneshot::Sender<bool>>>>,
+    handlers: Arc<Mutex<HashMap<String, mpsc::Sender<Substream>>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl Substreams {
+    fn supports(&self, protocol: &str) -> bool {
+        self.handlers
+            .lock()
+            .unwrap()
+            .get(protocol)
+            .is_some_and(|handler| !handler.is_closed())
+    }
+
+    fn open_over(&self, addr: SocketAddr) -> usize {
+        self.streams.lock().unwrap().keys().filter(|key| key.0 == addr).count()
+    }
+
+    fn forward(&self, key: StreamKey, inbound: Inbound) -> bool {
+        let stream = self.streams.lock().unwrap().get(&key).cloned();
+        match stream {
+            Some(stream) => match stream.try_send(inbound) {
+                Ok(()) => true,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    warn!(peer.addr = %key.0, stream.id = key.1, "substream not read fast enough, resetting");
+                    let _ = stream.try_send(Inbound::Reset);
+                    self.streams.lock().unwrap().remove(&key);
+                    false
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => false,
+            },
+            None => false,
+        }
+    }
+}
+
+fn frame(key: StreamKey, op: StreamOp, protocol: &str, data: Vec<u8>) -> Vec<u8> {
+    wire::encode(
+        MessageKind::Stream,
+        &StreamFrame {
+            stream_id: key.1,
+            initiator: key.2,
+            op: op as i32,
+            protocol: protocol.to_string(),
+            data,
+        },
+    )
+}
+
+/// Move bytes between the transport's end of a stream and the connection
+/// until both directions are finished, the stream is reset or shutdown begins.
+async fn pump(
+    tcp: TcpTransport,
+    substreams: Substreams,
+    key: StreamKey,
+    local: DuplexStream,
+    mut inbound: mpsc::Receiver<Inbound>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let addr = key.0;
+    let (mut reader, mut writer) = tokio::io::split(local);
+    let mut buf = vec![0; STREAM_BUFFER];
+    let (mut sent_fin, mut got_fin) = (false, false);
+
+    while !(sent_fin && got_fin) {
+        tokio::select! {
+            read = reader.read(&mut buf), if !sent_fin => {
+                let (op, data) = match read {
+                    Ok(0) => (StreamOp::Fin, Vec::new()),
+                    Ok(n) => (StreamOp::Data, buf[..n].to_vec()),
+                    Err(_) => (StreamOp::Reset, Vec::new()),
+                };
+                sent_fin = op == StreamOp::Fin;
+                if let Err(e) = tcp.send_with(addr, &frame(key, op, "", data), SendOptions::default()).await {
+                    debug!(peer.addr = %addr, stream.id = key.1, error.kind = ?e.kind(), error = %e, "substream write failed");
+                    break;
+                }
+                if op == StreamOp::Reset {
+                    break;
+                }
+            }
+            message = inbound.recv(), if !got_fin => match message {
+                Some(Inbound::Data(data)) => {
+                    // The handle was dropped; nothing will read the rest
+                    if writer.write_all(&data).await.is_err() {
+                        let _ = tcp.send_with(addr, &frame(key, StreamOp::Reset, "", Vec::new()), SendOptions::default()).await;
+                        break;
+                    }
+                }
+                Some(Inbound::Fin) => {
+                    got_fin = true;
+                    let _ = writer.shutdown().await;
+                }
+                Some(Inbound::Reset) | None => break,
+            },
+            _ = async { let _ = shutdown.wait_for(|stopping| *stopping).await; } => break,
+        }
+    }
+
+    substreams.streams.lock().unwrap().remove(&key);
+    trace!(peer.addr = %addr, stream.id = key.1, "substream closed");
+}
+
+impl NautilusTransport {
+    /// Accept substreams for a protocol. Streams peers open with it are
+    /// delivered on the returned receiver; dropping it stops accepting.
+    pub fn register_protocol(&self, protocol: &str) -> mpsc::Receiver<Substream> {
+        let (tx, rx) = mpsc::channel(16);
+        self.substreams.handlers.lock().unwrap().insert(protocol.to_string(), tx);
+        rx
+    }
+
+    pub fn unregister_protocol(&self, protocol: &str) {
+        self.substreams.handlers.lock().unwrap().remove(protocol);
+    }
+
+    /// Open a substream to a peer speaking `protocol`.
+    pub async fn open_stream(&self, peer_id: &str, protocol: &str) -> Result<Substream, TransportError> {
+        self.open_stream_any(peer_id, &[protocol]).await
+    }
+
+    /// Open a substream, proposing each protocol in turn until the peer accepts
+    /// one, as multistream-select does. Fails if the peer supports none of them.
+    pub async fn open_stream_any(&self, peer_id: &str, protocols: &[&str]) -> Result<Substream, TransportError> {
+        let dialed = self.connect_to_peer(peer_id).await?;
+        // Frames arrive keyed by the connection, which may be the peer's ephemeral port
+        let addr = self.tcp.connection_addr(dialed).await.unwrap_or(dialed);
+        let key = (addr, self.substreams.next_id.fetch_add(1, Ordering::Relaxed), true);
+
+        // Register before proposing so data sent right after the accept is kept
+        let (inbound_tx, inbound) = mpsc::channel(INBOUND_FRAMES);
+        self.substreams.streams.lock().unwrap().insert(key, inbound_tx);
+
+        for protocol in protocols {
+            let (answer_tx, answer) = oneshot::channel();
+            self.substreams.proposals.lock().unwrap().insert(key, answer_tx);
+            let proposed = self
+                .tcp
+                .send_with(addr, &frame(key, StreamOp::Propose, protocol, Vec::new()), SendOptions::control())
+                .await;
+            let accepted = match proposed {
+                Ok(_) => timeout(NEGOTIATION_TIMEOUT, answer).await,
+                Err(e) => {
+                    self.substreams.proposals.lock().unwrap().remove(&key);
+                    self.substreams.streams.lock().unwrap().remove(&key);
+                    return Err(e.into());
+                }
+            };
+            match accepted {
+                Ok(Ok(true)) => {
+                    debug!(peer.id = %peer_id, peer.addr = %addr, stream.id = key.1, protocol = %protocol, "substream opened");
+                    self.peer_manager.add_protocol(peer_id, protocol).await;
+                    return Ok(self.start_stream(key, protocol, inbound));
+                }
+                Ok(Ok(false)) => trace!(peer.id = %peer_id, protocol = %protocol, "protocol rejected"),
+                Ok(Err(_)) | Err(_) => {
+                    self.substreams.proposals.lock().unwrap().remove(&key);
+                    self.substreams.streams.lock().unwrap().remove(&key);
+                    return Err(TransportError::Unreachable(peer_id.to_string(), "protocol negotiation timed out".to_string()));
+                }
+            }
+        }
+
+        self.substreams.streams.lock().unwrap().remove(&key);
+        let _ = self.tcp.send_with(addr, &frame(key, StreamOp::Reset, "", Vec::new()), SendOptions::control()).await;
+        Err(TransportError::UnsupportedProtocol(protocols.join(", ")))
+    }
+
+    fn start_stream(&self, key: StreamKey, protocol: &str, inbound: mpsc::Receiver<Inbound>) -> Substream {
+        let (handle, local) = tokio::io::duplex(STREAM_BUFFER);
+        let shutdown = self.lifecycle.shutdown_signal();
+        self.lifecycle.spawn(pump(self.tcp.clone(), self.substreams.clone(), key, local, inbound, shutdown));
+        Substream {
+            inner: handle,
+            protocol: protocol.to_string(),
+            peer_addr: key.0,
+        }
+    }
+
+    pub(super) fn handle_stream_frame(&self, addr: SocketAddr, envelope: &TransportEnvelope) -> Result<(), TransportError> {
+        let stream = wire::decode_body::<StreamFrame>(envelope)?.message;
+        // The key is from this node's side: the peer's initiator flag flipped
+        let key = (addr, stream.stream_id, !stream.initiator);
+        let op = StreamOp::from_i32(stream.op).unwrap_or(StreamOp::Reset);
+
+        match op {
+            StreamOp::Propose => self.answer_proposal(key, &stream.protocol),
+            StreamOp::Accept | StreamOp::Reject => {
+                let answer = self.substreams.proposals.lock().unwrap().remove(&key);
+                if let Some(answer) = answer {
+                    let _ = answer.send(op == StreamOp::Accept && stream.protocol != NOT_AVAILABLE);
+                }
+            }
+            StreamOp::Data => {
+                if !self.substreams.forward(key, Inbound::Data(stream.data)) {
+                    self.reply(key, StreamOp::Reset, "");
+                }
+            }
+            StreamOp::Fin => {
+                self.substreams.forward(key, Inbound::Fin);
+            }
+            StreamOp::Reset => {
+                self.substreams.forward(key, Inbound::Reset);
+                self.substreams.streams.lock().unwrap().remove(&key);
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a control frame for a stream without holding up the listener loop.
+    fn reply(&self, key: StreamKey, op: StreamOp, protocol: &str) {
+        let tcp = self.tcp.clone();
+        let frame = frame(key, op, protocol, Vec::new());
+        self.lifecycle.spawn(async move {
+            if let Err(e) = tcp.send_with(key.0, &frame, SendOptions::control()).await {
+                debug!(peer.addr = %key.0, stream.id = key.1, error.kind = ?e.kind(), error = %e, "failed to answer substream frame");
+            }
+        });
+    }
+
+    /// Accept a proposed protocol if a handler is registered for it and the
+    /// connection has room for another stream.
+    fn answer_proposal(&self, key: StreamKey, protocol: &str) {
+        let addr = key.0;
+        if !self.substreams.supports(protocol) {
+            debug!(peer.addr = %addr, stream.id = key.1, protocol = %protocol, "no handler for proposed protocol");
+            return self.reply(key, StreamOp::Reject, NOT_AVAILABLE);
+        }
+        if self.substreams.open_over(addr) >= MAX_STREAMS_PER_CONNECTION {
+            warn!(peer.addr = %addr, stream.id = key.1, protocol = %protocol, "too many substreams on connection, rejecting");
+            return self.reply(key, StreamOp::Reject, NOT_AVAILABLE);
+        }
+
+        // Register now so frames following the proposal are not lost
+        let (inbound_tx, inbound) = mpsc::channel(INBOUND_FRAMES);
+        self.substreams.streams.lock().unwrap().insert(key, inbound_tx);
+
+        let (transport, protocol) = (self.clone(), protocol.to_string());
+        self.lifecycle.spawn(async move {
+            let accept = frame(key, StreamOp::Accept, &protocol, Vec::new());
+            if let Err(e) = transport.tcp.send_with(addr, &accept, SendOptions::control()).await {
+                debug!(peer.addr = %addr, stream.id = key.1, error.kind = ?e.kind(), error = %e, "failed to accept substream");
+                transport.substreams.streams.lock().unwrap().remove(&key);
+                return;
+            }
+
+            let peer_key = transport.peer_key(addr).await;
+            transport.peer_manager.add_protocol(&peer_key, &protocol).await;
+
+            let stream = transport.start_stream(key, &protocol, inbound);
+            let handler = transport.substreams.handlers.lock().unwrap().get(&protocol).cloned();
+            if let Some(handler) = handler {
+                if handler.try_send(stream).is_err() {
+                    warn!(peer.addr = %addr, protocol = %protocol, "protocol handler is not keeping up, dropping stream");
+                }
+            }
+        });
+    }
+}
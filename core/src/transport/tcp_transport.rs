@@ -2,97 +2,480 @@
 //tcp_transport.rs
 use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::Arc;
-use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::io::{self, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{mpsc, Mutex};
-use tokio::time::{sleep, Duration};
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio::time::{sleep, timeout, Duration};
+use tracing::{debug, error, field, info, info_span, instrument, trace, warn, Instrument, Span};
 
+#[cfg(feature = "identity_integration")]
+use super::envelope::EnvelopeSigner;
+use super::handshake::{self, Role};
+use super::lifecycle::Lifecycle;
+use super::scheduler::{Outbound, SendOptions};
+use super::throttle::{Flow, Throttle};
+use super::wire::{self, WireMessage};
+use crate::proto::{Handshake, HandshakeProof, MessageKind};
+use crate::record::{Ban, BanList};
+
+/// How long either side waits for the other half of the handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+type PeerMap = Arc<Mutex<Connections>>;
+type FrameSender = mpsc::Sender<(SocketAddr, Vec<u8>)>;
+type Writer = Arc<Mutex<OwnedWriteHalf>>;
+
+/// Which side opened a connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Inbound,
+    Outbound,
+}
+
+struct Connection {
+    id: u64, // Tells apart successive connections that reuse an address
+    writer: Writer,
+    outbound: Arc<Outbound>,
+    remote: Handshake, // Its public key is only set when the peer proved it
+    direction: Direction,
+}
+
+/// Open connections, indexed by socket address and by the peer ID each one
+/// announced, so only one connection per proven peer survives.
+#[derive(Default)]
+struct Connections {
+    streams: HashMap<SocketAddr, Connection>,
+    by_peer: HashMap<String, SocketAddr>,
+    dial_addrs: HashMap<SocketAddr, SocketAddr>, // Address a peer listens on -> address of its connection
+}
+
+impl Connections {
+    /// Map a dial address onto the connection currently serving it.
+    fn resolve(&self, addr: SocketAddr) -> SocketAddr {
+        if self.streams.contains_key(&addr) {
+            return addr;
+        }
+        self.dial_addrs.get(&addr).copied().unwrap_or(addr)
+    }
+
+    fn outbound(&self, addr: SocketAddr) -> Option<Arc<Outbound>> {
+        self.streams.get(&self.resolve(addr)).map(|conn| conn.outbound.clone())
+    }
+
+    /// Add a connection that has completed its handshake. If the peer already
+    /// has one, `keep_new` picks the survivor; the writer of the connection
+    /// that lost is returned so the caller can close it.
+    fn insert(&mut self, addr: SocketAddr, dial_addr: SocketAddr, conn: Connection, local_id: &str) -> Option<Writer> {
+        // Without IDs on both ends there is nothing both sides can agree on
+        let peer_id = conn.remote.peer_id.clone();
+        let existing = match peer_id.is_empty() || local_id.is_empty() {
+            true => None,
+            false => self.by_peer.get(&peer_id).copied().filter(|existing| *existing != addr),
+        };
+
+        let mut evicted = None;
+        let mut index = !peer_id.is_empty();
+        if let Some(existing_addr) = existing {
+            match keep_new(local_id, &self.streams[&existing_addr], &conn) {
+                Some(false) => {
+                    debug!(peer.id = %peer_id, kept = %existing_addr, dropped = %addr, "duplicate connection, keeping existing");
+                    if dial_addr != existing_addr {
+                        self.dial_addrs.insert(dial_addr, existing_addr);
+                    }
+                    return Some(conn.writer);
+                }
+                Some(true) => {
+                    debug!(peer.id = %peer_id, kept = %addr, dropped = %existing_addr, "duplicate connection, replacing existing");
+                    evicted = self.evict(existing_addr);
+                }
+                // The peer ID stays with the connection that had it first
+                None => {
+                    debug!(peer.id = %peer_id, existing = %existing_addr, new = %addr, "unproven connections share a peer ID, keeping both");
+                    index = false;
+                }
+            }
+        }
+
+        if index {
+            self.by_peer.insert(peer_id, addr);
+        }
+        if dial_addr != addr {
+            self.dial_addrs.insert(dial_addr, addr);
+        }
+        if let Some(replaced) = self.streams.insert(addr, conn) {
+            replaced.outbound.close();
+            evicted = Some(replaced.writer);
+        }
+        evicted
+    }
+
+    /// Drop the connection at `addr` along with everything pointing at it.
+    fn evict(&mut self, addr: SocketAddr) -> Option<Writer> {
+        let conn = self.streams.remove(&addr)?;
+        conn.outbound.close();
+        if self.by_peer.get(&conn.remote.peer_id) == Some(&addr) {
+            self.by_peer.remove(&conn.remote.peer_id);
+            // Another connection kept under the same ID takes it over
+            let other = self.streams.iter().find(|(_, other)| other.remote.peer_id == conn.remote.peer_id);
+            if let Some((other, _)) = other {
+                self.by_peer.insert(conn.remote.peer_id.clone(), *other);
+            }
+        }
+        self.dial_addrs.retain(|_, target| *target != addr);
+        Some(conn.writer)
+    }
+
+    /// Drop the connection at `addr`, but only if it is still the one with `id`.
+    fn remove(&mut self, addr: SocketAddr, id: u64) -> bool {
+        let current = self.streams.get(&addr).is_some_and(|conn| conn.id == id);
+        if current {
+            self.evict(addr);
+        }
+        current
+    }
+}
+
+/// Decide which of two connections announcing the same peer ID survives, or
+/// `None` to keep both. Only a proven key shows two connections lead to the
+/// same peer: a proven connection beats one that only claims the ID, and
+/// neither of two proving different keys replaces the other. Between two that
+/// proved the same key, both ends must reach the same answer without talking
+/// to each other, so the rule only uses the two peer IDs: keep the connection
+/// dialed by the node with the smaller ID. Two connections in the same
+/// direction mean a redial, so the newer wins.
+fn keep_new(local_id: &str, existing: &Connection, new: &Connection) -> Option<bool> {
+    let (existing_key, new_key) = (&existing.remote.public_key, &new.remote.public_key);
+    match (existing_key.is_empty(), new_key.is_empty()) {
+        (true, true) => return None,
+        (true, false) => return Some(true),
+        (false, true) => return Some(false),
+        (false, false) if existing_key != new_key => return Some(false),
+        (false, false) => {}
+    }
+    if existing.direction == new.direction {
+        return Some(true);
+    }
+    let preferred = if local_id < new.remote.peer_id.as_str() {
+        Direction::Outbound
+    } else {
+        Direction::Inbound
+    };
+    Some(new.direction == preferred)
+}
+
+/// Close a connection that lost deduplication or was replaced.
+async fn close_writer(writer: Writer, addr: SocketAddr) {
+    if let Err(e) = writer.lock().await.shutdown().await {
+        debug!(peer.addr = %addr, error.kind = ?e.kind(), error = %e, "error shutting down stream");
+    }
+}
 
 #[derive(Clone)]
 pub struct TcpTransport {
-    peers: Arc<Mutex<HashMap<SocketAddr, Arc<Mutex<TcpStream>>>>>, // Manage multiple connections
+    peers: PeerMap, // Write halves of the open connections; each read half has its own task
     addr: SocketAddr,
+    handshake: Arc<RwLock<Handshake>>,            // What this node announces about itself
+    handler: Arc<Mutex<Option<FrameSender>>>,     // Where frames go once `listen` is running
+    lifecycle: Lifecycle,                         // Owns the accept and per-connection tasks
+    throttle: Throttle,                           // Bandwidth limits, shared with UDP
+    bans: BanList,                                // Refused peers and addresses, shared with UDP
+    #[cfg(feature = "identity_integration")]
+    signer: Arc<RwLock<Option<Arc<EnvelopeSigner>>>>, // Proves the announced key; peers must then prove theirs
+    next_id: Arc<AtomicU64>,
 }
 
 impl TcpTransport {
-    /// Creates a new TcpTransport instance.
-    pub fn new(addr: SocketAddr) -> Self {
+    /// Creates a new TcpTransport instance whose tasks belong to `lifecycle`.
+    pub fn new(addr: SocketAddr, lifecycle: Lifecycle, throttle: Throttle, bans: BanList) -> Self {
         TcpTransport {
-            peers: Arc::new(Mutex::new(HashMap::new())),
+            peers: Arc::new(Mutex::new(Connections::default())),
             addr,
+            handshake: Arc::new(RwLock::new(Handshake {
+                listen_port: addr.port() as u32,
+                agent: format!("nautilus-core/{}", env!("CARGO_PKG_VERSION")),
+                ..Default::default()
+            })),
+            handler: Arc::new(Mutex::new(None)),
+            lifecycle,
+            throttle,
+            bans,
+            #[cfg(feature = "identity_integration")]
+            signer: Arc::default(),
+            next_id: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Set the peer ID and public key announced in handshakes. A public key
+    /// has to be proven, so announce one only together with `set_signer`.
+    pub fn set_identity(&self, peer_id: &str, public_key: &str) {
+        let mut handshake = self.handshake.write().unwrap();
+        handshake.peer_id = peer_id.to_string();
+        handshake.public_key = public_key.to_string();
+    }
+
+    /// Announce the signer's identity and prove it in every handshake. From
+    /// then on peers have to prove theirs too, or the handshake is refused.
+    #[cfg(feature = "identity_integration")]
+    pub(super) fn set_signer(&self, signer: Arc<EnvelopeSigner>) {
+        self.set_identity(signer.peer_id(), signer.public_key());
+        *self.signer.write().unwrap() = Some(signer);
+    }
+
+    fn local_handshake(&self) -> Handshake {
+        self.handshake.read().unwrap().clone()
+    }
+
+    /// Whether peers have to prove the identity they announce.
+    fn requires_proof(&self) -> bool {
+        #[cfg(feature = "identity_integration")]
+        {
+            self.signer.read().unwrap().is_some()
+        }
+        #[cfg(not(feature = "identity_integration"))]
+        {
+            false
+        }
+    }
+
+    /// Sign the `nonce` sent by the peer `audience`. Without a signer there is
+    /// nothing to sign with, and the signature is left empty.
+    fn sign_nonce(&self, role: Role, audience: &str, nonce: &[u8]) -> io::Result<Vec<u8>> {
+        #[cfg(feature = "identity_integration")]
+        if let Some(signer) = self.signer.read().unwrap().clone() {
+            return handshake::sign(&signer, role, &self.local_handshake(), audience, nonce);
+        }
+        let _ = (role, audience, nonce);
+        Ok(Vec::new())
+    }
+
+    /// Refuse a peer that announces no key when peers have to prove theirs.
+    fn check_announced(&self, remote: &Handshake) -> io::Result<()> {
+        if remote.public_key.is_empty() && self.requires_proof() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("peer {:?} announced no public key to prove its ID with", remote.peer_id),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check the proof of the key `remote` announced against the `nonce` this
+    /// node sent. Returns the handshake to keep for the connection: its public
+    /// key is cleared unless it was proven, so an unchecked key is never used.
+    fn authenticate(&self, role: Role, mut remote: Handshake, signature: &[u8], nonce: &[u8]) -> io::Result<Handshake> {
+        self.check_announced(&remote)?;
+        if !remote.public_key.is_empty() {
+            #[cfg(feature = "identity_integration")]
+            handshake::verify(role, &remote, signature, &self.local_handshake().peer_id, nonce)?;
+            // Keys cannot be checked in this build, so none is taken on trust
+            #[cfg(not(feature = "identity_integration"))]
+            {
+                let _ = (role, signature, nonce);
+                remote.public_key.clear();
+            }
+        }
+        remote.nonce.clear();
+        remote.signature.clear();
+        Ok(remote)
+    }
+
+    /// Record a connection that completed its handshake. Returns the ID to
+    /// hand to its reader, or `None` when a connection to the same peer
+    /// already won the tie-break and this one has been closed.
+    async fn register(
+        &self,
+        addr: SocketAddr,
+        dial_addr: SocketAddr,
+        writer: OwnedWriteHalf,
+        remote: &Handshake,
+        direction: Direction,
+    ) -> Option<u64> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let writer = Arc::new(Mutex::new(writer));
+        let outbound = Arc::new(Outbound::default());
+        let conn = Connection {
+            id,
+            writer: writer.clone(),
+            outbound: outbound.clone(),
+            remote: remote.clone(),
+            direction,
+        };
+        let local_id = self.local_handshake().peer_id;
+        let closed = self.peers.lock().await.insert(addr, dial_addr, conn, &local_id);
+
+        let survived = !closed.as_ref().is_some_and(|closed| Arc::ptr_eq(closed, &writer));
+        if let Some(closed) = closed {
+            close_writer(closed, addr).await;
+        }
+        if survived {
+            self.throttle.bind_addr(addr, &remote.peer_id);
+            self.throttle.bind_addr(dial_addr, &remote.peer_id);
+            // Datagrams from the peer come from the port it listens on
+            if remote.listen_port != 0 {
+                self.throttle.bind_addr(SocketAddr::new(addr.ip(), remote.listen_port as u16), &remote.peer_id);
+            }
+            self.lifecycle.spawn(outbound.run(writer, addr, self.throttle.clone()).in_current_span());
         }
+        survived.then_some(id)
     }
 
     /// Start listening for incoming connections.
+    /// Every frame read from any connection, including the peer's handshake,
+    /// is forwarded to `sender`.
     pub async fn listen(
         &self,
-        sender: mpsc::Sender<(SocketAddr, Vec<u8>)>,
-        mut shutdown_rx: tokio::sync::watch::Receiver<bool>,
+        sender: FrameSender,
+        mut shutdown_rx: watch::Receiver<bool>,
     ) -> io::Result<()> {
         let listener = TcpListener::bind(self.addr).await?;
-        println!("TCP listening on {}", self.addr);
-    
+        info!(local.addr = %self.addr, "TCP listening");
+        *self.handler.lock().await = Some(sender.clone());
+
         loop {
             tokio::select! {
                 // Accept new connections
                 Ok((stream, addr)) = listener.accept() => {
-                    println!("Accepted connection from {}", addr);
-    
-                    let stream = Arc::new(Mutex::new(stream));
-                    self.peers.lock().await.insert(addr, stream.clone());
-    
-                    let sender_clone = sender.clone();
-                    tokio::spawn(async move {
-                        let mut buf = vec![0; 1024];
-                        let mut stream = stream.lock().await;
-                        while let Ok(len) = stream.read(&mut buf).await {
-                            if len == 0 {
-                                break; // EOF
-                            }
-                            let message = buf[..len].to_vec();
-                            if sender_clone.send((addr, message)).await.is_err() {
-                                eprintln!("Failed to send message to handler for {}", addr);
-                            }
+                    if let Some(ban) = self.bans.ip_ban(addr.ip()) {
+                        debug!(peer.addr = %addr, ban.target = %ban.target, "refused connection from banned address");
+                        continue;
+                    }
+                    let span = info_span!(
+                        "connection",
+                        peer.addr = %addr,
+                        peer.id = field::Empty,
+                        direction = "inbound"
+                    );
+                    span.in_scope(|| info!("accepted connection"));
+
+                    let transport = self.clone();
+                    let sender = sender.clone();
+                    self.lifecycle.spawn(async move {
+                        if let Err(e) = transport.accept_peer(stream, addr, sender).await {
+                            warn!(error.kind = ?e.kind(), error = %e, "inbound handshake failed");
                         }
-                    });
+                    }.instrument(span));
                 }
-    
+
                 // Check for shutdown signal
                 _ = shutdown_rx.changed() => {
                     if *shutdown_rx.borrow() {
-                        println!("Shutting down TCP listener.");
+                        info!(local.addr = %self.addr, "shutting down TCP listener");
                         break;
                     }
                 }
             }
         }
-    
+
+        Ok(())
+    }
+
+    /// Answer the handshake of an inbound connection, then read its frames.
+    async fn accept_peer(&self, stream: TcpStream, addr: SocketAddr, sender: FrameSender) -> io::Result<()> {
+        let (mut reader, mut writer) = stream.into_split();
+
+        let frame = timeout(HANDSHAKE_TIMEOUT, wire::read_frame(&mut reader))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "handshake timed out"))??
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "closed before handshake"))?;
+        let remote = expect::<Handshake>(&frame, MessageKind::Handshake)?;
+        Span::current().record("peer.id", remote.peer_id.as_str());
+        if let Some(ban) = self.bans.peer_ban(&remote.peer_id) {
+            return Err(banned(&ban));
+        }
+        self.check_announced(&remote)?;
+
+        let nonce = handshake::nonce();
+        let ack = Handshake {
+            nonce: nonce.clone(),
+            signature: self.sign_nonce(Role::Ack, &remote.peer_id, &remote.nonce)?,
+            ..self.local_handshake()
+        };
+        let ack = wire::encode(MessageKind::HandshakeAck, &ack);
+        wire::write_frame(&mut writer, &ack).await?;
+        debug!(bytes = ack.len(), "handshake acknowledged");
+
+        // A dialer that announced a key proves it next
+        let signature = match remote.public_key.is_empty() {
+            true => Vec::new(),
+            false => {
+                let frame = timeout(HANDSHAKE_TIMEOUT, wire::read_frame(&mut reader))
+                    .await
+                    .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "handshake proof timed out"))??
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "closed before handshake proof"))?;
+                expect::<HandshakeProof>(&frame, MessageKind::HandshakeProof)?.signature
+            }
+        };
+        let remote = self.authenticate(Role::Proof, remote, &signature, &nonce)?;
+
+        let dial_addr = match remote.listen_port {
+            0 => addr,
+            port => SocketAddr::new(addr.ip(), port as u16),
+        };
+        let id = self.register(addr, dial_addr, writer, &remote, Direction::Inbound).await;
+        // Let the handler learn who is on the other end
+        if id.is_some() && sender.send((addr, frame)).await.is_err() {
+            warn!("failed to forward handshake to handler");
+        }
+
+        // A connection that lost the tie-break is still read until the peer
+        // closes its side, so frames it sent before noticing are not lost.
+        let id = id.unwrap_or(u64::MAX);
+        let shutdown = self.lifecycle.shutdown_signal();
+        read_frames(self.peers.clone(), reader, addr, id, sender, self.throttle.clone(), shutdown).await;
         Ok(())
     }
-    
 
-    
-    /// Connect to a remote peer.
-    pub async fn connect(&self, peer_addr: SocketAddr) -> io::Result<()> {
+    /// Connect to a remote peer and exchange handshakes.
+    /// Returns the handshake the peer answered with.
+    #[instrument(
+        name = "connection",
+        skip(self),
+        fields(peer.addr = %peer_addr, peer.id = field::Empty, direction = "outbound")
+    )]
+    pub async fn connect(&self, peer_addr: SocketAddr) -> io::Result<Handshake> {
+        if let Some(ban) = self.bans.ip_ban(peer_addr.ip()) {
+            return Err(banned(&ban));
+        }
         let stream: TcpStream = TcpStream::connect(peer_addr).await?;
-        println!("Connection Initiated to {}", peer_addr);
-    
-        let stream = Arc::new(Mutex::new(stream));
-        self.peers.lock().await.insert(peer_addr, stream);
-    
+        debug!("connection initiated");
+        let (mut reader, mut writer) = stream.into_split();
+
         // Perform the handshake
-        match self.handshake_with_peer(peer_addr).await {
-            Ok(_) => {
-                println!("Handshake successful with {}", peer_addr);
-                Ok(())
+        match timeout(HANDSHAKE_TIMEOUT, self.handshake_with_peer(&mut reader, &mut writer)).await {
+            Ok(Ok(remote)) => {
+                Span::current().record("peer.id", remote.peer_id.as_str());
+                // Dropping both halves closes the connection
+                if let Some(ban) = self.bans.peer_ban(&remote.peer_id) {
+                    warn!(ban.target = %ban.target, "dialed a banned peer");
+                    return Err(banned(&ban));
+                }
+                let id = self
+                    .register(peer_addr, peer_addr, writer, &remote, Direction::Outbound)
+                    .await
+                    .unwrap_or(u64::MAX);
+
+                // Without a running listener there is nowhere to deliver frames to
+                if let Some(sender) = self.handler.lock().await.clone() {
+                    let shutdown = self.lifecycle.shutdown_signal();
+                    self.lifecycle.spawn(
+                        read_frames(self.peers.clone(), reader, peer_addr, id, sender, self.throttle.clone(), shutdown)
+                            .in_current_span(),
+                    );
+                }
+                info!("connected");
+                Ok(remote)
             }
-            Err(e) => {
-                // If the handshake fails, remove the peer and return an error
-                self.peers.lock().await.remove(&peer_addr);
-                eprintln!("Handshake failed with {}: {}", peer_addr, e);
+            Ok(Err(e)) => {
+                warn!(error.kind = ?e.kind(), error = %e, "handshake failed");
                 Err(e)
             }
+            Err(_) => {
+                warn!(error.kind = ?io::ErrorKind::TimedOut, "handshake timed out");
+                Err(io::Error::new(io::ErrorKind::TimedOut, "handshake timed out"))
+            }
         }
     }
 
@@ -108,21 +491,29 @@ impl TcpTransport {
         loop {
             match self.connect(peer_addr).await {
                 Ok(_) => {
-                    println!("Reconnected to {}", peer_addr);
+                    info!(peer.addr = %peer_addr, attempts = attempts + 1, "reconnected");
                     return Ok(());
                 }
                 Err(e) => {
                     attempts += 1;
                     if attempts >= max_attempts {
-                        eprintln!(
-                            "Failed to reconnect to {} after {} attempts: {}",
-                            peer_addr, attempts, e
+                        error!(
+                            peer.addr = %peer_addr,
+                            attempts,
+                            error.kind = ?e.kind(),
+                            error = %e,
+                            "giving up on reconnection"
                         );
                         return Err(e);
                     }
-                    eprintln!(
-                        "Reconnection attempt {}/{} to {} failed: {}. Retrying in {:?}...",
-                        attempts, max_attempts, peer_addr, e, delay
+                    warn!(
+                        peer.addr = %peer_addr,
+                        attempts,
+                        max_attempts,
+                        retry_in = ?delay,
+                        error.kind = ?e.kind(),
+                        error = %e,
+                        "reconnection attempt failed"
                     );
                     sleep(delay).await;
                     delay = delay.saturating_mul(2); // Exponential backoff
@@ -130,172 +521,140 @@ impl TcpTransport {
             }
         }
     }
-    async fn handshake_with_peer(&self, peer_addr: SocketAddr) -> io::Result<()> {
-        let peers = self.peers.lock().await;
-        if let Some(peer_stream) = peers.get(&peer_addr) {
-            let mut stream = peer_stream.lock().await;
-
-            // Send handshake message
-            let handshake_message = b"HANDSHAKE_REQUEST";
-            stream.write_all(handshake_message).await?;
-            println!("Sent handshake request to {}", peer_addr);
-
-            // Wait for response
-            let mut response = vec![0; 1024];
-            let len = stream.read(&mut response).await?;
-            response.truncate(len);
-
-            if response == b"ACK_HANDSHAKE" {
-                println!("Handshake successful with {}", peer_addr);
-                Ok(())
-            } else {
-                Err(io::Error::new(io::ErrorKind::Other, "Handshake failed"))
-            }
-        } else {
-            Err(io::Error::new(
-                io::ErrorKind::NotConnected,
-                "Peer not connected",
-            ))
+
+    async fn handshake_with_peer(
+        &self,
+        reader: &mut OwnedReadHalf,
+        writer: &mut OwnedWriteHalf,
+    ) -> io::Result<Handshake> {
+        // Send handshake message
+        let local = self.local_handshake();
+        let nonce = handshake::nonce();
+        let request = wire::encode(
+            MessageKind::Handshake,
+            &Handshake {
+                nonce: nonce.clone(),
+                ..local.clone()
+            },
+        );
+        wire::write_frame(writer, &request).await?;
+        trace!(bytes = request.len(), "sent handshake request");
+
+        // Wait for response
+        let frame = wire::read_frame(reader)
+            .await?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "closed during handshake"))?;
+        let ack = expect::<Handshake>(&frame, MessageKind::HandshakeAck)?;
+        let (audience, their_nonce) = (ack.peer_id.clone(), ack.nonce.clone());
+        let signature = ack.signature.clone();
+        let remote = self.authenticate(Role::Ack, ack, &signature, &nonce)?;
+        debug!("handshake acknowledged");
+
+        // Prove the key this node announced
+        if !local.public_key.is_empty() {
+            let proof = HandshakeProof {
+                signature: self.sign_nonce(Role::Proof, &audience, &their_nonce)?,
+            };
+            wire::write_frame(writer, &wire::encode(MessageKind::HandshakeProof, &proof)).await?;
         }
+        Ok(remote)
     }
 
-    /// Send data to a specific peer.
-    pub async fn send(&self, peer_addr: SocketAddr, data: &[u8]) -> io::Result<usize> {
-        let peers = self.peers.lock().await;
-        if let Some(peer_stream) = peers.get(&peer_addr) {
-            let mut stream = peer_stream.lock().await;
-            stream.write(data).await
-        } else {
-            Err(io::Error::new(
-                io::ErrorKind::NotConnected,
-                "Peer not connected",
-            ))
-        }
+    /// Check whether an open connection exists for the address, either
+    /// directly or through the address the peer listens on.
+    pub async fn is_connected(&self, peer_addr: SocketAddr) -> bool {
+        self.peers.lock().await.outbound(peer_addr).is_some()
     }
 
-    /// Broadcast data to all connected peers.
-    pub async fn broadcast(&self, data: &[u8]) -> io::Result<()> {
+    /// Addresses of every open connection.
+    pub async fn connected_peers(&self) -> Vec<SocketAddr> {
+        self.peers.lock().await.streams.keys().copied().collect()
+    }
+
+    /// Address of the connection serving a dial address. For an inbound
+    /// connection this is the peer's ephemeral port rather than its listening one.
+    pub async fn connection_addr(&self, peer_addr: SocketAddr) -> Option<SocketAddr> {
         let peers = self.peers.lock().await;
-        for (addr, peer_stream) in peers.iter() {
-            let mut stream = peer_stream.lock().await;
-            if let Err(e) = stream.write_all(data).await {
-                eprintln!(
-                    "Failed to send to {}: {}. Attempting reconnection...",
-                    addr, e
-                );
-                drop(stream); // Release lock to allow reconnect
-                self.reconnect_peer(*addr, 5).await?; // Try reconnecting
-            }
-        }
-        Ok(())
+        let addr = peers.resolve(peer_addr);
+        peers.streams.contains_key(&addr).then_some(addr)
     }
 
+    /// Address of the connection to an authenticated peer, if one is open.
+    pub async fn peer_connection(&self, peer_id: &str) -> Option<SocketAddr> {
+        self.peers.lock().await.by_peer.get(peer_id).copied()
+    }
 
-    // Handle incoming messages from a peer.
-    async fn handle_peer(
-        peers: Arc<Mutex<HashMap<SocketAddr, Arc<Mutex<TcpStream>>>>>,
-        stream: Arc<Mutex<TcpStream>>,
-        addr: SocketAddr,
-        sender: mpsc::Sender<(SocketAddr, Vec<u8>)>,
-    ) {
-        let mut buf = vec![0; 1024]; // Fixed buffer size of 1024 bytes
+    /// Peer ID announced on the connection at `peer_addr`, if it sent one.
+    pub async fn peer_id(&self, peer_addr: SocketAddr) -> Option<String> {
+        let peers = self.peers.lock().await;
+        let conn = peers.streams.get(&peers.resolve(peer_addr))?;
+        Some(conn.remote.peer_id.clone()).filter(|id| !id.is_empty())
+    }
 
-        let mut stream_guard = stream.lock().await;
+    /// Handshake the peer at `peer_addr` connected with. Its public key is
+    /// only set when the peer proved it holds that key.
+    pub async fn remote_handshake(&self, peer_addr: SocketAddr) -> Option<Handshake> {
+        let peers = self.peers.lock().await;
+        peers.streams.get(&peers.resolve(peer_addr)).map(|conn| conn.remote.clone())
+    }
 
-        // Wait for handshake message
-        match stream_guard.read(&mut buf).await {
-            Ok(0) => {
-                println!("Connection closed by {} (before handshake)", addr);
-                return;
-            }
-            Ok(len) => {
-                buf.truncate(len);
-                if buf == b"HANDSHAKE_REQUEST" {
-                    println!("Received handshake request from {}", addr);
-                    // Respond with handshake acknowledgment
-                    if let Err(e) = stream_guard.write_all(b"ACK_HANDSHAKE").await {
-                        eprintln!("Failed to send handshake acknowledgment to {}: {}", addr, e);
-                        return;
-                    }
-                } else {
-                    eprintln!("Invalid handshake message from {}", addr);
-                    return;
-                }
-            }
-            Err(e) => {
-                eprintln!("Error during handshake with {}: {}", addr, e);
-                return;
-            }
+    /// Drop the connection to a peer, shutting the stream down if it is still open.
+    pub async fn disconnect(&self, peer_addr: SocketAddr) {
+        let removed = {
+            let mut peers = self.peers.lock().await;
+            let addr = peers.resolve(peer_addr);
+            peers.evict(addr)
+        };
+        if let Some(peer_stream) = removed {
+            close_writer(peer_stream, peer_addr).await;
         }
+    }
 
-        // Handshake successful, proceed with communication
-        println!("Handshake completed with {}", addr);
-        let mut last_activity = tokio::time::Instant::now();
-        println!("{:?}", last_activity);
-        loop {
-            // Check if we've passed the 60-second inactivity timeout
-            if last_activity.elapsed() > Duration::from_secs(60) {
-                println!(
-                    "Connection to {} has been idle for too long. Closing connection.",
-                    addr
-                );
-                break; // Close the connection
-            }
+    /// Send one frame to a specific peer at normal priority.
+    pub async fn send(&self, peer_addr: SocketAddr, data: &[u8]) -> io::Result<usize> {
+        self.send_with(peer_addr, data, SendOptions::default()).await
+    }
 
-            match stream_guard.read(&mut buf).await {
-                Ok(0) => {
-                    // Connection closed by the client (EOF reached)
-                    println!("Connection closed by {} (EOF reached)", addr);
-                    break; // Exit the loop
-                }
-                Ok(len) => {
-                    if len > 0 {
-                        buf.truncate(len); // Truncate buffer to actual data length
-                        println!(
-                            "Received {} bytes from {}: {}",
-                            len,
-                            addr,
-                            String::from_utf8_lossy(&buf)
-                        );
+    /// Queue one frame for a peer and wait until it is written. Fails with
+    /// `TimedOut` if the deadline in `options` passes first.
+    pub async fn send_with(&self, peer_addr: SocketAddr, data: &[u8], options: SendOptions) -> io::Result<usize> {
+        let outbound = self.peers.lock().await.outbound(peer_addr);
+        let Some(outbound) = outbound else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "Peer not connected",
+            ));
+        };
+        let written = outbound.push(data.to_vec(), options)?;
+        written
+            .await
+            .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::NotConnected, "connection closed")))?;
+        Ok(data.len())
+    }
 
-                        // Send the data to the message handler
-                        if sender.send((addr, buf.clone())).await.is_err() {
-                            eprintln!("Failed to send message to handler for {}", addr);
-                        }
-                        last_activity = tokio::time::Instant::now(); // Reset the last activity time
-                    }
-                }
-                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                    // Non-blocking error, continue retrying
-                    continue;
-                }
-                Err(ref e) if e.kind() == io::ErrorKind::ConnectionReset => {
-                    // The connection was reset by the client
-                    println!("Connection reset by peer: {}", addr);
-                    break; // Exit the loop if the client disconnects
-                }
-                Err(e) => {
-                    eprintln!("Error reading from {}: {}", addr, e);
-                    break;
-                }
-            }
-        }
+    /// Messages to a peer dropped so far because their deadline passed.
+    pub async fn expired_sends(&self, peer_addr: SocketAddr) -> u64 {
+        self.peers.lock().await.outbound(peer_addr).map_or(0, |outbound| outbound.expired())
+    }
 
-        // Remove the peer from the map if disconnected
-        peers.lock().await.remove(&addr);
-        println!("Peer {} removed from the peer map.", addr);
+    /// Broadcast a frame to all connected peers.
+    pub async fn broadcast(&self, data: &[u8]) -> io::Result<()> {
+        self.send_all(data, SendOptions::default()).await
     }
-    
+
     /// Closes all connections and clears the peer map.
     pub async fn close_all(&self) -> io::Result<()> {
         let mut peers = self.peers.lock().await;
-        for (addr, peer_stream) in peers.drain() {
-            let mut stream = peer_stream.lock().await;
+        peers.by_peer.clear();
+        peers.dial_addrs.clear();
+        for (addr, conn) in peers.streams.drain() {
+            conn.outbound.close();
+            let mut stream = conn.writer.lock().await;
             if let Err(e) = stream.shutdown().await {
-                eprintln!("Error closing connection to {}: {}", addr, e);
+                warn!(peer.addr = %addr, error.kind = ?e.kind(), error = %e, "error closing connection");
             }
         }
-        println!("All connections closed.");
+        info!("all connections closed");
         Ok(())
     }
 
@@ -303,22 +662,99 @@ impl TcpTransport {
         TcpStream::connect(peer_addr).await
     }
 
-    /// Send data to all connected peers.
-    pub async fn send_all(&self, data: &[u8]) -> io::Result<()> {
-        let peers = self.peers.lock().await;
-        for (addr, peer_stream) in peers.iter() {
-            let mut stream = peer_stream.lock().await;
-            if let Err(e) = stream.write_all(data).await {
-                eprintln!(
-                    "Failed to send to {}: {}. Attempting reconnection...",
-                    addr, e
+    /// Send a frame to all connected peers.
+    pub async fn send_all(&self, data: &[u8], options: SendOptions) -> io::Result<()> {
+        // Snapshot the map so reconnecting below can take the lock again
+        let peers = self.connected_peers().await;
+        for addr in peers {
+            if let Err(e) = self.send_with(addr, data, options).await {
+                if e.kind() == io::ErrorKind::TimedOut {
+                    debug!(peer.addr = %addr, "broadcast deadline passed, message dropped");
+                    continue;
+                }
+                warn!(
+                    peer.addr = %addr,
+                    error.kind = ?e.kind(),
+                    error = %e,
+                    "send failed, attempting reconnection"
                 );
-                drop(stream); // Release lock to allow reconnect
-                self.reconnect_peer(*addr, 5).await?; // Try reconnecting
+                self.peers.lock().await.evict(addr);
+                self.reconnect_peer(addr, 5).await?; // Try reconnecting
             }
         }
         Ok(())
     }
+}
+
+fn banned(ban: &Ban) -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, format!("{} is banned: {}", ban.target, ban.reason))
+}
 
-    
+/// Decode a handshake frame, checking it is of the expected kind.
+fn expect<M: WireMessage>(frame: &[u8], kind: MessageKind) -> io::Result<M> {
+    let envelope = wire::decode(frame)?;
+    if envelope.message.kind != kind as i32 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("expected {:?}, got message kind {}", kind, envelope.message.kind),
+        ));
+    }
+    Ok(wire::decode_body::<M>(&envelope.message)?.message)
+}
+
+/// Forward every frame from a connection to the handler until it closes or
+/// shutdown is signalled, then drop the connection from the peer map unless a
+/// newer connection has taken its place.
+async fn read_frames(
+    peers: PeerMap,
+    mut reader: OwnedReadHalf,
+    addr: SocketAddr,
+    id: u64,
+    sender: FrameSender,
+    throttle: Throttle,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        let read = tokio::select! {
+            read = wire::read_frame(&mut reader) => read,
+            _ = shutdown.wait_for(|stopping| *stopping) => {
+                debug!("stopping reader for shutdown");
+                break;
+            }
+        };
+        match read {
+            Ok(Some(frame)) => {
+                trace!(bytes = frame.len(), "TCP frame received");
+                // Holding off the next read lets TCP push back on the sender
+                throttle.acquire(Flow::Download, addr, frame.len()).await;
+                if sender.send((addr, frame)).await.is_err() {
+                    warn!("handler is gone, closing connection");
+                    break;
+                }
+            }
+            Ok(None) => {
+                debug!("connection closed by peer");
+                break;
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::ConnectionReset => {
+                debug!("connection reset by peer");
+                break;
+            }
+            Err(e) => {
+                warn!(error.kind = ?e.kind(), error = %e, "error reading from peer");
+                break;
+            }
+        }
+    }
+
+    let mut peers = peers.lock().await;
+    let peer_id = peers.streams.get(&addr).filter(|conn| conn.id == id).map(|conn| conn.remote.peer_id.clone());
+    if peers.remove(addr, id) {
+        throttle.unbind_addr(addr);
+        // Its dial and UDP addresses go once no connection to the peer is left
+        if let Some(peer_id) = peer_id.filter(|peer_id| !peer_id.is_empty() && !peers.by_peer.contains_key(peer_id)) {
+            throttle.unbind_peer(&peer_id);
+        }
+    }
+    debug!(peer.addr = %addr, "peer removed from connection map");
 }
@@ -0,0 +1,231 @@
+// throttle.rs
+//? Token-bucket bandwidth limits shared by the TCP and UDP transports
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use tokio::time::{sleep, Duration, Instant};
+use tracing::trace;
+
+/// Which way traffic is flowing, seen from this node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Flow {
+    Upload,
+    Download,
+}
+
+/// A sustained rate with the burst allowed on top of it, both in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    pub bytes_per_sec: u64,
+    pub burst: u64,
+}
+
+impl RateLimit {
+    /// A limit whose burst is one second's worth of traffic.
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self::with_burst(bytes_per_sec, bytes_per_sec)
+    }
+
+    pub fn with_burst(bytes_per_sec: u64, burst: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1),
+            burst: burst.max(1),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FlowStats {
+    pub bytes: u64,
+    pub throttled: u64,       // Transfers that had to wait for tokens
+    pub delayed: Duration,    // Total time spent waiting
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TrafficStats {
+    pub upload: FlowStats,
+    pub download: FlowStats,
+}
+
+impl TrafficStats {
+    fn flow(&mut self, flow: Flow) -> &mut FlowStats {
+        match flow {
+            Flow::Upload => &mut self.upload,
+            Flow::Download => &mut self.download,
+        }
+    }
+}
+
+/// Snapshot of what throttling has done so far.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ThrottleStats {
+    pub total: TrafficStats,
+    pub peers: HashMap<String, TrafficStats>,
+}
+
+/// Tokens may go negative: a transfer larger than what is left is let through
+/// after a wait long enough to pay off the debt, so large frames are never
+/// stuck behind a burst size smaller than themselves.
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst as f64,
+            updated: now,
+        }
+    }
+
+    fn set_limit(&mut self, limit: RateLimit) {
+        self.limit = limit;
+        self.tokens = self.tokens.min(limit.burst as f64);
+    }
+
+    /// Take `bytes` tokens and return how long to wait before using them.
+    fn reserve(&mut self, bytes: usize, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.updated = now;
+        let rate = self.limit.bytes_per_sec as f64;
+        self.tokens = (self.tokens + elapsed * rate).min(self.limit.burst as f64);
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+#[derive(Default)]
+struct Buckets {
+    upload: Option<TokenBucket>,
+    download: Option<TokenBucket>,
+}
+
+impl Buckets {
+    fn bucket(&mut self, flow: Flow) -> &mut Option<TokenBucket> {
+        match flow {
+            Flow::Upload => &mut self.upload,
+            Flow::Download => &mut self.download,
+        }
+    }
+
+    fn set_limit(&mut self, flow: Flow, limit: Option<RateLimit>) {
+        let bucket = self.bucket(flow);
+        match (bucket.as_mut(), limit) {
+            (Some(existing), Some(limit)) => existing.set_limit(limit),
+            (_, limit) => *bucket = limit.map(|limit| TokenBucket::new(limit, Instant::now())),
+        }
+    }
+
+    fn reserve(&mut self, flow: Flow, bytes: usize, now: Instant) -> Duration {
+        self.bucket(flow)
+            .as_mut()
+            .map_or(Duration::ZERO, |bucket| bucket.reserve(bytes, now))
+    }
+}
+
+#[derive(Default)]
+struct State {
+    global: Buckets,
+    peers: HashMap<String, Buckets>,
+    addrs: HashMap<SocketAddr, String>, // Which peer ID each socket address belongs to
+    stats: ThrottleStats,
+}
+
+/// Bandwidth limits for the whole node and for individual peers. Limits can be
+/// changed at any time; a transfer waits for both the global and its peer's bucket.
+#[derive(Clone, Default)]
+pub struct Throttle {
+    state: Arc<Mutex<State>>,
+}
+
+impl Throttle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit traffic to and from all peers combined. `None` removes the limit.
+    pub fn set_global_limit(&self, flow: Flow, limit: Option<RateLimit>) {
+        self.state.lock().unwrap().global.set_limit(flow, limit);
+    }
+
+    /// Limit traffic to and from one peer. `None` removes the limit.
+    pub fn set_peer_limit(&self, peer_id: &str, flow: Flow, limit: Option<RateLimit>) {
+        let mut state = self.state.lock().unwrap();
+        let buckets = state.peers.entry(peer_id.to_string()).or_default();
+        buckets.set_limit(flow, limit);
+        if buckets.upload.is_none() && buckets.download.is_none() {
+            state.peers.remove(peer_id);
+        }
+    }
+
+    /// Attribute traffic on a socket address to a peer ID.
+    pub fn bind_addr(&self, addr: SocketAddr, peer_id: &str) {
+        if peer_id.is_empty() {
+            return;
+        }
+        self.state.lock().unwrap().addrs.insert(addr, peer_id.to_string());
+    }
+
+    /// Stop attributing traffic on `addr`. A peer left with no address bound
+    /// to it loses its traffic totals as well.
+    pub fn unbind_addr(&self, addr: SocketAddr) {
+        let mut state = self.state.lock().unwrap();
+        let Some(peer_id) = state.addrs.remove(&addr) else {
+            return;
+        };
+        if !state.addrs.values().any(|bound| *bound == peer_id) {
+            state.stats.peers.remove(&peer_id);
+        }
+    }
+
+    /// Stop attributing traffic on any address to `peer_id`, and drop its totals.
+    pub fn unbind_peer(&self, peer_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.addrs.retain(|_, bound| bound != peer_id);
+        state.stats.peers.remove(peer_id);
+    }
+
+    /// Wait until `bytes` may be transferred to or from `addr`.
+    pub async fn acquire(&self, flow: Flow, addr: SocketAddr, bytes: usize) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let state = &mut *state;
+            let now = Instant::now();
+            let mut wait = state.global.reserve(flow, bytes, now);
+            let peer_id = state.addrs.get(&addr).cloned();
+            if let Some(buckets) = peer_id.as_ref().and_then(|peer_id| state.peers.get_mut(peer_id)) {
+                wait = wait.max(buckets.reserve(flow, bytes, now));
+            }
+
+            let record = |stats: &mut FlowStats| {
+                stats.bytes += bytes as u64;
+                if !wait.is_zero() {
+                    stats.throttled += 1;
+                    stats.delayed += wait;
+                }
+            };
+            record(state.stats.total.flow(flow));
+            if let Some(peer_id) = peer_id {
+                record(state.stats.peers.entry(peer_id).or_default().flow(flow));
+            }
+            wait
+        };
+
+        if !wait.is_zero() {
+            trace!(peer.addr = %addr, ?flow, bytes, wait = ?wait, "throttled");
+            sleep(wait).await;
+        }
+    }
+
+    pub fn stats(&self) -> ThrottleStats {
+        self.state.lock().unwrap().stats.clone()
+    }
+}
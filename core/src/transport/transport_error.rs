@@ -0,0 +1,113 @@
+// transport_error.rs
+use std::fmt;
+use std::io;
+
+use crate::record::RecordError;
+
+#[derive(Debug)]
+pub enum TransportError {
+    UnknownPeer(String),         // No record or address known for the peer ID
+    Unreachable(String, String), // Peer ID and the last error seen while dialing
+    InvalidEnvelope(String),
+    UnknownSender(String),       // Envelope from a peer with no cached public key
+    BadSignature(String),
+    Replay(String),
+    ShuttingDown,                // Sends are refused once shutdown has begun
+    Expired,                     // The send deadline passed before the message was written
+    BlobNotFound(String),        // Hex ID of a blob the peer does not serve
+    Integrity(String),           // Received content does not match its hash
+    UnsupportedProtocol(String), // The peer accepted none of the proposed protocols
+    Banned(String),              // Peer ID or address refused by its score or the ban list
+    IdentityMismatch(String, String), // Peer ID expected and the identity found instead
+    IO(String),
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportError::UnknownPeer(peer_id) => write!(f, "Unknown peer: {}", peer_id),
+            TransportError::Unreachable(peer_id, reason) => {
+                write!(f, "Peer {} is unreachable: {}", peer_id, reason)
+            }
+            TransportError::InvalidEnvelope(msg) => write!(f, "Invalid envelope: {}", msg),
+            TransportError::UnknownSender(peer_id) => {
+                write!(f, "No public key known for sender {}", peer_id)
+            }
+            TransportError::BadSignature(msg) => write!(f, "Bad signature: {}", msg),
+            TransportError::Replay(msg) => write!(f, "Replayed message: {}", msg),
+            TransportError::ShuttingDown => write!(f, "Transport is shutting down"),
+            TransportError::Expired => write!(f, "Send deadline passed"),
+            TransportError::BlobNotFound(blob_id) => write!(f, "Blob not found: {}", blob_id),
+            TransportError::Integrity(msg) => write!(f, "Integrity check failed: {}", msg),
+            TransportError::UnsupportedProtocol(protocols) => {
+                write!(f, "Peer supports none of the protocols: {}", protocols)
+            }
+            TransportError::Banned(peer) => write!(f, "Peer is banned: {}", peer),
+            TransportError::IdentityMismatch(expected, found) => {
+                write!(f, "Expected peer {} but found {}", expected, found)
+            }
+            TransportError::IO(msg) => write!(f, "I/O Error: {}", msg),
+        }
+    }
+}
+
+impl TransportError {
+    /// Short machine-readable name, used as the `error.kind` field in logs.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TransportError::UnknownPeer(_) => "unknown_peer",
+            TransportError::Unreachable(..) => "unreachable",
+            TransportError::InvalidEnvelope(_) => "invalid_envelope",
+            TransportError::UnknownSender(_) => "unknown_sender",
+            TransportError::BadSignature(_) => "bad_signature",
+            TransportError::Replay(_) => "replay",
+            TransportError::ShuttingDown => "shutting_down",
+            TransportError::Expired => "expired",
+            TransportError::BlobNotFound(_) => "blob_not_found",
+            TransportError::Integrity(_) => "integrity",
+            TransportError::UnsupportedProtocol(_) => "unsupported_protocol",
+            TransportError::Banned(_) => "banned",
+            TransportError::IdentityMismatch(..) => "identity_mismatch",
+            TransportError::IO(_) => "io",
+        }
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+impl From<io::Error> for TransportError {
+    fn from(e: io::Error) -> Self {
+        TransportError::IO(e.to_string())
+    }
+}
+
+impl From<TransportError> for io::Error {
+    fn from(e: TransportError) -> Self {
+        let kind = match e {
+            TransportError::UnknownPeer(_) => io::ErrorKind::NotFound,
+            TransportError::Unreachable(..) => io::ErrorKind::NotConnected,
+            TransportError::InvalidEnvelope(_) => io::ErrorKind::InvalidData,
+            TransportError::UnknownSender(_)
+            | TransportError::BadSignature(_)
+            | TransportError::Replay(_) => io::ErrorKind::PermissionDenied,
+            TransportError::ShuttingDown => io::ErrorKind::NotConnected,
+            TransportError::Expired => io::ErrorKind::TimedOut,
+            TransportError::BlobNotFound(_) => io::ErrorKind::NotFound,
+            TransportError::Integrity(_) => io::ErrorKind::InvalidData,
+            TransportError::UnsupportedProtocol(_) => io::ErrorKind::Unsupported,
+            TransportError::Banned(_) => io::ErrorKind::PermissionDenied,
+            TransportError::IdentityMismatch(..) => io::ErrorKind::PermissionDenied,
+            TransportError::IO(_) => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, e.to_string())
+    }
+}
+
+impl From<RecordError> for TransportError {
+    fn from(e: RecordError) -> Self {
+        match e {
+            RecordError::BadSignature(msg) | RecordError::Signing(msg) => TransportError::BadSignature(msg),
+            other => TransportError::InvalidEnvelope(other.to_string()),
+        }
+    }
+}
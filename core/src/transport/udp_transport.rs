@@ -1,11 +1,15 @@
 #[allow(dead_code)]
 // udp_transport.rs
 use tokio::net::UdpSocket;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::{mpsc, watch, Mutex};
 use std::sync::Arc;
 use std::net::SocketAddr;
 use std::collections::HashSet;
 use std::io;
+use tracing::{debug, error, info, trace, warn};
+
+use super::throttle::{Flow, Throttle};
+use crate::record::BanList;
 
 
 
@@ -13,55 +17,72 @@ use std::io;
 pub struct UdpTransport {
     peers: Arc<Mutex<HashSet<SocketAddr>>>, // Manage known peers
     socket: Arc<UdpSocket>,                 // UDP socket for communication
+    throttle: Throttle,                     // Bandwidth limits, shared with TCP
+    bans: BanList,                          // Datagrams from banned addresses are dropped
 }
 
 impl UdpTransport {
     /// Creates a new UdpTransport instance.
-    pub async fn new(local_addr: SocketAddr) -> io::Result<Self> {
+    pub async fn new(local_addr: SocketAddr, throttle: Throttle, bans: BanList) -> io::Result<Self> {
         let socket = UdpSocket::bind(local_addr).await?;
-        println!("UDP socket bound to {}", local_addr);
+        info!(local.addr = %local_addr, "UDP socket bound");
 
         Ok(UdpTransport {
             peers: Arc::new(Mutex::new(HashSet::new())),
             socket: Arc::new(socket),
+            throttle,
+            bans,
         })
     }
 
-    /// Listen for incoming messages.
-    pub async fn listen(&self, sender: mpsc::Sender<(SocketAddr, Vec<u8>)>) -> io::Result<()> {
-        let socket = self.socket.clone();
-    
-        tokio::spawn(async move {
-            let mut buf = vec![0; 1024];
-            loop {
-                match socket.recv_from(&mut buf).await {
-                    Ok((len, addr)) => {
-                        let message = buf[..len].to_vec();
-                        println!("UDP listener received message from {}: {}", addr, String::from_utf8_lossy(&message));
-    
-                        // Forward the message to the shared channel
-                        if let Err(e) = sender.send((addr, message)).await {
-                            eprintln!("Failed to forward message from {}: {}", addr, e);
-                        }
+    /// Listen for incoming messages until shutdown is signalled.
+    pub async fn listen(
+        &self,
+        sender: mpsc::Sender<(SocketAddr, Vec<u8>)>,
+        mut shutdown_rx: watch::Receiver<bool>,
+    ) -> io::Result<()> {
+        let mut buf = vec![0; super::READ_BUFFER_SIZE];
+        loop {
+            let received = tokio::select! {
+                received = self.socket.recv_from(&mut buf) => received,
+                _ = shutdown_rx.wait_for(|stopping| *stopping) => {
+                    info!("shutting down UDP listener");
+                    return Ok(());
+                }
+            };
+            match received {
+                Ok((len, addr)) if self.bans.ip_ban(addr.ip()).is_some() => {
+                    trace!(peer.addr = %addr, bytes = len, "dropped datagram from banned address");
+                }
+                Ok((len, addr)) => {
+                    let message = buf[..len].to_vec();
+                    trace!(peer.addr = %addr, bytes = len, "UDP datagram received");
+                    self.throttle.acquire(Flow::Download, addr, len).await;
+
+                    // Forward the message to the shared channel
+                    if sender.send((addr, message)).await.is_err() {
+                        warn!(peer.addr = %addr, "failed to forward UDP message to handler");
                     }
-                    Err(e) => eprintln!("Error receiving UDP message: {}", e),
                 }
+                Err(e) => error!(error.kind = ?e.kind(), error = %e, "error receiving UDP message"),
             }
-        });
-    
-        Ok(())
+        }
     }
     /// Send data to a specific peer.
     pub async fn send(&self, peer_addr: SocketAddr, data: &[u8]) -> io::Result<usize> {
-        self.socket.send_to(data, peer_addr).await
+        self.throttle.acquire(Flow::Upload, peer_addr, data.len()).await;
+        let sent = self.socket.send_to(data, peer_addr).await?;
+        trace!(peer.addr = %peer_addr, bytes = sent, "UDP datagram sent");
+        Ok(sent)
     }
 
     /// Broadcast data to all known peers.
     pub async fn broadcast(&self, data: &[u8]) -> io::Result<()> {
         let peers = self.peers.lock().await;
         for &peer in peers.iter() {
+            self.throttle.acquire(Flow::Upload, peer, data.len()).await;
             if let Err(e) = self.socket.send_to(data, peer).await {
-                eprintln!("Failed to send to {}: {}", peer, e);
+                warn!(peer.addr = %peer, error.kind = ?e.kind(), error = %e, "UDP broadcast send failed");
             }
         }
         Ok(())
@@ -70,13 +91,13 @@ impl UdpTransport {
     /// Remove a peer from the known peers list.
     pub async fn remove_peer(&self, peer_addr: SocketAddr) {
         self.peers.lock().await.remove(&peer_addr);
-        println!("Removed peer: {}", peer_addr);
+        debug!(peer.addr = %peer_addr, "removed UDP peer");
     }
 
     /// Clear all known peers.
     pub async fn clear_peers(&self) {
         self.peers.lock().await.clear();
-        println!("Cleared all peers.");
+        debug!("cleared all UDP peers");
     }
     
 }
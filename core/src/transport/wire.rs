@@ -0,0 +1,208 @@
+// wire.rs
+//? Encoding and framing for the protobuf wire schema in protocols/transport.proto
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use prost::encoding::{decode_key, skip_field, DecodeContext};
+use prost::{DecodeError, Message};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::proto::{
+    BlobChunk, BlobManifest, BlobRequest, Data, Goodbye, Handshake, HandshakeProof, MessageKind, PeerExchange,
+    PeerInfo, Ping, Pong, SignedEnvelope, SignedPeerRecord, StreamFrame, TransportEnvelope,
+};
+
+/// Version written into every TransportEnvelope.
+pub const WIRE_VERSION: u32 = 1;
+
+/// Largest frame accepted from a TCP stream.
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// A protobuf message from the wire schema, together with the field tags this
+/// version of the schema knows about.
+pub trait WireMessage: Message + Default {
+    const KNOWN_TAGS: &'static [u32];
+}
+
+impl WireMessage for TransportEnvelope {
+    const KNOWN_TAGS: &'static [u32] = &[1, 2, 3];
+}
+
+impl WireMessage for Handshake {
+    const KNOWN_TAGS: &'static [u32] = &[1, 2, 3, 4, 5, 6];
+}
+
+impl WireMessage for HandshakeProof {
+    const KNOWN_TAGS: &'static [u32] = &[1];
+}
+
+impl WireMessage for Ping {
+    const KNOWN_TAGS: &'static [u32] = &[1, 2];
+}
+
+impl WireMessage for Pong {
+    const KNOWN_TAGS: &'static [u32] = &[1, 2];
+}
+
+impl WireMessage for PeerInfo {
+    const KNOWN_TAGS: &'static [u32] = &[1, 2, 3, 4, 5];
+}
+
+impl WireMessage for PeerExchange {
+    const KNOWN_TAGS: &'static [u32] = &[1];
+}
+
+impl WireMessage for Data {
+    const KNOWN_TAGS: &'static [u32] = &[1];
+}
+
+impl WireMessage for SignedEnvelope {
+    const KNOWN_TAGS: &'static [u32] = &[1, 2, 3, 4, 5];
+}
+
+impl WireMessage for SignedPeerRecord {
+    const KNOWN_TAGS: &'static [u32] = &[1, 2, 3, 4, 5, 6];
+}
+
+impl WireMessage for Goodbye {
+    const KNOWN_TAGS: &'static [u32] = &[1, 2];
+}
+
+impl WireMessage for BlobManifest {
+    const KNOWN_TAGS: &'static [u32] = &[1, 2, 3, 4, 5, 6];
+}
+
+impl WireMessage for BlobRequest {
+    const KNOWN_TAGS: &'static [u32] = &[1, 2, 3];
+}
+
+impl WireMessage for BlobChunk {
+    const KNOWN_TAGS: &'static [u32] = &[1, 2, 3, 4];
+}
+
+impl WireMessage for StreamFrame {
+    const KNOWN_TAGS: &'static [u32] = &[1, 2, 3, 4, 5];
+}
+
+/// A decoded message plus the raw bytes of every field this node does not
+/// understand. Re-encoding writes those fields back out unchanged, so a message
+/// from a newer node survives being relayed through an older one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Preserved<M> {
+    pub message: M,
+    unknown: Vec<u8>,
+}
+
+impl<M: WireMessage> Preserved<M> {
+    pub fn new(message: M) -> Self {
+        Self {
+            message,
+            unknown: Vec::new(),
+        }
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let message = M::decode(bytes)?;
+        let unknown = unknown_fields(bytes, M::KNOWN_TAGS)?;
+        Ok(Self { message, unknown })
+    }
+
+    pub fn encode_to_vec(&self) -> Vec<u8> {
+        // Field order does not matter in protobuf, so unknown fields can
+        // simply follow the known ones.
+        let mut bytes = self.message.encode_to_vec();
+        bytes.extend_from_slice(&self.unknown);
+        bytes
+    }
+
+    /// Raw encoding of the fields that were not recognised.
+    pub fn unknown_fields(&self) -> &[u8] {
+        &self.unknown
+    }
+}
+
+/// Collect the raw encoding of every field whose tag is not in `known`.
+fn unknown_fields(bytes: &[u8], known: &[u32]) -> Result<Vec<u8>, DecodeError> {
+    let mut unknown = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let start = rest;
+        let (tag, wire_type) = decode_key(&mut rest)?;
+        skip_field(wire_type, tag, &mut rest, DecodeContext::default())?;
+        if !known.contains(&tag) {
+            unknown.extend_from_slice(&start[..start.len() - rest.len()]);
+        }
+    }
+    Ok(unknown)
+}
+
+/// Encode a message of the given kind into a TransportEnvelope.
+pub fn encode<M: Message>(kind: MessageKind, body: &M) -> Vec<u8> {
+    TransportEnvelope {
+        version: WIRE_VERSION,
+        kind: kind as i32,
+        body: body.encode_to_vec(),
+    }
+    .encode_to_vec()
+}
+
+/// Decode a TransportEnvelope. Messages from newer versions are accepted, since
+/// the schema only ever grows; a missing version means the bytes are not ours.
+pub fn decode(bytes: &[u8]) -> io::Result<Preserved<TransportEnvelope>> {
+    let envelope = Preserved::<TransportEnvelope>::decode(bytes).map_err(invalid_data)?;
+    if envelope.message.version == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "transport envelope without a version",
+        ));
+    }
+    Ok(envelope)
+}
+
+/// Decode the body of an envelope as the message type its kind names.
+pub fn decode_body<M: WireMessage>(envelope: &TransportEnvelope) -> io::Result<Preserved<M>> {
+    Preserved::decode(&envelope.body).map_err(invalid_data)
+}
+
+/// Write one length-prefixed frame to a stream.
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, frame: &[u8]) -> io::Result<()> {
+    if frame.len() > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("frame of {} bytes exceeds the {} byte limit", frame.len(), MAX_FRAME_SIZE),
+        ));
+    }
+    writer.write_u32(frame.len() as u32).await?;
+    writer.write_all(frame).await?;
+    writer.flush().await
+}
+
+/// Read one length-prefixed frame from a stream. Returns `None` on a clean EOF
+/// between frames.
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let len = match reader.read_u32().await {
+        Ok(len) => len as usize,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes exceeds the {} byte limit", len, MAX_FRAME_SIZE),
+        ));
+    }
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame).await?;
+    Ok(Some(frame))
+}
+
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn invalid_data(e: DecodeError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
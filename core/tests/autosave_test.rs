@@ -0,0 +1,109 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::active_peer;
+    use Nautilus_Core::record::{
+        AutosaveConfig, MemoryStore, PeerEvent, PeerManagement, PeerRecord, PeerStore,
+    };
+    use std::collections::HashMap;
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::watch;
+
+    /// A memory store that counts the writes reaching it.
+    #[derive(Default)]
+    struct CountingStore {
+        inner: MemoryStore,
+        writes: AtomicUsize,
+    }
+
+    impl PeerStore for CountingStore {
+        fn load(&self) -> io::Result<HashMap<String, PeerRecord>> {
+            self.inner.load()
+        }
+
+        fn upsert(&self, key: &str, peer: &PeerRecord) -> io::Result<()> {
+            self.writes.fetch_add(1, Ordering::SeqCst);
+            self.inner.upsert(key, peer)
+        }
+
+        fn remove(&self, key: &str) -> io::Result<()> {
+            self.writes.fetch_add(1, Ordering::SeqCst);
+            self.inner.remove(key)
+        }
+
+        fn save_all(&self, peers: &HashMap<String, PeerRecord>) -> io::Result<()> {
+            self.writes.fetch_add(1, Ordering::SeqCst);
+            self.inner.save_all(peers)
+        }
+    }
+
+    #[tokio::test]
+    async fn bursts_are_written_once_and_flushed_on_shutdown() {
+        let store = Arc::new(CountingStore::default());
+        let manager = PeerManagement::with_store(store.clone()).with_autosave(AutosaveConfig {
+            debounce: Duration::from_millis(100),
+            interval: Duration::from_secs(5),
+        });
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let autosave = tokio::spawn(manager.clone().run_autosave(shutdown_rx));
+
+        for i in 0..5 {
+            manager.add_or_update_peer(active_peer(&format!("peer{}", i), 8000 + i)).await;
+        }
+        for _ in 0..20 {
+            for i in 0..5 {
+                manager.report(&format!("peer{}", i), PeerEvent::UsefulMessage).await;
+            }
+        }
+        assert_eq!(store.writes.load(Ordering::SeqCst), 0, "nothing is written during the burst");
+        assert_eq!(manager.dirty_count(), 5);
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(store.writes.load(Ordering::SeqCst), 5, "one write per changed peer");
+        assert_eq!(manager.dirty_count(), 0);
+        let stored = store.load().unwrap();
+        assert_eq!(stored.len(), 5);
+        assert!(stored["peer0"].reputation.score > 0.0);
+
+        // Shutdown writes what the debounce was still holding back
+        manager.remove_peer("peer0").await;
+        shutdown_tx.send(true).unwrap();
+        autosave.await.unwrap();
+        assert!(!store.load().unwrap().contains_key("peer0"));
+        assert_eq!(manager.dirty_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn changes_survive_a_crash_with_a_json_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peers.json").to_string_lossy().into_owned();
+        let config = AutosaveConfig {
+            debounce: Duration::from_millis(50),
+            interval: Duration::from_millis(200),
+        };
+
+        let manager = PeerManagement::new(path.clone()).with_autosave(config);
+        let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+        let autosave = tokio::spawn(manager.clone().run_autosave(shutdown_rx));
+        // Changes keep coming faster than the debounce, so the interval has to flush
+        for i in 0..10 {
+            manager.add_or_update_peer(active_peer(&format!("peer{}", i), 9000 + i)).await;
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            if i == 8 {
+                assert!(std::path::Path::new(&path).exists());
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // No save_to_file: the process "crashes" here
+        autosave.abort();
+        drop(manager);
+        let reloaded = PeerManagement::new(path);
+        reloaded.load_from_file().await.unwrap();
+        assert_eq!(reloaded.get_all_peers().await.len(), 10);
+    }
+}
@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, SocketAddr};
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use tokio::sync::watch;
+    use Nautilus_Core::record::{Ban, BanList, BanTarget};
+    use Nautilus_Core::transport::{NautilusTransport, TransportError};
+
+    async fn start_node(peer_id: &str, bans: BanList) -> (NautilusTransport, SocketAddr, watch::Sender<bool>) {
+        let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let node = NautilusTransport::with_ban_list(port, bans).await.unwrap();
+        node.tcp.set_identity(peer_id, "");
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let listening = node.clone();
+        tokio::spawn(async move { listening.start_listeners(shutdown_rx).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        (node, format!("127.0.0.1:{}", port).parse().unwrap(), shutdown_tx)
+    }
+
+    #[tokio::test]
+    async fn test_bans_match_expire_and_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bans.json");
+        let target = |s: &str| s.parse::<BanTarget>().unwrap();
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        assert!(matches!(target("10.1.2.3/8"), BanTarget::Subnet(subnet) if subnet.to_string() == "10.0.0.0/8"));
+        assert_eq!(target("10.1.2.3"), BanTarget::Ip(ip("10.1.2.3")));
+        assert_eq!(target("mallory"), BanTarget::PeerId("mallory".to_string()));
+        assert!("10.0.0.0/33".parse::<BanTarget>().is_err());
+
+        let bans = BanList::open(&path).unwrap();
+        bans.ban(Ban::new(target("mallory"), "spam", None)).await.unwrap();
+        bans.ban(Ban::new(target("10.1.2.3"), "flood", Some(Duration::from_secs(3600)))).await.unwrap();
+        bans.ban(Ban::new(target("192.168.0.0/16"), "scanner", Some(Duration::from_secs(3600)))).await.unwrap();
+        bans.ban(Ban::new(target("trent"), "served", Some(Duration::ZERO))).await.unwrap();
+
+        assert_eq!(bans.peer_ban("mallory").unwrap().reason, "spam");
+        assert!(bans.peer_ban("trent").is_none(), "expired bans stop applying");
+        assert!(bans.ip_ban(ip("10.1.2.3")).is_some());
+        assert!(bans.ip_ban(ip("::ffff:10.1.2.3")).is_some());
+        assert_eq!(bans.ip_ban(ip("192.168.44.1")).unwrap().reason, "scanner");
+        assert!(bans.ip_ban(ip("192.169.0.1")).is_none());
+        assert_eq!(bans.list().len(), 3);
+
+        // A restart keeps what is still in force
+        let reopened = BanList::open(&path).unwrap();
+        assert_eq!(reopened.list().len(), 3);
+        assert!(reopened.lift(&target("mallory")).await.unwrap().is_some());
+        assert!(reopened.lift(&target("mallory")).await.unwrap().is_none());
+        assert!(BanList::open(&path).unwrap().peer_ban("mallory").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_banned_peers_are_refused_on_dial_and_accept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bans.json");
+        let (node, node_addr, _node_stop) = start_node("victor", BanList::open(&path).unwrap()).await;
+        let (mallory, mallory_addr, _mallory_stop) = start_node("mallory", BanList::new()).await;
+        let banned = BanTarget::PeerId("mallory".to_string());
+
+        node.ban(banned.clone(), "spam", None).await.unwrap();
+        assert_eq!(node.bans().len(), 1);
+        assert_eq!(BanList::open(&path).unwrap().list().len(), 1, "bans go to the list's own file");
+        let dial = node.tcp.connect(mallory_addr).await;
+        assert_eq!(dial.unwrap_err().kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(!node.tcp.is_connected(mallory_addr).await);
+        assert!(mallory.tcp.connect(node_addr).await.is_err(), "inbound handshake is refused");
+        assert!(matches!(node.connect_to_peer("mallory").await, Err(TransportError::Banned(_))));
+
+        // Lifted, the peer can connect; banned again, the connection is closed
+        assert!(node.lift_ban(&banned).await.unwrap().is_some());
+        node.tcp.connect(mallory_addr).await.unwrap();
+        assert!(node.tcp.is_connected(mallory_addr).await);
+        node.ban(banned.clone(), "spam again", Some(Duration::from_secs(60))).await.unwrap();
+        assert!(!node.tcp.is_connected(mallory_addr).await);
+        node.lift_ban(&banned).await.unwrap();
+    }
+}
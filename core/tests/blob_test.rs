@@ -0,0 +1,108 @@
+#[cfg(test)]
+mod tests {
+    use Nautilus_Core::record::PeerRecord;
+    use Nautilus_Core::transport::{BlobEvent, BlobId, NautilusTransport, TransportError};
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    /// Start a node with the given peer ID and return it with its address.
+    async fn node(peer_id: &str) -> (NautilusTransport, SocketAddr) {
+        let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let node = NautilusTransport::new(port).await.unwrap();
+        node.tcp.set_identity(peer_id, "");
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let listening = node.clone();
+        tokio::spawn(async move {
+            let _shutdown_tx = shutdown_tx;
+            listening.start_listeners(shutdown_rx).await
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        (node, format!("127.0.0.1:{}", port).parse().unwrap())
+    }
+
+    async fn know(node: &NautilusTransport, peer_id: &str, addr: SocketAddr) {
+        node.peer_manager()
+            .add_or_update_peer(PeerRecord {
+                peer_id: Some(peer_id.to_string()),
+                ..PeerRecord::new(addr)
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_fetch_blob_in_verified_chunks() {
+        let (server, server_addr) = node("blob-server").await;
+        let (client, _) = node("blob-client").await;
+        know(&client, "blob-server", server_addr).await;
+
+        let data: Vec<u8> = (0..1_100_000u32).map(|i| (i % 251) as u8).collect();
+        let published = server.publish_blob(data.clone(), "sample.bin");
+        let blob_id = BlobId::try_from(published.blob_id.as_slice()).unwrap();
+        assert_eq!(published.chunk_hashes.len(), 5);
+
+        let mut events = client.subscribe_blobs();
+        let manifest = client.fetch_manifest("blob-server", &blob_id).await.unwrap();
+        assert_eq!(manifest, published);
+        let fetched = client.fetch_blob_with("blob-server", &manifest, 2).await.unwrap();
+        assert_eq!(fetched, data);
+
+        let mut progress = 0;
+        loop {
+            match events.recv().await.unwrap() {
+                BlobEvent::Progress { chunks_total, bytes_total, .. } => {
+                    assert_eq!((chunks_total, bytes_total), (5, data.len() as u64));
+                    progress += 1;
+                }
+                BlobEvent::Completed { blob_id: done } => {
+                    assert_eq!(done, blob_id);
+                    break;
+                }
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+        assert_eq!(progress, 5);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_blob_and_advertisement() {
+        let (server, server_addr) = node("blob-server-2").await;
+        let (client, client_addr) = node("blob-client-2").await;
+        know(&client, "blob-server-2", server_addr).await;
+        know(&server, "blob-client-2", client_addr).await;
+
+        let result = client.fetch_manifest("blob-server-2", &[7; 32]).await;
+        assert!(matches!(result, Err(TransportError::BlobNotFound(_))));
+
+        let mut events = client.subscribe_blobs();
+        let published = server.publish_blob(b"small blob".to_vec(), "note.txt");
+        let blob_id = BlobId::try_from(published.blob_id.as_slice()).unwrap();
+        server.advertise_blob("blob-client-2", &blob_id).await.unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv()).await.unwrap().unwrap();
+        let BlobEvent::Advertised { manifest, .. } = event else { panic!("expected an advertisement") };
+        assert_eq!(manifest.name, "note.txt");
+        assert_eq!(client.fetch_blob("blob-server-2", &manifest).await.unwrap(), b"small blob");
+    }
+
+    #[tokio::test]
+    async fn test_missing_chunk_is_retried() {
+        let (server, server_addr) = node("blob-server-3").await;
+        let (client, _) = node("blob-client-3").await;
+        know(&client, "blob-server-3", server_addr).await;
+
+        let data = b"republished shortly".to_vec();
+        let published = server.publish_blob(data.clone(), "later.txt");
+        let blob_id = BlobId::try_from(published.blob_id.as_slice()).unwrap();
+        let manifest = client.fetch_manifest("blob-server-3", &blob_id).await.unwrap();
+
+        // The chunk is gone when first asked for, and back before the retries run out
+        server.unpublish_blob(&blob_id);
+        let fetching = client.clone();
+        let fetch = tokio::spawn(async move { fetching.fetch_blob("blob-server-3", &manifest).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        server.publish_blob(data.clone(), "later.txt");
+
+        assert_eq!(fetch.await.unwrap().unwrap(), data);
+    }
+}
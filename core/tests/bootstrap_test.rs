@@ -0,0 +1,53 @@
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use tokio::sync::watch;
+    use Nautilus_Core::transport::{BootstrapConfig, BootstrapPeer, NautilusTransport, TransportError, BOOTSTRAP_TAG};
+
+    async fn start_node(peer_id: &str) -> (SocketAddr, watch::Sender<bool>) {
+        let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let node = NautilusTransport::new(port).await.unwrap();
+        node.tcp.set_identity(peer_id, "");
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        tokio::spawn(async move { node.start_listeners(shutdown_rx).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        (format!("127.0.0.1:{}", port).parse().unwrap(), shutdown_tx)
+    }
+
+    #[test]
+    fn test_parse_bootstrap_list() {
+        let config = BootstrapConfig::parse("alpha@127.0.0.1:9000, 127.0.0.1:9001\n# comment\n[::1]:9002 # v6").unwrap();
+        let entries: Vec<String> = config.peers.iter().map(BootstrapPeer::to_string).collect();
+        assert_eq!(entries, ["alpha@127.0.0.1:9000", "127.0.0.1:9001", "[::1]:9002"]);
+        assert!(BootstrapConfig::parse("alpha@nowhere").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_verifies_identities_and_retries() {
+        let (alpha, _alpha_stop) = start_node("alpha").await;
+        let (gamma, _gamma_stop) = start_node("gamma").await;
+        let offline = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+
+        let node = NautilusTransport::new(0).await.unwrap();
+        let list = format!("{} wrong@{} {}", alpha, gamma, offline);
+        let mut config = BootstrapConfig::parse(&list).unwrap();
+        config.min_connections = 1;
+        config.max_rounds = Some(1);
+        config.dial_timeout = Duration::from_secs(2);
+        assert_eq!(node.bootstrap(&config).await.unwrap(), 1);
+
+        let peer = node.peer_manager().get_peer("alpha").await.unwrap();
+        assert!(peer.tags.contains(BOOTSTRAP_TAG));
+        assert!(!node.tcp.is_connected(gamma).await);
+        assert!(node.peer_manager().get_peer("gamma").await.is_none());
+
+        // A second connection cannot be had, so the rounds run out
+        config.min_connections = 2;
+        config.max_rounds = Some(2);
+        config.initial_backoff = Duration::from_millis(10);
+        let result = node.bootstrap(&config).await;
+        assert!(matches!(result, Err(TransportError::Unreachable(..))));
+    }
+}
@@ -0,0 +1,33 @@
+// common/mod.rs
+//? Peer records shared by the peer table tests
+// Each test binary compiles this separately and uses only part of it
+#![allow(dead_code)]
+
+use std::time::{Duration, SystemTime};
+use Nautilus_Core::record::PeerRecord;
+
+/// An inactive peer `id` at 127.0.0.1:`port`, with nothing else known.
+pub fn peer(id: &str, port: u16) -> PeerRecord {
+    PeerRecord {
+        peer_id: Some(id.to_string()),
+        ..PeerRecord::new(format!("127.0.0.1:{}", port).parse().unwrap())
+    }
+}
+
+/// An active peer `id` at 127.0.0.1:`port`.
+pub fn active_peer(id: &str, port: u16) -> PeerRecord {
+    PeerRecord {
+        is_active: true,
+        ..peer(id, port)
+    }
+}
+
+/// A peer `id` at `addr` last seen `silent_for` ago.
+pub fn seen_peer(id: &str, addr: &str, active: bool, silent_for: Duration) -> PeerRecord {
+    PeerRecord {
+        peer_id: Some(id.to_string()),
+        is_active: active,
+        last_seen: Some(SystemTime::now() - silent_for),
+        ..PeerRecord::new(addr.parse().unwrap())
+    }
+}
@@ -0,0 +1,66 @@
+#![cfg(feature = "identity_integration")]
+
+#[cfg(test)]
+mod tests {
+    use identity::Identity;
+    use std::time::Duration;
+    use Nautilus_Core::transport::{Envelope, EnvelopeSigner, EnvelopeVerifier, TransportError};
+
+    #[test]
+    fn test_seal_and_verify() {
+        let identity = Identity::new(None, None);
+        let signer = EnvelopeSigner::new(&identity);
+        let verifier = EnvelopeVerifier::default();
+
+        let envelope = signer.seal(b"hello").unwrap();
+        let decoded = Envelope::from_bytes(&envelope.to_bytes()).unwrap();
+
+        assert_eq!(decoded.sender_id, identity.get_peer_id());
+        assert_eq!(decoded.payload, b"hello");
+        verifier.verify(&decoded, signer.public_key()).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_tampered_payload() {
+        let identity = Identity::new(None, None);
+        let signer = EnvelopeSigner::new(&identity);
+        let verifier = EnvelopeVerifier::default();
+
+        let mut envelope = signer.seal(b"hello").unwrap();
+        envelope.payload = b"goodbye".to_vec();
+        let result = verifier.verify(&envelope, signer.public_key());
+        assert!(matches!(result, Err(TransportError::BadSignature(_))));
+    }
+
+    #[test]
+    fn test_rejects_replay_and_stale_messages() {
+        let identity = Identity::new(None, None);
+        let signer = EnvelopeSigner::new(&identity);
+        let verifier = EnvelopeVerifier::new(Duration::from_secs(5));
+
+        let envelope = signer.seal(b"hello").unwrap();
+        verifier.verify(&envelope, signer.public_key()).unwrap();
+        let replayed = verifier.verify(&envelope, signer.public_key());
+        assert!(matches!(replayed, Err(TransportError::Replay(_))));
+
+        // Re-sign an old timestamp so only the freshness check can fail
+        let mut stale = signer.seal(b"hello").unwrap();
+        stale.timestamp_ms -= 60_000;
+        stale.signature = identity.get_key_pair().sign(&stale.signing_bytes()).unwrap();
+        let result = verifier.verify(&stale, signer.public_key());
+        assert!(matches!(result, Err(TransportError::Replay(_))));
+    }
+
+    #[test]
+    fn test_forgets_senders_outside_the_window() {
+        let verifier = EnvelopeVerifier::new(Duration::from_millis(200));
+        let first = EnvelopeSigner::new(&Identity::new(None, None));
+        verifier.verify(&first.seal(b"hello").unwrap(), first.public_key()).unwrap();
+        assert_eq!(verifier.tracked_senders(), 1);
+
+        std::thread::sleep(Duration::from_millis(300));
+        let second = EnvelopeSigner::new(&Identity::new(None, None));
+        verifier.verify(&second.seal(b"hello").unwrap(), second.public_key()).unwrap();
+        assert_eq!(verifier.tracked_senders(), 1, "the quiet sender is dropped");
+    }
+}
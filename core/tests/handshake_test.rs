@@ -0,0 +1,109 @@
+#![cfg(feature = "identity_integration")]
+
+#[cfg(test)]
+mod tests {
+    use identity::Identity;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use tokio::net::{TcpListener, TcpStream, UdpSocket};
+    use Nautilus_Core::proto::{Handshake, HandshakeProof, MessageKind};
+    use Nautilus_Core::record::{Ban, BanList, BanTarget};
+    use Nautilus_Core::transport::{wire, NautilusTransport};
+
+    async fn start_node(identity: &Identity) -> (NautilusTransport, SocketAddr) {
+        start_node_with_bans(identity, BanList::new()).await
+    }
+
+    async fn start_node_with_bans(identity: &Identity, bans: BanList) -> (NautilusTransport, SocketAddr) {
+        let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let node = NautilusTransport::with_identity_and_ban_list(port, identity, bans).await.unwrap();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let listening = node.clone();
+        tokio::spawn(async move {
+            let _shutdown_tx = shutdown_tx;
+            listening.start_listeners(shutdown_rx).await
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        (node, format!("127.0.0.1:{}", port).parse().unwrap())
+    }
+
+    fn claim(identity: &Identity) -> Handshake {
+        Handshake {
+            peer_id: identity.get_peer_id().to_string(),
+            public_key: identity.get_key_pair().public_key.clone(),
+            nonce: vec![7; 32],
+            ..Default::default()
+        }
+    }
+
+    /// Send a handshake on a fresh connection and return the stream with the ack, if any.
+    async fn open(addr: SocketAddr, hello: &Handshake) -> (TcpStream, Option<Handshake>) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        wire::write_frame(&mut stream, &wire::encode(MessageKind::Handshake, hello)).await.unwrap();
+        let ack = match wire::read_frame(&mut stream).await {
+            Ok(Some(frame)) => Some(wire::decode_body::<Handshake>(&wire::decode(&frame).unwrap().message).unwrap().message),
+            _ => None,
+        };
+        (stream, ack)
+    }
+
+    async fn is_closed(stream: &mut TcpStream) -> bool {
+        let read = tokio::time::timeout(Duration::from_secs(5), wire::read_frame(stream)).await;
+        matches!(read, Ok(Ok(None)) | Ok(Err(_)))
+    }
+
+    #[tokio::test]
+    async fn test_handshakes_prove_keys_and_cannot_be_forged() {
+        let (alice, bob) = (Identity::new(None, None), Identity::new(None, None));
+        let (alice_node, alice_addr) = start_node(&alice).await;
+        let (bob_node, bob_addr) = start_node(&bob).await;
+        let bob_key = bob.get_key_pair().public_key.clone();
+
+        bob_node.connect(alice_addr).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let known = alice_node.peer_manager().get_peer(bob.get_peer_id()).await.unwrap();
+        assert_eq!(known.public_key.as_ref(), Some(&bob_key));
+        let bob_connection = alice_node.tcp.peer_connection(bob.get_peer_id()).await.unwrap();
+
+        // A handshake over UDP is dropped, whatever key it names
+        let forged = Handshake { public_key: "forged".into(), ..claim(&bob) };
+        let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        udp.send_to(&wire::encode(MessageKind::Handshake, &forged), alice_addr).await.unwrap();
+
+        // Without a key there is nothing to prove, so the claim is refused outright
+        let anonymous = Handshake { peer_id: "mallory".into(), ..Default::default() };
+        let (mut stream, ack) = open(alice_addr, &anonymous).await;
+        assert!(ack.is_none());
+        assert!(is_closed(&mut stream).await);
+
+        // Claiming Bob's identity, the best a forger has is Bob's signature from
+        // another handshake: here, his ack to a dial claiming to be Alice that
+        // carries the nonce Alice is waiting on. It is refused.
+        let (mut to_alice, ack) = open(alice_addr, &claim(&bob)).await;
+        let challenge = ack.unwrap().nonce;
+        let (_to_bob, bob_ack) = open(bob_addr, &Handshake { nonce: challenge, ..claim(&alice) }).await;
+        let relayed = HandshakeProof { signature: bob_ack.unwrap().signature };
+        wire::write_frame(&mut to_alice, &wire::encode(MessageKind::HandshakeProof, &relayed)).await.unwrap();
+        assert!(is_closed(&mut to_alice).await);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let known = alice_node.peer_manager().get_peer(bob.get_peer_id()).await.unwrap();
+        assert_eq!(known.public_key.as_ref(), Some(&bob_key), "Bob's key was never rebound");
+        assert_eq!(alice_node.tcp.peer_connection(bob.get_peer_id()).await, Some(bob_connection));
+    }
+
+    #[tokio::test]
+    async fn test_proven_identities_are_checked_against_the_ban_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bans.json");
+        let (alice, bob) = (Identity::new(None, None), Identity::new(None, None));
+        let bans = BanList::open(&path).unwrap();
+        let banned = BanTarget::PeerId(bob.get_peer_id().to_string());
+        bans.ban(Ban::new(banned, "spam", None)).await.unwrap();
+
+        let (alice_node, alice_addr) = start_node_with_bans(&alice, bans).await;
+        let (bob_node, _) = start_node(&bob).await;
+        assert_eq!(alice_node.bans().len(), 1);
+        assert!(bob_node.connect(alice_addr).await.is_err(), "the banned identity is refused");
+    }
+}
@@ -0,0 +1,56 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::seen_peer;
+    use Nautilus_Core::record::{
+        ExpiryReason, MaintenanceConfig, MemoryStore, PeerEvent, PeerExpired, PeerManagement,
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_maintenance_expires_and_caps_peers() {
+        let manager = PeerManagement::with_store(Arc::new(MemoryStore::new())).with_maintenance_config(
+            MaintenanceConfig {
+                inactive_after: Duration::from_secs(60),
+                remove_after: Duration::from_secs(3600),
+                max_peers: 2,
+                ..MaintenanceConfig::default()
+            },
+        );
+        let mut events = manager.subscribe_expiry();
+        manager.add_or_update_peer(seen_peer("fresh", "127.0.0.1:9001", true, Duration::ZERO)).await;
+        manager.add_or_update_peer(seen_peer("quiet", "127.0.0.1:9002", true, Duration::from_secs(120))).await;
+        manager.add_or_update_peer(seen_peer("gone", "127.0.0.1:9003", false, Duration::from_secs(7200))).await;
+        manager.add_or_update_peer(seen_peer("weak", "127.0.0.1:9004", false, Duration::ZERO)).await;
+        manager.add_or_update_peer(seen_peer("strong", "127.0.0.1:9005", false, Duration::ZERO)).await;
+        manager.report("weak", PeerEvent::DialFailed).await;
+        manager.report("strong", PeerEvent::Handshake).await;
+
+        let mut expired = manager.maintain().await;
+        expired.sort_by(|a, b| a.key.cmp(&b.key));
+        let expected = [
+            ("gone", ExpiryReason::Stale),
+            ("quiet", ExpiryReason::Inactive),
+            ("quiet", ExpiryReason::Evicted),
+            ("weak", ExpiryReason::Evicted),
+        ];
+        let expected: Vec<PeerExpired> = expected
+            .iter()
+            .map(|(key, reason)| PeerExpired { key: key.to_string(), reason: *reason })
+            .collect();
+        assert_eq!(expired, expected);
+
+        let mut remaining = manager.get_all_peers().await;
+        remaining.sort();
+        assert_eq!(remaining, ["fresh", "strong"]);
+        assert!(manager.get_peer("fresh").await.unwrap().history.first_seen.is_some());
+
+        let mut received = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            received.push(event);
+        }
+        assert_eq!(received.len(), expected.len());
+    }
+}
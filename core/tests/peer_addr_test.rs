@@ -0,0 +1,98 @@
+#[cfg(test)]
+mod tests {
+    use Nautilus_Core::record::{
+        AddrSource, AddrTransport, MemoryStore, PeerAddr, PeerManagement, PeerRecord,
+    };
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn record(peer_id: Option<&str>, addr: &str, addrs: Vec<PeerAddr>) -> PeerRecord {
+        PeerRecord {
+            addrs,
+            peer_id: peer_id.map(str::to_string),
+            ..PeerRecord::new(addr.parse().unwrap())
+        }
+    }
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_addresses_merge_into_one_record() {
+        let manager = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+        // Seen by address before its ID was known
+        manager.add_or_update_peer(record(None, "10.0.0.1:9000", Vec::new())).await;
+        manager
+            .add_or_update_peer(record(
+                Some("peer1"),
+                "10.0.0.1:9000",
+                vec![PeerAddr::tcp(addr("10.0.0.1:9000"), AddrSource::Dial)],
+            ))
+            .await;
+        manager
+            .add_or_update_peer(record(
+                Some("peer1"),
+                "10.0.0.2:9000",
+                vec![PeerAddr::tcp(addr("10.0.0.2:9000"), AddrSource::PeerExchange)],
+            ))
+            .await;
+        manager
+            .add_addr("peer1", PeerAddr::new(addr("10.0.0.3:9000"), AddrTransport::Udp, AddrSource::Observed))
+            .await;
+
+        assert_eq!(manager.get_all_peers().await, ["peer1"]);
+        let peer = manager.get_peer("peer1").await.unwrap();
+        assert_eq!(peer.addrs.len(), 3);
+        let dialed = peer.addrs.iter().find(|known| known.addr == addr("10.0.0.1:9000")).unwrap();
+        assert_eq!(dialed.source, AddrSource::Dial);
+        // Only TCP addresses are dialed, most trusted first
+        assert_eq!(manager.get_peer_addrs("peer1").await, [addr("10.0.0.1:9000"), addr("10.0.0.2:9000")]);
+    }
+
+    #[tokio::test]
+    async fn test_ranking_and_garbage_collection() {
+        let manager = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+        manager
+            .add_or_update_peer(record(
+                Some("peer1"),
+                "10.0.0.1:9000",
+                vec![
+                    PeerAddr::tcp(addr("10.0.0.1:9000"), AddrSource::Discovery),
+                    PeerAddr::tcp(addr("10.0.0.2:9000"), AddrSource::Discovery),
+                    PeerAddr::tcp(addr("10.0.0.3:9000"), AddrSource::Discovery).with_ttl(Some(Duration::ZERO)),
+                ],
+            ))
+            .await;
+        manager
+            .add_or_update_peer(record(
+                Some("peer2"),
+                "10.0.0.9:9000",
+                vec![PeerAddr::tcp(addr("10.0.0.9:9000"), AddrSource::Discovery).with_ttl(Some(Duration::ZERO))],
+            ))
+            .await;
+
+        manager.record_addr_failure("peer1", addr("10.0.0.1:9000")).await;
+        manager.record_addr_success("peer1", addr("10.0.0.2:9000")).await;
+        assert_eq!(manager.get_peer_addrs("peer1").await, [addr("10.0.0.2:9000"), addr("10.0.0.1:9000")]);
+
+        assert_eq!(manager.gc_addrs().await, 2);
+        let peer = manager.get_peer("peer1").await.unwrap();
+        assert_eq!(peer.addr, addr("10.0.0.2:9000"));
+        assert_eq!(peer.addrs.len(), 2);
+        assert!(manager.get_peer("peer2").await.is_none(), "peers without addresses are dropped");
+    }
+
+    #[tokio::test]
+    async fn test_udp_primary_is_not_listed_as_tcp() {
+        let manager = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+        let datagram = PeerAddr::new(addr("10.0.0.4:9000"), AddrTransport::Udp, AddrSource::Observed);
+        manager.add_or_update_peer(record(Some("peer1"), "10.0.0.4:9000", vec![datagram])).await;
+
+        let peer = manager.get_peer("peer1").await.unwrap();
+        assert_eq!(peer.addrs.len(), 1);
+        assert_eq!(peer.addrs[0].transport, AddrTransport::Udp);
+        assert!(manager.get_peer_addrs("peer1").await.is_empty());
+    }
+}
@@ -0,0 +1,56 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::active_peer;
+    use Nautilus_Core::record::{
+        MemoryStore, PeerChange, PeerEvent, PeerField, PeerManagement,
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::broadcast::error::RecvError;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn test_changes_are_typed_with_field_diffs() {
+        let manager = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+        let mut changes = manager.subscribe_changes();
+
+        manager.add_or_update_peer(active_peer("peer1", 9000)).await;
+        manager.add_or_update_peer(active_peer("peer1", 9001)).await;
+        manager.tag_peer("peer1", "relay").await;
+        manager.tag_peer("peer1", "relay").await; // No change, no event
+        manager.remove_peer("peer1").await;
+
+        assert!(matches!(changes.recv().await.unwrap(), PeerChange::Added { key, .. } if key == "peer1"));
+        match changes.recv().await.unwrap() {
+            PeerChange::Updated { peer, changed, .. } => {
+                assert_eq!(changed, [PeerField::Addr, PeerField::Addrs]);
+                assert_eq!(peer.addrs.len(), 2);
+            }
+            other => panic!("expected an update, got {:?}", other),
+        }
+        assert!(matches!(
+            changes.recv().await.unwrap(),
+            PeerChange::Updated { changed, .. } if changed == [PeerField::Tags]
+        ));
+        assert!(matches!(changes.recv().await.unwrap(), PeerChange::Removed { key } if key == "peer1"));
+        assert!(changes.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_lags_instead_of_blocking() {
+        let manager = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+        manager.add_or_update_peer(active_peer("peer1", 9000)).await;
+        let mut stalled = manager.subscribe_changes();
+
+        let writes = async {
+            for _ in 0..5000 {
+                manager.report("peer1", PeerEvent::UsefulMessage).await;
+            }
+        };
+        timeout(Duration::from_secs(5), writes).await.expect("writers never wait for subscribers");
+        assert!(matches!(stalled.recv().await, Err(RecvError::Lagged(_))));
+        assert!(matches!(stalled.recv().await, Ok(PeerChange::Updated { .. })));
+    }
+}
@@ -0,0 +1,108 @@
+#[cfg(test)]
+mod tests {
+    use Nautilus_Core::record::{
+        AddrSource, ExportFormat, MemoryStore, MergeStrategy, PeerAddr, PeerEvent,
+        PeerManagement, PeerRecord, EXPORT_FORMAT_VERSION,
+    };
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn peer(id: &str, port: u16, last_seen_ms: u64) -> PeerRecord {
+        PeerRecord {
+            peer_id: Some(id.to_string()),
+            last_seen: Some(UNIX_EPOCH + Duration::from_millis(last_seen_ms)),
+            ..PeerRecord::new(format!("127.0.0.1:{}", port).parse().unwrap())
+        }
+    }
+
+    #[tokio::test]
+    async fn exports_round_trip_in_every_format() {
+        let source = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+        let mut first = peer("peer1", 8001, 1_700_000_000_000);
+        first.addrs.push(
+            PeerAddr::tcp("10.0.0.1:8001".parse().unwrap(), AddrSource::PeerExchange).with_learned_from("peer2"),
+        );
+        first.protocols.insert("/blob/1".to_string());
+        first.tags.insert("bootstrap".to_string());
+        source.add_or_update_peer(first).await;
+        source.add_or_update_peer(peer("peer2", 8002, 1_700_000_500_000)).await;
+        source.report("peer2", PeerEvent::Handshake).await;
+        source.record_failure("peer1").await;
+
+        for format in [ExportFormat::Json, ExportFormat::Cbor, ExportFormat::Protobuf] {
+            let data = source.export(format).await.unwrap();
+            let target = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+            let summary = target.import(format, &data, MergeStrategy::Replace).await.unwrap();
+            assert_eq!(summary.added, 2, "{:?}", format);
+
+            // Times are kept to the millisecond, as in the cache file
+            for id in ["peer1", "peer2"] {
+                let before = serde_json::to_value(source.get_peer(id).await.unwrap()).unwrap();
+                let after = serde_json::to_value(target.get_peer(id).await.unwrap()).unwrap();
+                assert_eq!(before, after, "{:?}", format);
+            }
+        }
+
+        // Exports from a newer version and garbage are refused
+        let text = String::from_utf8(source.export(ExportFormat::Json).await.unwrap()).unwrap();
+        let current = format!("\"version\": {}", EXPORT_FORMAT_VERSION);
+        let newer = text.replacen(&current, &format!("\"version\": {}", EXPORT_FORMAT_VERSION + 1), 1);
+        let target = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+        let err = target.import(ExportFormat::Json, newer.as_bytes(), MergeStrategy::Replace).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+        assert!(target.import(ExportFormat::Cbor, b"not cbor", MergeStrategy::Replace).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn merge_strategies_decide_who_wins() {
+        let exporter = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+        exporter.add_or_update_peer(peer("old", 9001, 1_000)).await;
+        exporter.add_or_update_peer(peer("fresh", 9002, 9_000)).await;
+        exporter.add_or_update_peer(peer("new", 9003, 5_000)).await;
+        let data = exporter.export(ExportFormat::Protobuf).await.unwrap();
+
+        let table = || async {
+            let manager = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+            manager.add_or_update_peer(peer("old", 7001, 2_000)).await;
+            manager.add_or_update_peer(peer("fresh", 7002, 3_000)).await;
+            manager.add_or_update_peer(peer("local", 7004, 4_000)).await;
+            manager
+        };
+        let port = |manager: &PeerManagement, id: &'static str| {
+            let manager = manager.clone();
+            async move { manager.get_peer(id).await.map(|peer| peer.addr.port()) }
+        };
+
+        // Newer: only the record seen later than the local one wins
+        let manager = table().await;
+        let summary = manager.import(ExportFormat::Protobuf, &data, MergeStrategy::MergeNewer).await.unwrap();
+        assert_eq!((summary.added, summary.updated, summary.skipped, summary.removed), (1, 1, 1, 0));
+        assert_eq!(port(&manager, "old").await, Some(7001));
+        assert_eq!(port(&manager, "fresh").await, Some(9002));
+        assert_eq!(port(&manager, "new").await, Some(9003));
+        assert_eq!(port(&manager, "local").await, Some(7004));
+
+        // Keep existing: known peers are untouched
+        let manager = table().await;
+        let summary = manager.import(ExportFormat::Protobuf, &data, MergeStrategy::KeepExisting).await.unwrap();
+        assert_eq!((summary.added, summary.updated, summary.skipped), (1, 0, 2));
+        assert_eq!(port(&manager, "fresh").await, Some(7002));
+        assert_eq!(port(&manager, "new").await, Some(9003));
+
+        // Replace: the table is exactly the export
+        let manager = table().await;
+        let mut changes = manager.subscribe_changes();
+        let summary = manager.import(ExportFormat::Protobuf, &data, MergeStrategy::Replace).await.unwrap();
+        assert_eq!(summary.removed, 1);
+        assert_eq!(port(&manager, "old").await, Some(9001));
+        assert_eq!(port(&manager, "local").await, None);
+        let mut keys = manager.get_all_peers().await;
+        keys.sort();
+        assert_eq!(keys, ["fresh", "new", "old"]);
+        let mut events = 0;
+        while changes.try_recv().is_ok() {
+            events += 1;
+        }
+        assert_eq!(events, 4);
+    }
+}
@@ -1,17 +1,17 @@
 #[cfg(test)]
 mod tests {
-    use Nautilus_Core::record::{PeerManagement, PeerRecord};
+    use Nautilus_Core::record::{MemoryStore, PeerManagement, PeerRecord};
     use std::fs;
+    use std::sync::Arc;
+    use std::time::Duration;
 
     #[tokio::test]
     async fn test_add_or_update_peer() {
         let peer_manager = PeerManagement::new("test_peers.json".to_string());
         let peer = PeerRecord {
-            addr: "127.0.0.1:8000".parse().unwrap(),
             peer_id: Some("peer1".to_string()),
-            public_key: None,
             is_active: true,
-            last_seen: None,
+            ..PeerRecord::new("127.0.0.1:8000".parse().unwrap())
         };
 
         peer_manager.add_or_update_peer(peer.clone()).await;
@@ -23,11 +23,9 @@ mod tests {
     async fn test_remove_peer() {
         let peer_manager = PeerManagement::new("test_peers.json".to_string());
         let peer = PeerRecord {
-            addr: "127.0.0.1:8000".parse().unwrap(),
             peer_id: Some("peer1".to_string()),
-            public_key: None,
             is_active: true,
-            last_seen: None,
+            ..PeerRecord::new("127.0.0.1:8000".parse().unwrap())
         };
 
         peer_manager.add_or_update_peer(peer.clone()).await;
@@ -41,11 +39,9 @@ mod tests {
       let test_file = "test_peers.json";
       let peer_manager = PeerManagement::new(test_file.to_string());
       let peer = PeerRecord {
-          addr: "127.0.0.1:8000".parse().unwrap(),
           peer_id: Some("peer1".to_string()),
-          public_key: None,
           is_active: true,
-          last_seen: None,
+          ..PeerRecord::new("127.0.0.1:8000".parse().unwrap())
       };
   
       peer_manager.add_or_update_peer(peer.clone()).await;
@@ -59,4 +55,60 @@ mod tests {
       // Cleanup test file
       fs::remove_file(test_file).unwrap();
   }
+
+    #[tokio::test]
+    async fn test_history_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peers.json").to_string_lossy().into_owned();
+        let peer_manager = PeerManagement::new(path.clone());
+        peer_manager
+            .add_or_update_peer(PeerRecord {
+                peer_id: Some("peer1".to_string()),
+                is_active: true,
+                ..PeerRecord::new("127.0.0.1:8000".parse().unwrap())
+            })
+            .await;
+        peer_manager.record_connected("peer1").await;
+        peer_manager.record_failure("peer1").await;
+        peer_manager.record_connected("peer1").await;
+        peer_manager.save_to_file().await.unwrap();
+
+        let reloaded = PeerManagement::new(path);
+        reloaded.load_from_file().await.unwrap();
+        let before = peer_manager.get_peer("peer1").await.unwrap();
+        let after = reloaded.get_peer("peer1").await.unwrap();
+        assert_eq!(after.history.successes, 2);
+        assert_eq!(after.history.failures, 1);
+        assert!(after.history.first_seen.is_some() && after.history.last_failed.is_some());
+        // Timestamps are stored to the millisecond, so ages match across the restart
+        let drift = before.age().unwrap().abs_diff(after.age().unwrap());
+        assert!(drift < Duration::from_millis(5), "drift was {:?}", drift);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_updates_are_not_lost() {
+        let peer_manager = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+        let mut tasks = Vec::new();
+        for port in 8000..8064u16 {
+            let peer_manager = peer_manager.clone();
+            tasks.push(tokio::spawn(async move {
+                peer_manager
+                    .add_or_update_peer(PeerRecord {
+                        peer_id: Some("peer1".to_string()),
+                        is_active: true,
+                        ..PeerRecord::new(format!("127.0.0.1:{}", port).parse().unwrap())
+                    })
+                    .await;
+                peer_manager.tag_peer("peer1", &port.to_string()).await;
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let peer = peer_manager.get_peer("peer1").await.unwrap();
+        assert_eq!(peer.addrs.len(), 64);
+        assert_eq!(peer.tags.len(), 64);
+        assert_eq!(peer_manager.get_all_peers().await, vec!["peer1".to_string()]);
+    }
 }
@@ -0,0 +1,63 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::seen_peer;
+    use Nautilus_Core::record::{
+        AddrFamily, MemoryStore, PeerEvent, PeerManagement, PeerQuery, PeerRecord, SortKey,
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    async fn table() -> PeerManagement {
+        let manager = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+        manager.add_or_update_peer(seen_peer("a", "10.0.0.1:9000", true, Duration::from_secs(10))).await;
+        manager.add_or_update_peer(seen_peer("b", "10.0.0.2:9000", true, Duration::from_secs(20))).await;
+        manager.add_or_update_peer(seen_peer("c", "[::1]:9000", false, Duration::from_secs(3600))).await;
+        manager.add_or_update_peer(seen_peer("d", "10.0.0.4:9000", false, Duration::from_secs(30))).await;
+        manager.report("a", PeerEvent::Handshake).await;
+        manager.report("d", PeerEvent::Handshake).await;
+        manager.report("d", PeerEvent::Handshake).await;
+        manager.report("c", PeerEvent::InvalidMessage).await;
+        manager.add_protocol("a", "/chat/1").await;
+        manager.add_protocol("d", "/chat/1").await;
+        manager.tag_peer("d", "relay").await;
+        manager
+    }
+
+    fn ids(peers: &[PeerRecord]) -> Vec<&str> {
+        peers.iter().map(|peer| peer.peer_id.as_deref().unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_filters_combine() {
+        let manager = table().await;
+        let active = manager.query(&PeerQuery::new().active(true)).await;
+        assert_eq!(ids(&active.peers), ["a", "b"]);
+
+        let recent = PeerQuery::new().seen_within(Duration::from_secs(60)).family(AddrFamily::V4);
+        assert_eq!(manager.count(&recent).await, 3);
+        assert_eq!(ids(&manager.query(&PeerQuery::new().family(AddrFamily::V6)).await.peers), ["c"]);
+
+        let chat = PeerQuery::new().protocol("/chat/1").score_between(Some(1.0), None);
+        assert_eq!(ids(&manager.query(&chat).await.peers), ["a", "d"]);
+        assert_eq!(ids(&manager.query(&chat.clone().tag("relay")).await.peers), ["d"]);
+        assert!(manager.untag_peer("d", "relay").await);
+        assert_eq!(manager.count(&chat.tag("relay")).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_sorting_and_pagination() {
+        let manager = table().await;
+        let by_score = PeerQuery::new().sort_by(SortKey::Score, true);
+        assert_eq!(ids(&manager.query(&by_score).await.peers), ["d", "a", "b", "c"]);
+
+        let page = manager.query(&PeerQuery::new().sort_by(SortKey::LastSeen, true).page(1, 2)).await;
+        assert_eq!(page.total, 4);
+        assert_eq!(ids(&page.peers), ["b", "d"]);
+
+        let past_end = manager.query(&PeerQuery::new().page(10, 5)).await;
+        assert_eq!(past_end.total, 4);
+        assert!(past_end.peers.is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::active_peer;
+    use Nautilus_Core::record::{
+        JsonFileStore, MemoryStore, PeerEvent, PeerManagement, PeerStore, CACHE_FORMAT_VERSION,
+    };
+    use std::collections::HashMap;
+    use std::fs;
+    use std::time::Duration;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_memory_store_sees_incremental_writes() {
+        let store = Arc::new(MemoryStore::new());
+        let manager = PeerManagement::with_store(store.clone());
+
+        manager.add_or_update_peer(active_peer("peer1", 8001)).await;
+        manager.add_or_update_peer(active_peer("peer2", 8002)).await;
+        manager.remove_peer("peer1").await;
+        // Without autosave, in-place updates are written through too
+        manager.report("peer2", PeerEvent::Handshake).await;
+
+        let stored = store.load().unwrap();
+        assert_eq!(stored.len(), 1);
+        let current = manager.get_peer("peer2").await.unwrap();
+        assert_eq!(stored["peer2"].reputation, current.reputation);
+
+        let reloaded = PeerManagement::with_store(store);
+        reloaded.load_from_file().await.unwrap();
+        assert!(reloaded.get_peer("peer2").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_json_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peers.json").to_string_lossy().into_owned();
+
+        let manager = PeerManagement::new(path.clone());
+        manager.add_or_update_peer(active_peer("peer1", 8001)).await;
+        manager.add_or_update_peer(active_peer("peer2", 8002)).await;
+        manager.save_to_file().await.unwrap();
+
+        // Removals reach the file without another full save
+        manager.remove_peer("peer1").await;
+        let stored = JsonFileStore::new(path.clone()).load().unwrap();
+        assert_eq!(stored.len(), 1);
+
+        let reloaded = PeerManagement::new(path);
+        reloaded.load_from_file().await.unwrap();
+        assert!(reloaded.get_peer("peer1").await.is_none());
+        assert_eq!(reloaded.get_peer("peer2").await.unwrap().addr, active_peer("peer2", 8002).addr);
+    }
+
+    #[test]
+    fn test_json_store_migrates_unversioned_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peers.json").to_string_lossy().into_owned();
+        let legacy: HashMap<_, _> = [("peer1".to_string(), active_peer("peer1", 8001))].into();
+        fs::write(&path, serde_json::to_string(&legacy).unwrap()).unwrap();
+
+        let store = JsonFileStore::new(path.clone());
+        let peers = store.load().unwrap();
+        assert!(peers.contains_key("peer1"));
+
+        store.save_all(&peers).unwrap();
+        let saved: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["version"], CACHE_FORMAT_VERSION);
+        assert!(!dir.path().join("peers.json.tmp").exists());
+    }
+
+    #[test]
+    fn test_json_store_quarantines_corrupt_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peers.json").to_string_lossy().into_owned();
+        let store = JsonFileStore::new(path.clone());
+
+        let first: HashMap<_, _> = [("peer1".to_string(), active_peer("peer1", 8001))].into();
+        store.save_all(&first).unwrap();
+        store.save_all(&HashMap::new()).unwrap();
+        // A crash or bad disk leaves half a file behind
+        fs::write(&path, "{\"version\": 1, \"peers\": {").unwrap();
+
+        let peers = store.load().unwrap();
+        assert!(peers.contains_key("peer1"), "falls back to the backup");
+        assert!(!dir.path().join("peers.json").exists());
+        let quarantined = fs::read_dir(dir.path())
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().contains(".corrupt-"))
+            .count();
+        assert_eq!(quarantined, 1);
+    }
+
+    #[test]
+    fn test_json_store_migrates_elapsed_last_seen_to_wall_clock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peers.json").to_string_lossy().into_owned();
+        // Version 1 stored how many seconds ago the peer was seen when saving
+        let v1 = r#"{"version": 1, "peers": {"peer1": {
+            "addr": "127.0.0.1:8001", "peer_id": "peer1", "public_key": null,
+            "is_active": true, "last_seen": 3600}}}"#;
+        fs::write(&path, v1).unwrap();
+
+        let peers = JsonFileStore::new(path).load().unwrap();
+        let age = peers["peer1"].age().unwrap();
+        assert!(age >= Duration::from_secs(3600) && age < Duration::from_secs(3660), "age was {:?}", age);
+    }
+}
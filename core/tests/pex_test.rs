@@ -0,0 +1,122 @@
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use tokio::net::{TcpListener, TcpStream, UdpSocket};
+    use Nautilus_Core::proto::{Data, Handshake, MessageKind, PeerExchange, PeerInfo};
+    use Nautilus_Core::record::{AddrSource, PeerHistory, PeerRecord};
+    use Nautilus_Core::transport::{wire, NautilusTransport};
+
+    /// A peer this node connected to at `last_connected`, but claims to have
+    /// just seen regardless.
+    fn record(peer_id: &str, addr: &str, last_connected: Option<SystemTime>) -> PeerRecord {
+        PeerRecord {
+            peer_id: Some(peer_id.to_string()),
+            last_seen: Some(SystemTime::now()),
+            history: PeerHistory { last_connected, ..Default::default() },
+            ..PeerRecord::new(addr.parse().unwrap())
+        }
+    }
+
+    fn exchange(peer_ids: &[&str]) -> Vec<u8> {
+        let peers = peer_ids
+            .iter()
+            .map(|peer_id| {
+                PeerInfo {
+                    peer_id: peer_id.to_string(),
+                    addrs: vec!["127.0.0.1:4000".to_string()],
+                    last_seen_ms: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64,
+                    ..Default::default()
+                }
+                .encode_to_vec()
+            })
+            .collect();
+        wire::encode(MessageKind::PeerExchange, &PeerExchange { peers })
+    }
+
+    #[tokio::test]
+    async fn test_new_neighbour_gets_recently_seen_peers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let remote = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            wire::read_frame(&mut stream).await.unwrap().unwrap();
+            let ack = Handshake {
+                peer_id: "remote".to_string(),
+                ..Default::default()
+            };
+            wire::write_frame(&mut stream, &wire::encode(MessageKind::HandshakeAck, &ack))
+                .await
+                .unwrap();
+
+            let frame = wire::read_frame(&mut stream).await.unwrap().unwrap();
+            let envelope = wire::decode(&frame).unwrap().message;
+            let exchange = wire::decode_body::<PeerExchange>(&envelope).unwrap().message;
+            exchange
+                .peers
+                .iter()
+                .map(|entry| PeerInfo::decode(entry.as_slice()).unwrap().peer_id)
+                .collect::<Vec<_>>()
+        });
+
+        let transport = NautilusTransport::new(0).await.unwrap();
+        let peers = transport.peer_manager();
+        let now = SystemTime::now();
+        peers.add_or_update_peer(record("remote", &addr.to_string(), Some(now))).await;
+        peers.add_or_update_peer(record("fresh", "127.0.0.1:4001", Some(now))).await;
+        let long_ago = now - Duration::from_secs(2 * 60 * 60);
+        peers.add_or_update_peer(record("stale", "127.0.0.1:4002", Some(long_ago))).await;
+        peers.add_or_update_peer(record("hearsay", "127.0.0.1:4003", None)).await;
+
+        transport.connect(addr).await.unwrap();
+        let shared = tokio::time::timeout(Duration::from_secs(5), remote).await.unwrap().unwrap();
+        assert!(shared.contains(&"fresh".to_string()));
+        assert!(!shared.contains(&"stale".to_string()));
+        assert!(!shared.contains(&"hearsay".to_string()));
+        assert!(!shared.contains(&"remote".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_received_peers_are_attributed_and_rate_limited() {
+        let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let server = NautilusTransport::new(port).await.unwrap();
+        let mut inbound = server.subscribe();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let listening = server.clone();
+        tokio::spawn(async move { listening.start_listeners(shutdown_rx).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // Over UDP there is no handshake to attribute the entries to
+        let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        udp.send_to(&exchange(&["erin"]), ("127.0.0.1", port)).await.unwrap();
+
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let handshake = Handshake {
+            peer_id: "relay".to_string(),
+            ..Default::default()
+        };
+        wire::write_frame(&mut stream, &wire::encode(MessageKind::Handshake, &handshake))
+            .await
+            .unwrap();
+        wire::read_frame(&mut stream).await.unwrap().unwrap();
+
+        // The second exchange follows too closely and is dropped
+        wire::write_frame(&mut stream, &exchange(&["carol"])).await.unwrap();
+        wire::write_frame(&mut stream, &exchange(&["dave"])).await.unwrap();
+        let data = wire::encode(MessageKind::Data, &Data { payload: b"done".to_vec() });
+        wire::write_frame(&mut stream, &data).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), inbound.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let carol = server.peer_manager().get_peer("carol").await.unwrap();
+        assert_eq!(carol.addrs[0].source, AddrSource::PeerExchange);
+        assert_eq!(carol.addrs[0].learned_from.as_deref(), Some("relay"));
+        assert_eq!(carol.last_seen, None, "the relay's last-seen time is not taken on trust");
+        assert!(server.peer_manager().get_peer("dave").await.is_none());
+        assert!(server.peer_manager().get_peer("erin").await.is_none());
+
+        shutdown_tx.send(true).unwrap();
+    }
+}
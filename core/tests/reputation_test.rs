@@ -0,0 +1,63 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::peer;
+    use Nautilus_Core::record::{MemoryStore, PeerEvent, PeerManagement, Reputation, ScoreConfig};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_score_decays_towards_zero() {
+        let config = ScoreConfig::default();
+        let start = SystemTime::now();
+        let mut reputation = Reputation::default();
+        reputation.apply(PeerEvent::ProtocolViolation, start, &config);
+        assert_eq!(reputation.score, config.protocol_violation);
+
+        let later = start + config.half_life;
+        let decayed = reputation.score_at(later, &config);
+        assert!((decayed - config.protocol_violation / 2.0).abs() < 1e-9);
+
+        // Clamped at the configured bounds
+        for _ in 0..100 {
+            reputation.apply(PeerEvent::Handshake, later, &config);
+        }
+        assert_eq!(reputation.score, config.max_score);
+    }
+
+    #[tokio::test]
+    async fn test_scores_drive_dialing_banning_and_eviction() {
+        let manager = PeerManagement::with_store(Arc::new(MemoryStore::new())).with_score_config(ScoreConfig {
+            half_life: Duration::from_secs(3600),
+            ..ScoreConfig::default()
+        });
+        for (id, port) in [("good", 9001), ("meh", 9002), ("bad", 9003), ("evil", 9004)] {
+            manager.add_or_update_peer(peer(id, port)).await;
+        }
+        manager.report("good", PeerEvent::Handshake).await;
+        manager.report("good", PeerEvent::Rtt(Duration::from_millis(5))).await;
+        manager.report("bad", PeerEvent::InvalidMessage).await;
+        manager.report("bad", PeerEvent::InvalidMessage).await;
+        manager.report("bad", PeerEvent::InvalidMessage).await;
+        for _ in 0..3 {
+            manager.report("evil", PeerEvent::ProtocolViolation).await;
+        }
+
+        assert!(manager.is_banned("evil").await);
+        assert!(!manager.is_banned("bad").await);
+        let order: Vec<_> = manager
+            .dial_candidates()
+            .await
+            .into_iter()
+            .map(|peer| peer.peer_id.unwrap())
+            .collect();
+        assert_eq!(order, ["good", "meh", "bad"]);
+
+        let mut evicted = manager.evict_low_scores(10).await;
+        evicted.sort();
+        assert_eq!(evicted, ["bad", "evil"]);
+        assert_eq!(manager.evict_low_scores(1).await, ["meh"]);
+        assert!(manager.get_peer("good").await.is_some());
+    }
+}
@@ -0,0 +1,126 @@
+#![cfg(feature = "identity_integration")]
+
+#[cfg(test)]
+mod tests {
+    use identity::Identity;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+    use Nautilus_Core::record::{
+        AddrSource, ExportFormat, MemoryStore, MergeStrategy, PeerManagement, PeerRecord, RecordError,
+        SignedPeerRecord,
+    };
+
+    fn sign(identity: &Identity, addrs: &[&str], sequence: u64) -> SignedPeerRecord {
+        SignedPeerRecord::sign(
+            identity.get_peer_id(),
+            identity.get_key_pair(),
+            addrs.iter().map(|addr| addr.parse().unwrap()).collect(),
+            sequence,
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_verify_rejects_forgeries() {
+        let identity = Identity::new(None, None);
+        let other = Identity::new(None, None);
+        let now = SystemTime::now();
+
+        let record = sign(&identity, &["127.0.0.1:9000"], 1);
+        let decoded = SignedPeerRecord::from_bytes(&record.to_bytes()).unwrap();
+        assert_eq!(decoded, record);
+        decoded.verify(now).unwrap();
+
+        let mut moved = record.clone();
+        moved.addrs = vec!["10.0.0.1:9000".parse().unwrap()];
+        assert!(matches!(moved.verify(now), Err(RecordError::BadSignature(_))));
+
+        // Validly signed, but claiming an ID that belongs to another key
+        let impostor = SignedPeerRecord::sign(
+            identity.get_peer_id(),
+            other.get_key_pair(),
+            record.addrs.clone(),
+            2,
+            SystemTime::now() + Duration::from_secs(3600),
+        )
+        .unwrap();
+        assert!(matches!(impostor.verify(now), Err(RecordError::PeerIdMismatch(_))));
+
+        let later = now + Duration::from_secs(7200);
+        assert!(matches!(record.verify(later), Err(RecordError::Expired(_))));
+    }
+
+    #[tokio::test]
+    async fn test_newer_sequence_replaces_older() {
+        let identity = Identity::new(None, None);
+        let peer_id = identity.get_peer_id().to_string();
+        let peer_manager = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+
+        let first = sign(&identity, &["127.0.0.1:9000", "127.0.0.1:9001"], 1);
+        peer_manager.accept_signed_record(first.clone(), AddrSource::PeerExchange, None).await.unwrap();
+        let second = sign(&identity, &["127.0.0.1:9001"], 2);
+        peer_manager.accept_signed_record(second, AddrSource::PeerExchange, None).await.unwrap();
+
+        let stale = peer_manager.accept_signed_record(first, AddrSource::PeerExchange, None).await;
+        assert_eq!(stale, Err(RecordError::Stale { current: 2, received: 1 }));
+
+        // An unsigned update can add addresses but not swap the verified key
+        peer_manager
+            .add_or_update_peer(PeerRecord {
+                peer_id: Some(peer_id.clone()),
+                public_key: Some("forged".to_string()),
+                ..PeerRecord::new("127.0.0.1:9002".parse().unwrap())
+            })
+            .await;
+
+        let peer = peer_manager.get_peer(&peer_id).await.unwrap();
+        assert_eq!(peer.signed_record.unwrap().sequence, 2);
+        assert_eq!(peer.public_key.as_deref(), Some(identity.get_key_pair().public_key.as_str()));
+        let addrs: Vec<SocketAddr> = peer.addrs.iter().map(|known| known.addr).collect();
+        assert!(!addrs.contains(&"127.0.0.1:9000".parse().unwrap()));
+        assert!(addrs.contains(&"127.0.0.1:9001".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_import_holds_records_to_the_same_rules() {
+        let alice = Identity::new(None, None);
+        let alice_id = alice.get_peer_id().to_string();
+        let exporter = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+        let first = sign(&alice, &["127.0.0.1:9000"], 1);
+        exporter.accept_signed_record(first.clone(), AddrSource::PeerExchange, None).await.unwrap();
+        exporter
+            .add_or_update_peer(PeerRecord {
+                peer_id: Some("bob".to_string()),
+                public_key: Some(alice.get_key_pair().public_key.clone()),
+                ..PeerRecord::new("127.0.0.1:9001".parse().unwrap())
+            })
+            .await;
+        exporter
+            .add_or_update_peer(PeerRecord {
+                peer_id: Some("mallory".to_string()),
+                signed_record: Some(first.clone()),
+                ..PeerRecord::new("127.0.0.1:9002".parse().unwrap())
+            })
+            .await;
+        let data = exporter.export(ExportFormat::Json).await.unwrap();
+
+        let fresh = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+        fresh.import(ExportFormat::Json, &data, MergeStrategy::MergeNewer).await.unwrap();
+        let imported = fresh.get_peer(&alice_id).await.unwrap();
+        assert_eq!(imported.signed_record, Some(first));
+        assert_eq!(imported.public_key.as_deref(), Some(alice.get_key_pair().public_key.as_str()));
+        let bob = fresh.get_peer("bob").await.unwrap();
+        assert_eq!(bob.public_key, None, "the key does not belong to bob's ID");
+        let mallory = fresh.get_peer("mallory").await.unwrap();
+        assert_eq!(mallory.signed_record, None, "the record was signed for alice");
+
+        // Even a replacing import cannot roll a signed record back
+        let ahead = PeerManagement::with_store(Arc::new(MemoryStore::new()));
+        let second = sign(&alice, &["127.0.0.1:9003"], 2);
+        ahead.accept_signed_record(second.clone(), AddrSource::PeerExchange, None).await.unwrap();
+        ahead.import(ExportFormat::Json, &data, MergeStrategy::Replace).await.unwrap();
+        assert_eq!(ahead.get_peer(&alice_id).await.unwrap().signed_record, Some(second));
+    }
+}
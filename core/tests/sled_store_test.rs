@@ -0,0 +1,33 @@
+#![cfg(feature = "sled_store")]
+
+#[cfg(test)]
+mod tests {
+    use Nautilus_Core::record::{PeerManagement, PeerRecord, PeerStore, SledStore};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_sled_store_persists_across_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peers.sled").to_string_lossy().into_owned();
+
+        {
+            let store = Arc::new(SledStore::open(&path).unwrap());
+            let manager = PeerManagement::with_store(store);
+            for (id, port) in [("peer1", 8001), ("peer2", 8002)] {
+                manager
+                    .add_or_update_peer(PeerRecord {
+                        peer_id: Some(id.to_string()),
+                        is_active: true,
+                        ..PeerRecord::new(format!("127.0.0.1:{}", port).parse().unwrap())
+                    })
+                    .await;
+            }
+            manager.remove_peer("peer1").await;
+        }
+
+        let store = SledStore::open(&path).unwrap();
+        let stored = store.load().unwrap();
+        assert_eq!(stored.len(), 1);
+        assert!(stored.contains_key("peer2"));
+    }
+}
@@ -0,0 +1,121 @@
+#[cfg(test)]
+mod tests {
+    use Nautilus_Core::proto::{MessageKind, StreamFrame, StreamOp};
+    use Nautilus_Core::record::PeerRecord;
+    use Nautilus_Core::transport::{wire, NautilusTransport, TransportError};
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, UdpSocket};
+
+    const ECHO: &str = "/echo/1.0.0";
+
+    /// Start a node with the given peer ID and return it with its address.
+    async fn node(peer_id: &str) -> (NautilusTransport, SocketAddr) {
+        let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let node = NautilusTransport::new(port).await.unwrap();
+        node.tcp.set_identity(peer_id, "");
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let listening = node.clone();
+        tokio::spawn(async move {
+            let _shutdown_tx = shutdown_tx;
+            listening.start_listeners(shutdown_rx).await
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        (node, format!("127.0.0.1:{}", port).parse().unwrap())
+    }
+
+    /// A server answering every echo stream, and a client that knows it.
+    async fn echo_pair(server_id: &str, client_id: &str) -> (NautilusTransport, NautilusTransport) {
+        let (server, server_addr) = node(server_id).await;
+        let (client, _) = node(client_id).await;
+        client
+            .peer_manager()
+            .add_or_update_peer(PeerRecord {
+                peer_id: Some(server_id.to_string()),
+                ..PeerRecord::new(server_addr)
+            })
+            .await;
+
+        let mut incoming = server.register_protocol(ECHO);
+        tokio::spawn(async move {
+            while let Some(mut stream) = incoming.recv().await {
+                tokio::spawn(async move {
+                    let mut received = Vec::new();
+                    stream.read_to_end(&mut received).await.unwrap();
+                    stream.write_all(&received).await.unwrap();
+                    stream.shutdown().await.unwrap();
+                });
+            }
+        });
+        (server, client)
+    }
+
+    #[tokio::test]
+    async fn test_open_stream_round_trip() {
+        let (_server, client) = echo_pair("stream-server", "stream-client").await;
+
+        let mut stream = client.open_stream("stream-server", ECHO).await.unwrap();
+        assert_eq!(stream.protocol(), ECHO);
+        let payload: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        stream.write_all(&payload).await.unwrap();
+        stream.shutdown().await.unwrap();
+
+        let mut echoed = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut echoed))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(echoed, payload);
+    }
+
+    #[tokio::test]
+    async fn test_protocol_negotiation() {
+        let (_server, client) = echo_pair("select-server", "select-client").await;
+
+        let result = client.open_stream("select-server", "/chat/2.0.0").await;
+        assert!(matches!(result, Err(TransportError::UnsupportedProtocol(_))));
+
+        let stream = client
+            .open_stream_any("select-server", &["/chat/2.0.0", ECHO])
+            .await
+            .unwrap();
+        assert_eq!(stream.protocol(), ECHO);
+    }
+
+    #[tokio::test]
+    async fn test_ignores_stream_frames_over_udp() {
+        let (server, server_addr) = node("udp-stream-server").await;
+        let mut incoming = server.register_protocol(ECHO);
+
+        let propose = StreamFrame {
+            stream_id: 1,
+            initiator: true,
+            op: StreamOp::Propose as i32,
+            protocol: ECHO.to_string(),
+            data: Vec::new(),
+        };
+        let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        udp.send_to(&wire::encode(MessageKind::Stream, &propose), server_addr).await.unwrap();
+
+        let accepted = tokio::time::timeout(Duration::from_millis(300), incoming.recv()).await;
+        assert!(accepted.is_err(), "a datagram must not open a stream");
+    }
+
+    #[tokio::test]
+    async fn test_caps_streams_per_connection() {
+        let (_server, client) = echo_pair("capped-server", "capped-client").await;
+
+        // The echo handler holds each stream until the client finishes writing
+        let mut open = Vec::new();
+        for _ in 0..64 {
+            open.push(client.open_stream("capped-server", ECHO).await.unwrap());
+        }
+        let result = client.open_stream("capped-server", ECHO).await;
+        assert!(matches!(result, Err(TransportError::UnsupportedProtocol(_))));
+
+        drop(open);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(client.open_stream("capped-server", ECHO).await.is_ok());
+    }
+}
@@ -0,0 +1,56 @@
+#[cfg(test)]
+mod tests {
+    use Nautilus_Core::transport::{Flow, RateLimit, Throttle};
+    use std::net::SocketAddr;
+    use std::time::{Duration, Instant};
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[tokio::test]
+    async fn test_global_limit_delays_and_reports() {
+        let throttle = Throttle::new();
+        throttle.set_global_limit(Flow::Upload, Some(RateLimit::with_burst(100_000, 10_000)));
+
+        let started = Instant::now();
+        for _ in 0..4 {
+            throttle.acquire(Flow::Upload, addr(1), 10_000).await;
+        }
+        // The burst covers the first transfer, the other 30k go at 100k/s
+        assert!(started.elapsed() >= Duration::from_millis(250));
+
+        let stats = throttle.stats();
+        assert_eq!(stats.total.upload.bytes, 40_000);
+        assert_eq!(stats.total.upload.throttled, 3);
+        assert_eq!(stats.total.download.bytes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_peer_limit_only_applies_to_that_peer() {
+        let throttle = Throttle::new();
+        throttle.bind_addr(addr(1), "slow");
+        throttle.set_peer_limit("slow", Flow::Download, Some(RateLimit::with_burst(50_000, 1_000)));
+
+        let started = Instant::now();
+        throttle.acquire(Flow::Download, addr(2), 50_000).await;
+        assert!(started.elapsed() < Duration::from_millis(50));
+
+        throttle.acquire(Flow::Download, addr(1), 10_000).await;
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        assert_eq!(throttle.stats().peers["slow"].download.throttled, 1);
+
+        // Lifting the limit at runtime takes effect immediately
+        throttle.set_peer_limit("slow", Flow::Download, None);
+        let lifted = Instant::now();
+        throttle.acquire(Flow::Download, addr(1), 50_000).await;
+        assert!(lifted.elapsed() < Duration::from_millis(50));
+
+        // Totals are kept while any of the peer's addresses is still bound
+        throttle.bind_addr(addr(3), "slow");
+        throttle.unbind_addr(addr(1));
+        assert!(throttle.stats().peers.contains_key("slow"));
+        throttle.unbind_addr(addr(3));
+        assert!(throttle.stats().peers.is_empty());
+    }
+}
@@ -0,0 +1,267 @@
+#[cfg(test)]
+mod tests {
+    use Nautilus_Core::record::PeerRecord;
+    use Nautilus_Core::proto::{Data, Goodbye, Handshake, MessageKind, Ping};
+    use Nautilus_Core::transport::{wire, Lifecycle, NautilusTransport, Priority, SendOptions, TransportError};
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    fn record(peer_id: &str, addr: &str) -> PeerRecord {
+        PeerRecord {
+            peer_id: Some(peer_id.to_string()),
+            ..PeerRecord::new(addr.parse().unwrap())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_to_unknown_peer() {
+        let transport = NautilusTransport::new(0).await.unwrap();
+        let result = transport.send_to_peer("missing", b"hello").await;
+        assert!(matches!(result, Err(TransportError::UnknownPeer(id)) if id == "missing"));
+    }
+
+    #[tokio::test]
+    async fn test_send_to_unreachable_peer() {
+        // Reserve a port and close it again so nothing is listening there
+        let addr = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+
+        let transport = NautilusTransport::new(0).await.unwrap();
+        transport
+            .peer_manager()
+            .add_or_update_peer(record("offline", &addr.to_string()))
+            .await;
+
+        let result = transport.send_to_peer("offline", b"hello").await;
+        assert!(matches!(result, Err(TransportError::Unreachable(id, _)) if id == "offline"));
+    }
+
+    #[tokio::test]
+    async fn test_send_to_peer_dials_and_delivers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let remote = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let frame = wire::read_frame(&mut stream).await.unwrap().unwrap();
+            let handshake = wire::decode(&frame).unwrap().message;
+            assert_eq!(handshake.kind, MessageKind::Handshake as i32);
+
+            let ack = Handshake {
+                peer_id: "remote".to_string(),
+                ..Default::default()
+            };
+            wire::write_frame(&mut stream, &wire::encode(MessageKind::HandshakeAck, &ack))
+                .await
+                .unwrap();
+
+            let frame = wire::read_frame(&mut stream).await.unwrap().unwrap();
+            let envelope = wire::decode(&frame).unwrap().message;
+            wire::decode_body::<Data>(&envelope).unwrap().message.payload
+        });
+
+        let transport = NautilusTransport::new(0).await.unwrap();
+        transport
+            .peer_manager()
+            .add_or_update_peer(record("remote", &addr.to_string()))
+            .await;
+
+        transport.send_to_peer("remote", b"hello").await.unwrap();
+        assert_eq!(remote.await.unwrap(), b"hello");
+
+        let peer = transport.peer_manager().get_peer("remote").await.unwrap();
+        assert!(peer.is_active);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_and_delivery_between_nodes() {
+        let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let server = NautilusTransport::new(port).await.unwrap();
+        let mut inbound = server.subscribe();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let listening = server.clone();
+        tokio::spawn(async move { listening.start_listeners(shutdown_rx).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let client = NautilusTransport::new(0).await.unwrap();
+        let server_addr = format!("127.0.0.1:{}", port).parse().unwrap();
+        client.connect(server_addr).await.unwrap();
+        client.tcp.send(server_addr, &wire::encode(MessageKind::Data, &Data { payload: b"hello".to_vec() }))
+            .await
+            .unwrap();
+
+        let message = tokio::time::timeout(Duration::from_secs(5), inbound.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.payload, b"hello");
+        assert!(client.get_peers().await.contains(&server_addr.to_string()));
+
+        shutdown_tx.send(true).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_says_goodbye_and_stops_listeners() {
+        let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let server = NautilusTransport::new(port).await.unwrap();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let listening = server.clone();
+        let listeners = tokio::spawn(async move { listening.start_listeners(shutdown_rx).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // A bare peer that completes the handshake and then only listens
+        let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let hello = Handshake { peer_id: "remote".into(), listen_port: 1, ..Default::default() };
+        wire::write_frame(&mut stream, &wire::encode(MessageKind::Handshake, &hello)).await.unwrap();
+        wire::read_frame(&mut stream).await.unwrap().unwrap();
+
+        shutdown_tx.send(true).unwrap();
+        tokio::time::timeout(Duration::from_secs(10), listeners)
+            .await
+            .expect("listeners did not stop")
+            .unwrap()
+            .unwrap();
+
+        let frame = wire::read_frame(&mut stream).await.unwrap().unwrap();
+        let envelope = wire::decode(&frame).unwrap().message;
+        assert_eq!(envelope.kind, MessageKind::Goodbye as i32);
+        assert_eq!(wire::decode_body::<Goodbye>(&envelope).unwrap().message.reason, "shutdown");
+        assert!(wire::read_frame(&mut stream).await.unwrap_or(None).is_none());
+
+        let result = server.send_to_peer("remote", b"late").await;
+        assert!(matches!(result, Err(TransportError::ShuttingDown)));
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_reaps_finished_tasks() {
+        let lifecycle = Lifecycle::new();
+        for _ in 0..100 {
+            lifecycle.spawn(async {});
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        lifecycle.spawn(async {});
+        assert_eq!(lifecycle.task_count(), 1, "finished tasks are dropped on spawn");
+        lifecycle.join(tokio::time::Instant::now() + Duration::from_secs(1)).await;
+        assert_eq!(lifecycle.task_count(), 0);
+    }
+
+    #[cfg(feature = "identity_integration")]
+    #[tokio::test]
+    async fn test_simultaneous_open_keeps_one_connection() {
+        let mut nodes = Vec::new();
+        for _ in 0..2 {
+            let identity = identity::Identity::new(None, None);
+            let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+            let node = NautilusTransport::with_identity(port, &identity).await.unwrap();
+            let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+            let listening = node.clone();
+            tokio::spawn(async move { listening.start_listeners(shutdown_rx).await });
+            let addr: std::net::SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+            nodes.push((node, addr, identity.get_peer_id().to_string(), shutdown_tx));
+        }
+        // Call the node with the smaller ID `a`
+        nodes.sort_by(|x, y| x.2.cmp(&y.2));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let (a, a_addr, a_id, _) = &nodes[0];
+        let (b, b_addr, b_id, _) = &nodes[1];
+        let (dial_a, dial_b) = tokio::join!(a.connect(*b_addr), b.connect(*a_addr));
+        dial_a.unwrap();
+        dial_b.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // `a` has the smaller ID, so the connection it dialed survives on both ends
+        assert_eq!(a.tcp.connected_peers().await, vec![*b_addr]);
+        assert_eq!(a.tcp.peer_connection(b_id).await, Some(*b_addr));
+        let survivor = b.tcp.peer_connection(a_id).await.unwrap();
+        assert_eq!(b.tcp.connected_peers().await, vec![survivor]);
+        assert_ne!(survivor, *a_addr);
+        assert_eq!(b.tcp.connection_addr(*a_addr).await, Some(survivor));
+
+        // Both ends can still reach each other through the dial address
+        b.tcp.send(*a_addr, &wire::encode(MessageKind::Data, &Data { payload: b"hi".to_vec() }))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unproven_claim_does_not_evict() {
+        let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let node = NautilusTransport::new(port).await.unwrap();
+        node.tcp.set_identity("node", "");
+        let (_shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let listening = node.clone();
+        tokio::spawn(async move { listening.start_listeners(shutdown_rx).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // Two bare peers that only claim the same ID: neither pushes the other out
+        let hello = Handshake { peer_id: "claimed".into(), listen_port: 1, ..Default::default() };
+        let mut streams = Vec::new();
+        for _ in 0..2 {
+            let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+            wire::write_frame(&mut stream, &wire::encode(MessageKind::Handshake, &hello)).await.unwrap();
+            wire::read_frame(&mut stream).await.unwrap().unwrap();
+            streams.push(stream);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(node.tcp.connected_peers().await.len(), 2);
+        let first = node.tcp.peer_connection("claimed").await.unwrap();
+        assert_eq!(first, streams[0].local_addr().unwrap());
+
+        // The ID passes to the other connection once the first closes
+        drop(streams.remove(0));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(node.tcp.peer_connection("claimed").await, Some(streams[0].local_addr().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_control_overtakes_queued_bulk_and_expired_sends_drop() {
+        const BULK_FRAMES: usize = 32;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // A slow peer: it lets the socket buffers fill before reading anything
+        let remote = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            wire::read_frame(&mut stream).await.unwrap().unwrap();
+            let ack = Handshake { peer_id: "remote".into(), ..Default::default() };
+            wire::write_frame(&mut stream, &wire::encode(MessageKind::HandshakeAck, &ack)).await.unwrap();
+
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            let mut kinds = Vec::new();
+            for _ in 0..BULK_FRAMES + 1 {
+                let frame = wire::read_frame(&mut stream).await.unwrap().unwrap();
+                kinds.push(wire::decode(&frame).unwrap().message.kind);
+            }
+            kinds
+        });
+
+        let transport = NautilusTransport::new(0).await.unwrap();
+        transport.connect(addr).await.unwrap();
+
+        let bulk = wire::encode(MessageKind::Data, &Data { payload: vec![0; 1024 * 1024] });
+        let mut sends = Vec::new();
+        for _ in 0..BULK_FRAMES {
+            let (transport, bulk) = (transport.clone(), bulk.clone());
+            sends.push(tokio::spawn(async move {
+                transport.tcp.send_with(addr, &bulk, SendOptions::new(Priority::Bulk)).await
+            }));
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let late = SendOptions::new(Priority::Bulk).with_timeout(Duration::from_millis(10));
+        let expired = transport.tcp.send_with(addr, &bulk, late);
+        let ping = wire::encode(MessageKind::Ping, &Ping::default());
+        let control = transport.tcp.send_with(addr, &ping, SendOptions::control());
+        let (expired, control) = tokio::join!(expired, control);
+        assert_eq!(expired.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+        control.unwrap();
+
+        let kinds = remote.await.unwrap();
+        let position = kinds.iter().position(|kind| *kind == MessageKind::Ping as i32).unwrap();
+        assert!(position < BULK_FRAMES / 2, "control frame was written at position {}", position);
+        for send in sends {
+            send.await.unwrap().unwrap();
+        }
+        assert_eq!(transport.tcp.expired_sends(addr).await, 1);
+    }
+}
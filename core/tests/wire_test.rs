@@ -0,0 +1,131 @@
+#[cfg(test)]
+mod tests {
+    use Nautilus_Core::proto::{
+        BlobChunk, BlobManifest, BlobRequest, Data, Goodbye, Handshake, HandshakeProof, MessageKind, PeerExchange,
+        PeerInfo, Ping, Pong, SignedEnvelope, SignedPeerRecord, StreamFrame, TransportEnvelope,
+    };
+    use Nautilus_Core::transport::wire::{self, Preserved, WireMessage};
+    use prost::Message;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let ping = Ping { nonce: 7, timestamp_ms: 42 };
+        let frame = wire::encode(MessageKind::Ping, &ping);
+
+        let envelope = wire::decode(&frame).unwrap().message;
+        assert_eq!(envelope.version, wire::WIRE_VERSION);
+        assert_eq!(envelope.kind, MessageKind::Ping as i32);
+        assert_eq!(wire::decode_body::<Ping>(&envelope).unwrap().message, ping);
+    }
+
+    #[test]
+    fn test_unknown_fields_round_trip() {
+        let handshake = Handshake {
+            peer_id: "peer1".to_string(),
+            listen_port: 8000,
+            ..Default::default()
+        };
+        let mut bytes = handshake.encode_to_vec();
+        // Field 15 as a newer node might send it: tag 15, wire type 2, 3 bytes
+        let future_field = [0x7a, 0x03, b'n', b'e', b'w'];
+        bytes.extend_from_slice(&future_field);
+
+        let decoded = Preserved::<Handshake>::decode(&bytes).unwrap();
+        assert_eq!(decoded.message, handshake);
+        assert_eq!(decoded.unknown_fields(), future_field);
+
+        let reencoded = Preserved::<Handshake>::decode(&decoded.encode_to_vec()).unwrap();
+        assert_eq!(reencoded, decoded);
+    }
+
+    #[test]
+    fn test_peer_info_keeps_signed_record() {
+        let record = SignedPeerRecord {
+            peer_id: "peer1".to_string(),
+            public_key: "key".to_string(),
+            addrs: vec!["127.0.0.1:8000".to_string()],
+            sequence: 3,
+            expires_ms: 1_000,
+            signature: vec![9; 8],
+        };
+        let info = PeerInfo {
+            peer_id: "peer1".to_string(),
+            addrs: record.addrs.clone(),
+            signed_record: record.encode_to_vec(),
+            ..Default::default()
+        };
+
+        let decoded = Preserved::<PeerInfo>::decode(&info.encode_to_vec()).unwrap();
+        assert!(decoded.unknown_fields().is_empty());
+        let reencoded = decoded.encode_to_vec();
+        assert_eq!(reencoded, info.encode_to_vec(), "the signed record is written once");
+        let relayed = PeerInfo::decode(reencoded.as_slice()).unwrap();
+        assert_eq!(SignedPeerRecord::decode(relayed.signed_record.as_slice()).unwrap(), record);
+    }
+
+    #[test]
+    fn test_known_tags_match_schema() {
+        // Field numbers of every message in the schema prost builds from
+        let mut schema: HashMap<&str, Vec<u32>> = HashMap::new();
+        let mut message = None;
+        for line in include_str!("../protocols/transport.proto").lines() {
+            let line = line.split("//").next().unwrap().trim();
+            if let Some(name) = line.strip_prefix("message ") {
+                message = Some(name.trim_end_matches('{').trim());
+                schema.entry(message.unwrap()).or_default();
+            } else if line.starts_with("enum ") || line == "}" {
+                message = None;
+            } else if let (Some(name), Some((_, tag))) = (message, line.split_once('=')) {
+                schema.get_mut(name).unwrap().push(tag.trim_end_matches(';').trim().parse().unwrap());
+            }
+        }
+
+        let known: &[(&str, &[u32])] = &[
+            ("TransportEnvelope", TransportEnvelope::KNOWN_TAGS),
+            ("Handshake", Handshake::KNOWN_TAGS),
+            ("HandshakeProof", HandshakeProof::KNOWN_TAGS),
+            ("Ping", Ping::KNOWN_TAGS),
+            ("Pong", Pong::KNOWN_TAGS),
+            ("PeerInfo", PeerInfo::KNOWN_TAGS),
+            ("PeerExchange", PeerExchange::KNOWN_TAGS),
+            ("Data", Data::KNOWN_TAGS),
+            ("SignedEnvelope", SignedEnvelope::KNOWN_TAGS),
+            ("SignedPeerRecord", SignedPeerRecord::KNOWN_TAGS),
+            ("Goodbye", Goodbye::KNOWN_TAGS),
+            ("BlobManifest", BlobManifest::KNOWN_TAGS),
+            ("BlobRequest", BlobRequest::KNOWN_TAGS),
+            ("BlobChunk", BlobChunk::KNOWN_TAGS),
+            ("StreamFrame", StreamFrame::KNOWN_TAGS),
+        ];
+        for &(name, tags) in known {
+            assert_eq!(schema.get(name).map(Vec::as_slice), Some(tags), "KNOWN_TAGS of {}", name);
+        }
+        let covered: Vec<&str> = known.iter().map(|(name, _)| *name).collect();
+        for name in schema.keys() {
+            assert!(covered.contains(name), "{} has no WireMessage impl", name);
+        }
+    }
+
+    #[test]
+    fn test_rejects_unversioned_envelope() {
+        let envelope = TransportEnvelope {
+            version: 0,
+            kind: MessageKind::Ping as i32,
+            body: Ping { nonce: 1, timestamp_ms: 1 }.encode_to_vec(),
+        };
+        assert!(wire::decode(&envelope.encode_to_vec()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_frames_over_stream() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        wire::write_frame(&mut client, b"first").await.unwrap();
+        wire::write_frame(&mut client, b"second").await.unwrap();
+        drop(client);
+
+        assert_eq!(wire::read_frame(&mut server).await.unwrap().unwrap(), b"first");
+        assert_eq!(wire::read_frame(&mut server).await.unwrap().unwrap(), b"second");
+        assert!(wire::read_frame(&mut server).await.unwrap().is_none());
+    }
+}
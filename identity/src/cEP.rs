@@ -9,9 +9,11 @@ use rsa::{Pkcs1v15Sign, RsaPublicKey};
 use rsa::pkcs1::DecodeRsaPublicKey;
 use chrono::Utc;
 use tokio::net::TcpStream;
+use tracing::{debug, instrument, warn};
 pub struct CEP;
 
 impl CEP {
+    #[instrument(name = "cep_identify", skip(stream, keypair, nonce), fields(peer.id = %peer_id))]
     pub async fn identify<S>(
         stream: &mut S,
         keypair: &KeyPair,
@@ -37,6 +39,7 @@ impl CEP {
         let mut buffer = Vec::new();
         message.encode(&mut buffer)?;
         stream.write_all(&buffer).await?;
+        debug!(bytes = buffer.len(), "sent CEP identification");
         Ok(())
     }
 
@@ -58,9 +61,9 @@ impl CEP {
     
         let mut buffer = Vec::new();
         message.encode(&mut buffer).expect("Encoding failed");
-        println!("Encoded response message size: {}", buffer.len());
     
         stream.write_all(&buffer).await?;
+        debug!(peer.addr = ?stream.peer_addr().ok(), bytes = buffer.len(), "sent CEP response");
         Ok(())
     }
 
@@ -71,6 +74,7 @@ impl CEP {
         let mut buffer = vec![0; 1024];
         let n = stream.read(&mut buffer).await?;
         let message = CepMessage::decode(&buffer[..n])?; // Ensure proper decoding
+        debug!(bytes = n, "received CEP message");
     
         if let Some(CepType::Response(response)) = message.cep_type {
             let decoded_nonce = base64::decode(response.signed_nonce)?;
@@ -79,12 +83,23 @@ impl CEP {
                 rsa::Pkcs1v15Sign::new::<sha2::Sha256>(),
                 expected_nonce.as_bytes(),
                 &decoded_nonce,
-            )?;
+            )
+            .map_err(|e| {
+                warn!(error.kind = "verification", error = %e, "CEP response failed verification");
+                e
+            })?;
+            debug!("verified CEP response");
             Ok(true)
         } else {
+            warn!(error.kind = "invalid_message_type", "expected a CEP response");
             Err(CEPError::InvalidMessageType)
         }
     }
+    #[instrument(
+        name = "cep",
+        skip(stream, keypair),
+        fields(peer.id = %peer_id, peer.addr = ?stream.peer_addr().ok())
+    )]
     pub async fn perform(
         stream: &mut TcpStream,
         keypair: &KeyPair,
@@ -39,9 +39,24 @@ impl KeyPair {
 
     /// Verify a signature using the KeyPair
     pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<bool, String> {
-        match self.algorithm {
-            Algorithm::RSA => RSAKeyPair::verify(self, message, signature),
-            _ => Err(format!("Verification not implemented for {:?}", self.algorithm)),
+        Self::verify_with_public_key(self.algorithm.clone(), &self.public_key, message, signature)
+    }
+
+    /// Verify a signature against a bare public key, e.g. one cached for a remote peer
+    pub fn verify_with_public_key(
+        algorithm: Algorithm,
+        public_key: &str,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, String> {
+        match algorithm {
+            Algorithm::RSA => RSAKeyPair::verify(public_key, message, signature),
+            _ => Err(format!("Verification not implemented for {:?}", algorithm)),
         }
     }
+
+    /// Get the algorithm backing this KeyPair
+    pub fn algorithm(&self) -> &Algorithm {
+        &self.algorithm
+    }
 }
@@ -49,9 +49,9 @@ impl RSAKeyPair {
         Ok(signature)
     }
 
-    /// Verify a signature using RSA
-    pub fn verify(keypair: &KeyPair, message: &[u8], signature: &[u8]) -> Result<bool, String> {
-        let public_key = RsaPublicKey::from_pkcs1_pem(&keypair.public_key)
+    /// Verify a signature using an RSA public key in PKCS#1 PEM form
+    pub fn verify(public_key_pem: &str, message: &[u8], signature: &[u8]) -> Result<bool, String> {
+        let public_key = RsaPublicKey::from_pkcs1_pem(public_key_pem)
             .map_err(|e| format!("Failed to parse public key: {}", e))?;
         let verifying_key = VerifyingKey::<Sha256>::new(public_key);
 
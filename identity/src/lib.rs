@@ -4,12 +4,15 @@ mod peer_id; // generate a peer_id
 mod identity; // Unified Access to the Keypair and Peer_ID
 mod cep_error;
 mod cEP;
+mod log_config;
 
-pub use peer_id::PeerIDGeneration; // Enum Options for PeerID generation
+pub use peer_id::{PeerID, PeerIDGeneration}; // PeerID derivation and its Enum Options
 pub use keypair::{Algorithm,KeyPair}; // Enum Options for the PKI Algo
 pub use identity::Identity;
 pub use cEP::CEP;
 pub use cep_error::CEPError;
+#[cfg(feature = "logging")]
+pub use log_config::setup_logging;
 
 pub mod c_ep { // protocol Contact Exchange Protocol
   include!(concat!(env!("OUT_DIR"), "/cEP.rs"));
@@ -31,3 +31,18 @@ fn test_rsa_sign_and_verify() {
         .expect("Failed to verify the signature");
     assert!(is_valid, "Signature verification failed");
 }
+
+#[test]
+fn test_rsa_verify_with_public_key() {
+    let keypair = KeyPair::generate(Algorithm::RSA);
+    let message = b"Hello, RSA Testing!";
+    let signature = keypair.sign(message).expect("Failed to sign the message");
+
+    // Only the public half is needed to check a remote peer's signature
+    let is_valid = KeyPair::verify_with_public_key(Algorithm::RSA, &keypair.public_key, message, &signature)
+        .expect("Failed to verify the signature");
+    assert!(is_valid, "Signature verification failed");
+
+    let tampered = KeyPair::verify_with_public_key(Algorithm::RSA, &keypair.public_key, b"tampered", &signature);
+    assert!(tampered.is_err(), "Tampered message should not verify");
+}